@@ -0,0 +1,638 @@
+// Copyright 2024, F. Stan
+//
+// Licensed under the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Alarm engine evaluating rules over the SCD30 measurements
+//!
+//! Each [`Rule`](crate::alarms::Rule) has a [`Condition`](crate::alarms::Condition): a
+//! threshold, a range, a rate of change or stale data. A rule is raised when its condition
+//! holds for `raise_after`, and cleared when the value is back past the threshold by
+//! `hysteresis` for `clear_after`. Each raise and clear is an
+//! [`AlarmEvent`](crate::alarms::AlarmEvent), returned by
+//! [`AlarmEngine::update`](crate::alarms::AlarmEngine::update) and sent to the
+//! callbacks and channels of the engine. Stale data is counted from the creation of the
+//! engine until the first measurement arrives. With the `serde` feature the rules can be
+//! loaded from TOML, durations in seconds:
+//!
+//!```toml
+//![[rules]]
+//!name = "co2_high"
+//!kind = "above"
+//!quantity = "co2"
+//!limit = 1400.0
+//!hysteresis = 100.0
+//!raise_after = 600
+//!
+//![[rules]]
+//!name = "humidity_out_of_range"
+//!kind = "outside"
+//!quantity = "humidity"
+//!low = 30.0
+//!high = 60.0
+//!hysteresis = 2.0
+//!
+//![[rules]]
+//!name = "no_data"
+//!kind = "stale"
+//!after = 60
+//!```
+//!
+//!```no_run
+//!use scd30_i2c::alarms::{AlarmEngine, Quantity, Rule};
+//!use scd30_i2c::scd30::Scd30;
+//!use std::time::{Duration, SystemTime};
+//!
+//!let mut engine = AlarmEngine::new(vec![
+//!    Rule::above("co2_high", Quantity::Co2, 1400.0)
+//!        .with_hysteresis(100.0)
+//!        .with_raise_after(Duration::from_secs(600)),
+//!    Rule::stale("no_data", Duration::from_secs(60)),
+//!])
+//!.unwrap();
+//!engine.on_event(|event| println!("{}", event));
+//!
+//!let mut scd = Scd30::new().unwrap();
+//!scd.trigger_cont_measurements().unwrap();
+//!for measurement in scd.measurements() {
+//!    match measurement {
+//!        Ok(m) => engine.update(&m),
+//!        Err(_) => engine.check(SystemTime::now()),
+//!    };
+//!}
+//!```
+
+use crate::measurement::Measurement;
+use std::error::Error;
+use std::fmt;
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, SystemTime};
+
+/// Value of a measurement a rule applies to
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Quantity {
+    /// CO2 concentration in ppm
+    Co2,
+    /// Temperature in Celsius
+    Temperature,
+    /// Relative humidity in %
+    Humidity,
+}
+
+impl Quantity {
+    /// Value of the quantity in the measurement
+    ///
+    pub fn value(&self, measurement: &Measurement) -> f32 {
+        match *self {
+            Quantity::Co2 => measurement.co2_ppm,
+            Quantity::Temperature => measurement.temperature_c,
+            Quantity::Humidity => measurement.humidity_pct,
+        }
+    }
+}
+
+///Implementation of display for Quantity
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Quantity::Co2 => fmt::Display::fmt("CO2", f),
+            Quantity::Temperature => fmt::Display::fmt("temperature", f),
+            Quantity::Humidity => fmt::Display::fmt("humidity", f),
+        }
+    }
+}
+
+/// What raises a rule
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", rename_all = "snake_case"))]
+pub enum Condition {
+    /// Value above the limit, cleared at the limit minus the hysteresis
+    Above { quantity: Quantity, limit: f32 },
+    /// Value below the limit, cleared at the limit plus the hysteresis
+    Below { quantity: Quantity, limit: f32 },
+    /// Value outside the range, cleared when back inside by the hysteresis
+    Outside {
+        quantity: Quantity,
+        low: f32,
+        high: f32,
+    },
+    /// Change of the value between two measurements faster than `per_minute`,
+    /// rising or falling, cleared below `per_minute` minus the hysteresis
+    RateOfChange { quantity: Quantity, per_minute: f32 },
+    /// No measurement for longer than `after`, checked with [`AlarmEngine::check`]
+    /// and cleared by the next measurement
+    Stale {
+        #[cfg_attr(feature = "serde", serde(with = "seconds"))]
+        after: Duration,
+    },
+}
+
+/// Named condition with its hysteresis and minimum durations
+///
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rule {
+    /// Name of the rule, reported in the events
+    pub name: String,
+    /// What raises the rule
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    pub condition: Condition,
+    /// Margin in the unit of the quantity the value must recover past the
+    /// threshold to clear the rule
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub hysteresis: f32,
+    /// Time the condition must hold before raising the rule
+    #[cfg_attr(feature = "serde", serde(default, with = "seconds"))]
+    pub raise_after: Duration,
+    /// Time the value must stay recovered before clearing the rule
+    #[cfg_attr(feature = "serde", serde(default, with = "seconds"))]
+    pub clear_after: Duration,
+}
+
+impl Rule {
+    /// Rule with the condition, without hysteresis nor minimum durations
+    ///
+    pub fn new(name: &str, condition: Condition) -> Rule {
+        Rule {
+            name: name.to_string(),
+            condition,
+            hysteresis: 0.0,
+            raise_after: Duration::ZERO,
+            clear_after: Duration::ZERO,
+        }
+    }
+
+    /// Raised when the quantity is above the limit
+    ///
+    pub fn above(name: &str, quantity: Quantity, limit: f32) -> Rule {
+        Rule::new(name, Condition::Above { quantity, limit })
+    }
+
+    /// Raised when the quantity is below the limit
+    ///
+    pub fn below(name: &str, quantity: Quantity, limit: f32) -> Rule {
+        Rule::new(name, Condition::Below { quantity, limit })
+    }
+
+    /// Raised when the quantity is outside the range from low to high
+    ///
+    pub fn outside(name: &str, quantity: Quantity, low: f32, high: f32) -> Rule {
+        Rule::new(
+            name,
+            Condition::Outside {
+                quantity,
+                low,
+                high,
+            },
+        )
+    }
+
+    /// Raised when the quantity changes faster than `per_minute`
+    ///
+    pub fn rate_of_change(name: &str, quantity: Quantity, per_minute: f32) -> Rule {
+        Rule::new(
+            name,
+            Condition::RateOfChange {
+                quantity,
+                per_minute,
+            },
+        )
+    }
+
+    /// Raised when there are no measurements for longer than `after`
+    ///
+    pub fn stale(name: &str, after: Duration) -> Rule {
+        Rule::new(name, Condition::Stale { after })
+    }
+
+    /// Sets the hysteresis to clear the rule
+    ///
+    pub fn with_hysteresis(mut self, hysteresis: f32) -> Rule {
+        self.hysteresis = hysteresis;
+        self
+    }
+
+    /// Sets the time the condition must hold before raising the rule
+    ///
+    pub fn with_raise_after(mut self, raise_after: Duration) -> Rule {
+        self.raise_after = raise_after;
+        self
+    }
+
+    /// Sets the time the value must stay recovered before clearing the rule
+    ///
+    pub fn with_clear_after(mut self, clear_after: Duration) -> Rule {
+        self.clear_after = clear_after;
+        self
+    }
+
+    /// Checks the limits are numbers, the range is not empty and the
+    /// hysteresis and rate are not negative
+    ///
+    pub fn validate(&self) -> Result<(), AlarmError> {
+        let invalid = |reason| {
+            Err(AlarmError::InvalidRule {
+                rule: self.name.clone(),
+                reason,
+            })
+        };
+        if self.hysteresis.is_nan() || self.hysteresis < 0.0 {
+            return invalid("hysteresis must not be negative");
+        }
+        match self.condition {
+            Condition::Above { limit, .. } | Condition::Below { limit, .. } if limit.is_nan() => {
+                invalid("limit is not a number")
+            }
+            Condition::Outside { low, high, .. }
+                if low.is_nan() || high.is_nan() || low >= high =>
+            {
+                invalid("low must be below high")
+            }
+            Condition::Outside { low, high, .. }
+                if low + self.hysteresis > high - self.hysteresis =>
+            {
+                invalid("hysteresis is wider than the range")
+            }
+            Condition::RateOfChange { per_minute, .. }
+                if per_minute.is_nan() || per_minute <= 0.0 =>
+            {
+                invalid("rate must be positive")
+            }
+            Condition::RateOfChange { per_minute, .. } if self.hysteresis >= per_minute => {
+                invalid("hysteresis is wider than the rate")
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Rules of the engine, to load them from a file
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AlarmConfig {
+    /// Rules evaluated on each measurement
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub rules: Vec<Rule>,
+}
+
+///
+///Alarm error enum. InvalidRule when the values of a rule do not
+///make sense, keeping its name and the reason. DuplicatedRule when
+///two rules have the same name
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AlarmError {
+    /// Rule with invalid values
+    InvalidRule { rule: String, reason: &'static str },
+    /// Two rules with the same name
+    DuplicatedRule(String),
+}
+
+///Implementation of display for AlarmError
+impl fmt::Display for AlarmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AlarmError::InvalidRule { ref rule, reason } => {
+                write!(f, "Invalid rule {}: {}", rule, reason)
+            }
+            AlarmError::DuplicatedRule(ref rule) => write!(f, "Duplicated rule {}", rule),
+        }
+    }
+}
+
+///Implementation for Error to AlarmError
+impl Error for AlarmError {}
+
+/// Raise or clear of a rule. The value is the one of the quantity, the rate
+/// per minute for rate of change rules, or the seconds without measurements
+/// for stale rules.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum AlarmEvent {
+    /// Condition held for the raise time
+    Raised {
+        rule: String,
+        value: f32,
+        time: SystemTime,
+    },
+    /// Value recovered for the clear time
+    Cleared {
+        rule: String,
+        value: f32,
+        time: SystemTime,
+    },
+}
+
+impl AlarmEvent {
+    /// Name of the rule
+    ///
+    pub fn rule(&self) -> &str {
+        match self {
+            AlarmEvent::Raised { rule, .. } | AlarmEvent::Cleared { rule, .. } => rule,
+        }
+    }
+
+    /// If the rule was raised
+    ///
+    pub fn is_raised(&self) -> bool {
+        matches!(self, AlarmEvent::Raised { .. })
+    }
+}
+
+///Implementation of display for AlarmEvent
+impl fmt::Display for AlarmEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AlarmEvent::Raised {
+                ref rule, value, ..
+            } => write!(f, "Alarm {} raised, value {}", rule, value),
+            AlarmEvent::Cleared {
+                ref rule, value, ..
+            } => write!(f, "Alarm {} cleared, value {}", rule, value),
+        }
+    }
+}
+
+/// State of a rule between measurements
+#[derive(Debug, Clone, Default)]
+struct RuleState {
+    active: bool,
+    /// Since when the condition holds, while not raised
+    raising_since: Option<SystemTime>,
+    /// Since when the value is recovered, while raised
+    clearing_since: Option<SystemTime>,
+    /// Previous value and its time, for the rate of change
+    previous: Option<(f32, SystemTime)>,
+}
+
+/// Evaluation of a rule for one value
+enum Evaluation {
+    /// Condition holds
+    Violated,
+    /// Value recovered past the hysteresis
+    Recovered,
+    /// Between the threshold and the hysteresis, or not enough data
+    Unchanged,
+}
+
+/// Receiver of the events of the engine
+type Callback = Box<dyn FnMut(&AlarmEvent) + Send>;
+
+/// Evaluates the rules over the measurements and reports their raises and clears
+///
+pub struct AlarmEngine {
+    rules: Vec<Rule>,
+    states: Vec<RuleState>,
+    started: SystemTime,
+    last_measurement: Option<SystemTime>,
+    callbacks: Vec<Callback>,
+}
+
+impl AlarmEngine {
+    /// Creates the engine, failing if a rule is not valid or two have the same name
+    ///
+    pub fn new(rules: Vec<Rule>) -> Result<AlarmEngine, AlarmError> {
+        for (i, rule) in rules.iter().enumerate() {
+            rule.validate()?;
+            if rules[..i].iter().any(|r| r.name == rule.name) {
+                return Err(AlarmError::DuplicatedRule(rule.name.clone()));
+            }
+        }
+        Ok(AlarmEngine {
+            states: vec![RuleState::default(); rules.len()],
+            rules,
+            started: SystemTime::now(),
+            last_measurement: None,
+            callbacks: Vec::new(),
+        })
+    }
+
+    /// Sets the time the stale data rules count from until the first
+    /// measurement arrives, by default when the engine was created
+    ///
+    pub fn with_start(mut self, started: SystemTime) -> AlarmEngine {
+        self.started = started;
+        self
+    }
+
+    /// Creates the engine with the rules of the configuration
+    ///
+    pub fn from_config(config: AlarmConfig) -> Result<AlarmEngine, AlarmError> {
+        AlarmEngine::new(config.rules)
+    }
+
+    /// Rules evaluated by the engine
+    ///
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    /// Names of the raised rules
+    ///
+    pub fn active(&self) -> Vec<&str> {
+        self.rules
+            .iter()
+            .zip(&self.states)
+            .filter(|(_, state)| state.active)
+            .map(|(rule, _)| rule.name.as_str())
+            .collect()
+    }
+
+    /// Calls the callback with every event
+    ///
+    pub fn on_event<F: FnMut(&AlarmEvent) + Send + 'static>(&mut self, callback: F) {
+        self.callbacks.push(Box::new(callback));
+    }
+
+    /// Channel receiving every event. Events are dropped once the receiver is gone
+    ///
+    pub fn subscribe(&mut self) -> Receiver<AlarmEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.on_event(move |event| {
+            let _ = sender.send(event.clone());
+        });
+        receiver
+    }
+
+    /// Evaluates the rules with the measurement, at its timestamp,
+    /// or now if the measurement has none.
+    /// Returns the events, also sent to the callbacks and channels.
+    ///
+    pub fn update(&mut self, measurement: &Measurement) -> Vec<AlarmEvent> {
+        let time = measurement.timestamp().unwrap_or_else(SystemTime::now);
+        self.last_measurement = Some(time);
+        let mut events = Vec::new();
+        for (rule, state) in self.rules.iter().zip(self.states.iter_mut()) {
+            let (evaluation, value) = match rule.condition {
+                Condition::Above { quantity, limit } => {
+                    let value = quantity.value(measurement);
+                    let evaluation = if value > limit {
+                        Evaluation::Violated
+                    } else if value <= limit - rule.hysteresis {
+                        Evaluation::Recovered
+                    } else {
+                        Evaluation::Unchanged
+                    };
+                    (evaluation, value)
+                }
+                Condition::Below { quantity, limit } => {
+                    let value = quantity.value(measurement);
+                    let evaluation = if value < limit {
+                        Evaluation::Violated
+                    } else if value >= limit + rule.hysteresis {
+                        Evaluation::Recovered
+                    } else {
+                        Evaluation::Unchanged
+                    };
+                    (evaluation, value)
+                }
+                Condition::Outside {
+                    quantity,
+                    low,
+                    high,
+                } => {
+                    let value = quantity.value(measurement);
+                    let evaluation = if value < low || value > high {
+                        Evaluation::Violated
+                    } else if value >= low + rule.hysteresis && value <= high - rule.hysteresis {
+                        Evaluation::Recovered
+                    } else {
+                        Evaluation::Unchanged
+                    };
+                    (evaluation, value)
+                }
+                Condition::RateOfChange {
+                    quantity,
+                    per_minute,
+                } => {
+                    let value = quantity.value(measurement);
+                    let previous = state.previous.replace((value, time));
+                    let minutes = previous.and_then(|(_, at)| {
+                        let minutes = time.duration_since(at).ok()?.as_secs_f32() / 60.0;
+                        (minutes > 0.0).then_some(minutes)
+                    });
+                    match (previous, minutes) {
+                        (Some((previous, _)), Some(minutes)) => {
+                            let rate = (value - previous) / minutes;
+                            let evaluation = if rate.abs() > per_minute {
+                                Evaluation::Violated
+                            } else if rate.abs() <= per_minute - rule.hysteresis {
+                                Evaluation::Recovered
+                            } else {
+                                Evaluation::Unchanged
+                            };
+                            (evaluation, rate)
+                        }
+                        _ => (Evaluation::Unchanged, 0.0),
+                    }
+                }
+                Condition::Stale { .. } => (Evaluation::Recovered, 0.0),
+            };
+            events.extend(step(rule, state, evaluation, value, time));
+        }
+        self.dispatch(&events);
+        events
+    }
+
+    /// Evaluates the stale data rules at the given time, without a new measurement.
+    /// Before the first measurement the data is as old as the engine.
+    /// Call it periodically, for example when reading the device fails.
+    /// Returns the events, also sent to the callbacks and channels.
+    ///
+    pub fn check(&mut self, now: SystemTime) -> Vec<AlarmEvent> {
+        let last = self.last_measurement.unwrap_or(self.started);
+        let age = now.duration_since(last).unwrap_or_default();
+        let mut events = Vec::new();
+        for (rule, state) in self.rules.iter().zip(self.states.iter_mut()) {
+            if let Condition::Stale { after } = rule.condition {
+                let evaluation = if age > after {
+                    Evaluation::Violated
+                } else {
+                    Evaluation::Unchanged
+                };
+                events.extend(step(rule, state, evaluation, age.as_secs_f32(), now));
+            }
+        }
+        self.dispatch(&events);
+        events
+    }
+
+    /// Sends the events to the callbacks and channels
+    fn dispatch(&mut self, events: &[AlarmEvent]) {
+        for event in events {
+            for callback in self.callbacks.iter_mut() {
+                callback(event);
+            }
+        }
+    }
+}
+
+/// Advances the state of the rule with the evaluation, returning the event if
+/// the rule is raised or cleared
+fn step(
+    rule: &Rule,
+    state: &mut RuleState,
+    evaluation: Evaluation,
+    value: f32,
+    time: SystemTime,
+) -> Option<AlarmEvent> {
+    let held = |since: SystemTime| time.duration_since(since).unwrap_or_default();
+    if state.active {
+        match evaluation {
+            Evaluation::Recovered => {
+                let since = *state.clearing_since.get_or_insert(time);
+                if held(since) >= rule.clear_after {
+                    state.active = false;
+                    state.clearing_since = None;
+                    return Some(AlarmEvent::Cleared {
+                        rule: rule.name.clone(),
+                        value,
+                        time,
+                    });
+                }
+            }
+            _ => state.clearing_since = None,
+        }
+    } else {
+        match evaluation {
+            Evaluation::Violated => {
+                let since = *state.raising_since.get_or_insert(time);
+                if held(since) >= rule.raise_after {
+                    state.active = true;
+                    state.raising_since = None;
+                    return Some(AlarmEvent::Raised {
+                        rule: rule.name.clone(),
+                        value,
+                        time,
+                    });
+                }
+            }
+            _ => state.raising_since = None,
+        }
+    }
+    None
+}
+
+/// Durations serialized as seconds
+#[cfg(feature = "serde")]
+mod seconds {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(duration.as_secs_f64())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        let seconds = f64::deserialize(deserializer)?;
+        Duration::try_from_secs_f64(seconds).map_err(de::Error::custom)
+    }
+}
@@ -0,0 +1,415 @@
+// Copyright 2024, F. Stan
+//
+// Licensed under the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Async version of the SCD30 driver
+//!
+//! Same operations as [`Scd30`](crate::scd30::Scd30), over any bus implementing the
+//! embedded-hal-async `I2c` trait. All the delays are awaited, so the executor is
+//! never blocked. With the `tokio` feature the Linux bus, whose blocking transfers run
+//! with `spawn_blocking`, and a tokio delay provider are available.
+//!
+//!```no_run
+//!# #[cfg(feature = "tokio")]
+//!# async fn run() {
+//!use futures_util::StreamExt;
+//!use scd30_i2c::asynch::Scd30Async;
+//!
+//!let mut scd = Scd30Async::open("/dev/i2c-1", 0x61).unwrap();
+//!scd.trigger_cont_measurements().await.unwrap();
+//!
+//!let measurements = scd.measurements();
+//!futures_util::pin_mut!(measurements);
+//!while let Some(measurement) = measurements.next().await {
+//!    println!("{:?}", measurement);
+//!}
+//!# }
+//!```
+
+use crate::measurement::Measurement;
+use crate::scd30::{
+    check_forced_value, check_interval, check_pressure, command_frame, offset_ticks, Scd30Error,
+    Timing, DEFAULT_ADDRESS, RESPONSE_LEN,
+};
+use crate::sensirion_frame;
+use crate::stream::{self as sync_stream, RecoveryPolicy, StreamState, DEFAULT_POLL_INTERVAL};
+use core::time::Duration;
+use embedded_hal_async::delay::DelayNs;
+use embedded_hal_async::i2c::I2c;
+use futures_util::stream::{self, Stream};
+
+#[cfg(feature = "tokio")]
+use crate::linux::{LinuxI2c, TokioDelay, TokioI2c};
+#[cfg(feature = "tokio")]
+use crate::scd30::DEFAULT_BUS;
+#[cfg(feature = "tokio")]
+use i2cdev::linux::{LinuxI2CDevice, LinuxI2CError};
+#[cfg(feature = "tokio")]
+use std::path::Path;
+
+/// Async SCD30 Struct, wraps any I2C bus implementing the embedded-hal-async
+/// I2C trait and an async delay provider
+///
+pub struct Scd30Async<I, D> {
+    pub i2c: I,
+    pub delay: D,
+    pub address: u8,
+    pub timing: Timing,
+}
+
+/// Linux specific constructors, using i2cdev on the blocking threads of
+/// tokio as I2C bus and tokio sleep as delay provider
+///
+#[cfg(feature = "tokio")]
+impl Scd30Async<TokioI2c, TokioDelay> {
+    /// Create a new async SCD30 Struct
+    ///
+    /// Tries to create the device on bus /dev/i2c-1 and standard address 0x61.
+    /// If fails, return an LinuxI2CError from i2cdev
+    ///
+    pub fn new() -> Result<Scd30Async<TokioI2c, TokioDelay>, LinuxI2CError> {
+        Scd30Async::open(DEFAULT_BUS, DEFAULT_ADDRESS)
+    }
+
+    /// Create a new async SCD30 Struct on the given bus path and address
+    ///
+    /// If fails, return an LinuxI2CError from i2cdev
+    ///
+    pub fn open<P: AsRef<Path>>(
+        path: P,
+        address: u8,
+    ) -> Result<Scd30Async<TokioI2c, TokioDelay>, LinuxI2CError> {
+        let device = LinuxI2CDevice::new(path, u16::from(address))?;
+        Ok(Scd30Async::from_i2c_with_address(
+            TokioI2c::new(LinuxI2c::new(device, address)),
+            TokioDelay,
+            address,
+        ))
+    }
+}
+
+impl<I, D> Scd30Async<I, D> {
+    /// Create a new async SCD30 Struct from any I2C bus and delay provider
+    ///
+    /// The device is expected on standard address 0x61.
+    ///
+    pub fn from_i2c(i2c: I, delay: D) -> Scd30Async<I, D> {
+        Scd30Async::from_i2c_with_address(i2c, delay, DEFAULT_ADDRESS)
+    }
+
+    /// Create a new async SCD30 Struct from any I2C bus and delay provider,
+    /// with the device on the given address.
+    ///
+    pub fn from_i2c_with_address(i2c: I, delay: D, address: u8) -> Scd30Async<I, D> {
+        Scd30Async {
+            i2c,
+            delay,
+            address,
+            timing: Timing::default(),
+        }
+    }
+
+    /// Changes the waits around the I2C transactions
+    ///
+    pub fn with_timing(mut self, timing: Timing) -> Scd30Async<I, D> {
+        self.timing = timing;
+        self
+    }
+
+    /// Releases the I2C bus and the delay provider
+    ///
+    pub fn release(self) -> (I, D) {
+        (self.i2c, self.delay)
+    }
+}
+
+/// Implementation of SCD30 related
+/// operations over the async I2C bus
+///
+impl<I: I2c, D: DelayNs> Scd30Async<I, D> {
+    /// Sleeps the given duration, if any
+    async fn wait(&mut self, duration: Duration) {
+        if !duration.is_zero() {
+            let us = u32::try_from(duration.as_micros()).unwrap_or(u32::MAX);
+            self.delay.delay_us(us).await;
+        }
+    }
+
+    /// Writes a command word, with its argument and checksum if any,
+    /// and waits for the device to process it
+    async fn send(
+        &mut self,
+        command: u16,
+        argument: Option<u16>,
+    ) -> Result<(), Scd30Error<I::Error>> {
+        self.write_command(command, argument).await?;
+        self.wait(self.timing.write_delay).await;
+        Ok(())
+    }
+
+    /// Writes a command word, with its argument and checksum if any
+    async fn write_command(
+        &mut self,
+        command: u16,
+        argument: Option<u16>,
+    ) -> Result<(), Scd30Error<I::Error>> {
+        let (frame, len) = command_frame(command, argument);
+        self.i2c
+            .write(self.address, &frame[..len])
+            .await
+            .map_err(|e| Scd30Error::write(command, e))
+    }
+
+    /// Writes a command word and reads `words.len()` data words,
+    /// checking the checksum of each one
+    async fn read_words(
+        &mut self,
+        command: u16,
+        words: &mut [u16],
+    ) -> Result<(), Scd30Error<I::Error>> {
+        self.write_command(command, None).await?;
+        self.wait(self.timing.read_delay).await;
+        let mut data_buffer = [0; RESPONSE_LEN];
+        let data_buffer = &mut data_buffer[..sensirion_frame::frame_len(words.len())];
+        match self.i2c.read(self.address, data_buffer).await {
+            Ok(_) => Ok(sensirion_frame::decode_words(data_buffer, words)?),
+            Err(e) => Err(Scd30Error::read(command, e)),
+        }
+    }
+
+    /// Writes a command word and reads one data word
+    async fn read_word(&mut self, command: u16) -> Result<u16, Scd30Error<I::Error>> {
+        let mut words = [0; 1];
+        self.read_words(command, &mut words).await?;
+        Ok(words[0])
+    }
+
+    /// Checks the firmware version of the SCD30 device.
+    /// If fails, return SCD30Error.
+    /// Else returns the firmware version.
+    ///
+    pub async fn check_firmware(&mut self) -> Result<u16, Scd30Error<I::Error>> {
+        self.read_word(0xD100).await
+    }
+
+    /// Trigger the continous measurements for SCD30 device.
+    /// Ambient pressure compensation is disabled.
+    ///
+    pub async fn trigger_cont_measurements(&mut self) -> Result<(), Scd30Error<I::Error>> {
+        self.trigger_cont_measurements_with_pressure(0).await
+    }
+
+    /// Trigger the continous measurements for SCD30 device
+    /// with ambient pressure compensation.
+    /// Pressure is in mbar, between 700 and 1400, 0 disables the compensation.
+    ///
+    pub async fn trigger_cont_measurements_with_pressure(
+        &mut self,
+        pressure: u16,
+    ) -> Result<(), Scd30Error<I::Error>> {
+        check_pressure(pressure)?;
+        self.send(0x0010, Some(pressure)).await
+    }
+
+    /// Updates the ambient pressure used for compensation while the
+    /// continous measurements are running.
+    ///
+    pub async fn set_ambient_pressure(
+        &mut self,
+        pressure: u16,
+    ) -> Result<(), Scd30Error<I::Error>> {
+        self.trigger_cont_measurements_with_pressure(pressure).await
+    }
+
+    /// Stops the continous measurements for SCD30 device.
+    ///
+    pub async fn stop_cont_measurements(&mut self) -> Result<(), Scd30Error<I::Error>> {
+        self.send(0x0104, None).await
+    }
+
+    /// Sets the measurements interval for the device, between 2 and 1800 seconds.
+    ///
+    pub async fn set_measurements_interval(
+        &mut self,
+        seconds: u16,
+    ) -> Result<(), Scd30Error<I::Error>> {
+        check_interval(seconds)?;
+        self.send(0x4600, Some(seconds)).await
+    }
+
+    /// Gets the measurements interval of the device in seconds.
+    ///
+    pub async fn get_measurements_interval(&mut self) -> Result<u16, Scd30Error<I::Error>> {
+        self.read_word(0x4600).await
+    }
+
+    /// Gets if the device is ready for reading a measurement.
+    ///
+    pub async fn get_data_ready(&mut self) -> Result<bool, Scd30Error<I::Error>> {
+        Ok(self.read_word(0x0202).await? == 0x01)
+    }
+
+    /// Get CO2, Temperature and Humidity for the device as a Measurement.
+    ///
+    pub async fn get_measurements(&mut self) -> Result<Measurement, Scd30Error<I::Error>> {
+        let mut words = [0; 6];
+        self.read_words(0x0300, &mut words).await?;
+        Ok(Measurement::from_words(&words))
+    }
+
+    /// Gets if the devive is in self calibration procedure or not.
+    ///
+    pub async fn get_self_calibration_status(&mut self) -> Result<bool, Scd30Error<I::Error>> {
+        Ok(self.read_word(0x5306).await? == 0x01)
+    }
+
+    /// Set self calibration configuration.
+    ///
+    pub async fn set_self_calibration(&mut self, active: bool) -> Result<(), Scd30Error<I::Error>> {
+        self.send(0x5306, Some(u16::from(active))).await
+    }
+
+    /// Soft reset the sensor device.
+    ///
+    pub async fn soft_reset(&mut self) -> Result<(), Scd30Error<I::Error>> {
+        self.write_command(0xD304, None).await?;
+        // The device does not answer until it boots again
+        self.wait(self.timing.boot_time).await;
+        Ok(())
+    }
+
+    /// Gets the set altitude of the device in meters from sea level.
+    ///
+    pub async fn get_altitude(&mut self) -> Result<u16, Scd30Error<I::Error>> {
+        self.read_word(0x5102).await
+    }
+
+    /// Sets the altitude for the device in meters from sea level.
+    ///
+    pub async fn set_altitude(&mut self, altitude: u16) -> Result<(), Scd30Error<I::Error>> {
+        self.send(0x5102, Some(altitude)).await
+    }
+
+    /// Gets the temperature offset of the device in ticks of 0.01 Celsius.
+    ///
+    pub async fn get_temperature_offset(&mut self) -> Result<u16, Scd30Error<I::Error>> {
+        self.read_word(0x5403).await
+    }
+
+    /// Sets the temperature offset of the device in ticks of 0.01 Celsius.
+    ///
+    pub async fn set_temperature_offset(
+        &mut self,
+        offset: u16,
+    ) -> Result<(), Scd30Error<I::Error>> {
+        self.send(0x5403, Some(offset)).await
+    }
+
+    /// Gets the temperature offset of the device in Celsius.
+    ///
+    pub async fn get_temperature_offset_celsius(&mut self) -> Result<f32, Scd30Error<I::Error>> {
+        Ok(f32::from(self.get_temperature_offset().await?) / 100.0)
+    }
+
+    /// Sets the temperature offset of the device in Celsius, rounded to the
+    /// 0.01 Celsius ticks of the device, between 0 and 655.35.
+    ///
+    pub async fn set_temperature_offset_celsius(
+        &mut self,
+        offset_c: f32,
+    ) -> Result<(), Scd30Error<I::Error>> {
+        let ticks = offset_ticks(offset_c)?;
+        self.set_temperature_offset(ticks).await
+    }
+
+    /// Checks the forced calibration value of the device in ppm.
+    ///
+    pub async fn get_forced_value(&mut self) -> Result<u16, Scd30Error<I::Error>> {
+        self.read_word(0x5204).await
+    }
+
+    /// Sets a force recalibration value to the device in ppm, between 400 and 2000.
+    ///
+    pub async fn set_force_recalibration_value(
+        &mut self,
+        forced_value: u16,
+    ) -> Result<(), Scd30Error<I::Error>> {
+        check_forced_value(forced_value)?;
+        self.send(0x5204, Some(forced_value)).await
+    }
+
+    /// Sleeps the given duration in milliseconds, if any
+    async fn sleep(&mut self, duration: Duration) {
+        if !duration.is_zero() {
+            self.delay.delay_ms(sync_stream::millis(duration)).await;
+        }
+    }
+
+    /// Async counterpart of the blocking loop of [`StreamState`], it can not be
+    /// shared since the device operations are awaited
+    async fn next_measurement(
+        &mut self,
+        state: &mut StreamState,
+    ) -> Result<Measurement, Scd30Error<I::Error>> {
+        let interval = match state.interval() {
+            Some(interval) => interval,
+            None => self.get_measurements_interval().await?,
+        };
+        state.begin_read(interval);
+        while !self.get_data_ready().await? {
+            let wait = state.not_ready()?;
+            self.sleep(wait).await;
+        }
+        self.get_measurements().await
+    }
+
+    /// Async stream of measurements, with the default [`RecoveryPolicy`].
+    ///
+    /// Same behaviour as the blocking [`Measurements`](crate::stream::Measurements)
+    /// iterator, each item waits for the data ready flag of the device.
+    ///
+    pub fn measurements(
+        &mut self,
+    ) -> impl Stream<Item = Result<Measurement, Scd30Error<I::Error>>> + '_ {
+        self.measurements_with_policy(RecoveryPolicy::default())
+    }
+
+    /// Async stream of measurements, with the given [`RecoveryPolicy`].
+    ///
+    pub fn measurements_with_policy(
+        &mut self,
+        policy: RecoveryPolicy,
+    ) -> impl Stream<Item = Result<Measurement, Scd30Error<I::Error>>> + '_ {
+        self.measurements_with_options(policy, DEFAULT_POLL_INTERVAL)
+    }
+
+    /// Async stream of measurements, with the given [`RecoveryPolicy`]
+    /// and time between data ready checks.
+    ///
+    pub fn measurements_with_options(
+        &mut self,
+        policy: RecoveryPolicy,
+        poll_interval: Duration,
+    ) -> impl Stream<Item = Result<Measurement, Scd30Error<I::Error>>> + '_ {
+        let state = StreamState::new(policy, poll_interval);
+        stream::unfold((self, state), |(scd, mut state)| async move {
+            let wait = state.start()?;
+            scd.sleep(wait).await;
+            loop {
+                match scd.next_measurement(&mut state).await {
+                    Ok(measurement) => {
+                        state.succeeded();
+                        return Some((Ok(measurement), (scd, state)));
+                    }
+                    Err(e) => match state.failed() {
+                        Some(wait) => scd.sleep(wait).await,
+                        None => return Some((Err(e), (scd, state))),
+                    },
+                }
+            }
+        })
+    }
+}
@@ -0,0 +1,195 @@
+// Copyright 2024, F. Stan
+//
+// Licensed under the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Command line tool to inspect and configure a SCD30 device
+
+use clap::Parser;
+use scd30_i2c::cli::{Action, AscAction, Cli, Command, OffsetAction};
+use scd30_i2c::linux::{LinuxI2c, StdDelay};
+use scd30_i2c::measurement::Measurement;
+use scd30_i2c::scd30::Scd30;
+use scd30_i2c::stream::RecoveryPolicy;
+use serde_json::{json, Value};
+use std::error::Error;
+use std::process::ExitCode;
+use std::time::Duration;
+
+/// Prints a value in human readable or JSON format
+fn print(json: bool, value: Value, human: String) {
+    if json {
+        println!("{}", value);
+    } else {
+        println!("{}", human);
+    }
+}
+
+/// Prints a measurement in human readable or JSON format
+fn print_measurement(json: bool, measurement: &Measurement) {
+    let timestamp = measurement.timestamp_ms.map(|ms| ms as f64 / 1000.0);
+    print(
+        json,
+        json!({
+            "co2_ppm": measurement.co2_ppm,
+            "temperature_c": measurement.temperature_c,
+            "humidity_pct": measurement.humidity_pct,
+            "timestamp": timestamp,
+        }),
+        format!(
+            "Co2: {:.1} ppm Temp: {:.2} C RH: {:.2} %",
+            measurement.co2_ppm, measurement.temperature_c, measurement.humidity_pct
+        ),
+    );
+}
+
+/// Prints the confirmation of a write
+fn print_done(json: bool, what: &str) {
+    print(json, json!({ "ok": true }), what.to_string());
+}
+
+/// Prints an error, as JSON on standard output or as text on standard error
+fn print_error(json: bool, error: &dyn Error) {
+    if json {
+        println!("{}", json!({ "ok": false, "error": error.to_string() }));
+    } else {
+        eprintln!("error: {}", error);
+    }
+}
+
+fn run(cli: Cli) -> Result<(), Box<dyn Error>> {
+    let mut scd: Scd30<LinuxI2c, StdDelay> = Scd30::open(&cli.bus, cli.address)
+        .map_err(|e| format!("cannot open {}: {}", cli.bus, e))?;
+    let json = cli.json;
+    match cli.command {
+        Command::Read => {
+            let measurement = scd
+                .measurements()
+                .with_policy(RecoveryPolicy::Stop)
+                .next()
+                .ok_or("no measurement")??;
+            print_measurement(json, &measurement);
+        }
+        Command::Watch { count } => {
+            let policy = RecoveryPolicy::Continue {
+                wait: Duration::from_secs(10),
+            };
+            let measurements = scd.measurements().with_policy(policy);
+            for measurement in measurements.take(count.unwrap_or(usize::MAX)) {
+                match measurement {
+                    Ok(measurement) => print_measurement(json, &measurement),
+                    Err(e) => print_error(json, &e),
+                }
+            }
+        }
+        Command::Firmware => {
+            let [major, minor] = scd.check_firmware()?.to_be_bytes();
+            print(
+                json,
+                json!({ "major": major, "minor": minor }),
+                format!("{}.{}", major, minor),
+            );
+        }
+        Command::Interval { action } => match action {
+            Action::Get => {
+                let interval = scd.get_measurements_interval()?;
+                print(
+                    json,
+                    json!({ "interval_s": interval }),
+                    format!("{} s", interval),
+                );
+            }
+            Action::Set { value } => {
+                scd.set_measurements_interval(value)?;
+                print_done(json, "Interval set");
+            }
+        },
+        Command::Altitude { action } => match action {
+            Action::Get => {
+                let altitude = scd.get_altitude()?;
+                print(
+                    json,
+                    json!({ "altitude_m": altitude }),
+                    format!("{} m", altitude),
+                );
+            }
+            Action::Set { value } => {
+                scd.set_altitude(value)?;
+                print_done(json, "Altitude set");
+            }
+        },
+        Command::TempOffset { action } => match action {
+            OffsetAction::Get => {
+                let offset = scd.get_temperature_offset_celsius()?;
+                print(
+                    json,
+                    json!({ "offset_c": offset }),
+                    format!("{:.2} C", offset),
+                );
+            }
+            OffsetAction::Set { value } => {
+                scd.set_temperature_offset_celsius(value)?;
+                print_done(json, "Temperature offset set");
+            }
+        },
+        Command::Asc { action } => match action {
+            AscAction::On => {
+                scd.set_self_calibration(true)?;
+                print_done(json, "Automatic self calibration on");
+            }
+            AscAction::Off => {
+                scd.set_self_calibration(false)?;
+                print_done(json, "Automatic self calibration off");
+            }
+            AscAction::Status => {
+                let active = scd.get_self_calibration_status()?;
+                print(
+                    json,
+                    json!({ "asc": active }),
+                    (if active { "on" } else { "off" }).to_string(),
+                );
+            }
+        },
+        Command::Frc { action } => match action {
+            Action::Get => {
+                let forced_value = scd.get_forced_value()?;
+                print(
+                    json,
+                    json!({ "frc_ppm": forced_value }),
+                    format!("{} ppm", forced_value),
+                );
+            }
+            Action::Set { value } => {
+                scd.set_force_recalibration_value(value)?;
+                print_done(json, "Forced recalibration value set");
+            }
+        },
+        Command::Reset => {
+            scd.soft_reset()?;
+            print_done(json, "Reset done");
+        }
+        Command::Start { pressure } => {
+            scd.trigger_cont_measurements_with_pressure(pressure)?;
+            print_done(json, "Continuous measurements started");
+        }
+        Command::Stop => {
+            scd.stop_cont_measurements()?;
+            print_done(json, "Continuous measurements stopped");
+        }
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let json = cli.json;
+    match run(cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            print_error(json, e.as_ref());
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,334 @@
+// Copyright 2024, F. Stan
+//
+// Licensed under the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Guided calibration procedures for the SCD30 device
+//!
+//! The forced recalibration (FRC) must only be applied after the device has been
+//! measuring continuously for at least 2 minutes in a stable environment, with a
+//! reference value between 400 and 2000 ppm.
+//! [`Scd30::force_recalibration`](crate::scd30::Scd30::force_recalibration) runs the
+//! whole procedure: starts the continuous measurements, waits for the CO2 readings to
+//! settle, applies the reference value and verifies it.
+//!
+//! The temperature offset is subtracted from the measured temperature and adds to the
+//! one already stored in the device.
+//! [`Scd30::calibrate_temperature_offset`](crate::scd30::Scd30::calibrate_temperature_offset)
+//! averages a window of readings and computes the new offset from a reference thermometer.
+//!
+//!```no_run
+//!use scd30_i2c::calibration::FrcOptions;
+//!use scd30_i2c::scd30::Scd30;
+//!
+//!let mut scd = Scd30::new().unwrap();
+//!// Device outdoors, fresh air is around 420 ppm
+//!let report = scd.force_recalibration(420, &FrcOptions::default()).unwrap();
+//!println!("Calibrated after {:?}, CO2 was {} ppm", report.settled_after, report.co2_before);
+//!
+//!// Reference thermometer next to the device reads 21.3 Celsius
+//!let report = scd.calibrate_temperature_offset(21.3, 10).unwrap();
+//!println!("Temperature offset is now {} C", report.offset_c);
+//!```
+
+use crate::scd30::{
+    offset_ticks, Scd30, Scd30Error, MAX_FORCED_RECALIBRATION_VALUE, MIN_FORCED_RECALIBRATION_VALUE,
+};
+use crate::stream::RecoveryPolicy;
+use embedded_hal::delay::DelayNs;
+use embedded_hal::i2c::I2c;
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+pub use crate::scd30::MAX_TEMPERATURE_OFFSET;
+
+/// Measurement interval recommended by the interface description for the
+/// forced recalibration, in seconds
+pub const FRC_INTERVAL: u16 = 2;
+/// Minimum time of continuous measurements before a forced recalibration
+pub const FRC_MIN_SETTLE_TIME: Duration = Duration::from_secs(120);
+
+/// Options of the forced recalibration procedure
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrcOptions {
+    /// Measurement interval in seconds used during the procedure
+    pub interval: u16,
+    /// Ambient pressure in mbar for the continuous measurements, 0 to disable
+    pub pressure: u16,
+    /// Time the CO2 readings must stay within the tolerance,
+    /// at least [`FRC_MIN_SETTLE_TIME`]
+    pub settle_time: Duration,
+    /// Maximum difference in ppm between the readings of the settle window
+    pub tolerance_ppm: f32,
+    /// Time to wait for the readings to settle before giving up
+    pub timeout: Duration,
+}
+
+/// Default options: 2 seconds interval, no pressure compensation, readings within
+/// 20 ppm for 2 minutes, giving up after 10 minutes
+impl Default for FrcOptions {
+    fn default() -> Self {
+        FrcOptions {
+            interval: FRC_INTERVAL,
+            pressure: 0,
+            settle_time: FRC_MIN_SETTLE_TIME,
+            tolerance_ppm: 20.0,
+            timeout: Duration::from_secs(600),
+        }
+    }
+}
+
+/// What the forced recalibration procedure did
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrcReport {
+    /// Reference value applied, in ppm
+    pub reference_ppm: u16,
+    /// Value reported by the device after the calibration, in ppm
+    pub verified_ppm: u16,
+    /// Average CO2 of the settle window before the calibration, in ppm
+    pub co2_before: f32,
+    /// Lowest and highest CO2 of the settle window, in ppm
+    pub co2_range: (f32, f32),
+    /// Measurements read during the procedure
+    pub readings: usize,
+    /// Time of continuous measurements until the readings settled
+    pub settled_after: Duration,
+    /// Measurement interval in seconds used during the procedure
+    pub interval: u16,
+}
+
+/// What the temperature offset calibration did
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OffsetReport {
+    /// Temperature of the reference thermometer, in Celsius
+    pub reference_c: f32,
+    /// Average temperature measured by the device before the calibration, in Celsius
+    pub measured_c: f32,
+    /// Offset stored in the device before the calibration, in Celsius
+    pub previous_offset_c: f32,
+    /// New offset applied to the device, in Celsius
+    pub offset_c: f32,
+    /// Measurements averaged
+    pub readings: usize,
+}
+
+///
+///Calibration error enum. Device when an operation with the
+///SCD30 fails. OutOfRange when the reference value is outside
+///the range accepted by the device. NotSettled when the readings
+///did not stay within the tolerance before the timeout, the
+///calibration is not applied. VerificationFailed when the device
+///reports a different value after the calibration. InvalidOffset
+///when the temperature offset is negative or too big for the device.
+///SettleTimeTooShort when the options skip the minimum settle time.
+///NoReadings when the temperature offset window has no readings
+///
+#[derive(Debug)]
+pub enum CalibrationError<E> {
+    /// Error from the device
+    Device(Scd30Error<E>),
+    /// Reference value outside the valid range, nothing is sent to the device
+    OutOfRange { value: u16, min: u16, max: u16 },
+    /// Readings did not settle, keeps the spread in ppm of the last window
+    NotSettled { readings: usize, spread_ppm: f32 },
+    /// Value read back from the device does not match the applied one
+    VerificationFailed { expected: u16, actual: u16 },
+    /// Temperature offset in Celsius the device can not store, nothing is sent to the device
+    InvalidOffset { offset_c: f32 },
+    /// Settle time shorter than [`FRC_MIN_SETTLE_TIME`], nothing is sent to the device
+    SettleTimeTooShort { settle_time: Duration },
+    /// Temperature offset calibration asked to average no readings, nothing is sent to the device
+    NoReadings,
+}
+
+///Implementation for Scd30Error to CalibrationError
+impl<E> From<Scd30Error<E>> for CalibrationError<E> {
+    fn from(e: Scd30Error<E>) -> Self {
+        CalibrationError::Device(e)
+    }
+}
+
+///Implementation of display for CalibrationError
+impl<E> fmt::Display for CalibrationError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CalibrationError::Device(ref e) => fmt::Display::fmt(e, f),
+            CalibrationError::OutOfRange { value, min, max } => write!(
+                f,
+                "Reference value {} ppm out of range, valid range is {} to {}",
+                value, min, max
+            ),
+            CalibrationError::NotSettled {
+                readings,
+                spread_ppm,
+            } => write!(
+                f,
+                "CO2 did not settle after {} readings, last spread was {} ppm",
+                readings, spread_ppm
+            ),
+            CalibrationError::VerificationFailed { expected, actual } => write!(
+                f,
+                "Calibration not applied, expected {} ppm but device reports {} ppm",
+                expected, actual
+            ),
+            CalibrationError::InvalidOffset { offset_c } => write!(
+                f,
+                "Temperature offset {} C not valid, valid range is 0 to {}",
+                offset_c, MAX_TEMPERATURE_OFFSET
+            ),
+            CalibrationError::SettleTimeTooShort { settle_time } => write!(
+                f,
+                "Settle time {:?} too short, the minimum is {:?}",
+                settle_time, FRC_MIN_SETTLE_TIME
+            ),
+            CalibrationError::NoReadings => fmt::Display::fmt(
+                "Temperature offset calibration needs at least one reading",
+                f,
+            ),
+        }
+    }
+}
+
+///Implementation for Error to CalibrationError, the source is the device error
+impl<E: Error + 'static> Error for CalibrationError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            CalibrationError::Device(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl<I: I2c, D: DelayNs> Scd30<I, D> {
+    /// Runs the forced recalibration procedure with the given reference CO2 in ppm.
+    ///
+    /// Sets the measurement interval, triggers the continuous measurements and waits
+    /// until the readings of a whole settle window are within the tolerance. A settle
+    /// time under the 2 minutes of the interface description fails before writing to
+    /// the device. If the readings do not settle before the timeout, fails without
+    /// calibrating. Else applies the reference value, reads it back and returns a
+    /// report. The continuous measurements are left running.
+    ///
+    pub fn force_recalibration(
+        &mut self,
+        reference_ppm: u16,
+        options: &FrcOptions,
+    ) -> Result<FrcReport, CalibrationError<I::Error>> {
+        if !(MIN_FORCED_RECALIBRATION_VALUE..=MAX_FORCED_RECALIBRATION_VALUE)
+            .contains(&reference_ppm)
+        {
+            return Err(CalibrationError::OutOfRange {
+                value: reference_ppm,
+                min: MIN_FORCED_RECALIBRATION_VALUE,
+                max: MAX_FORCED_RECALIBRATION_VALUE,
+            });
+        }
+        if options.settle_time < FRC_MIN_SETTLE_TIME {
+            return Err(CalibrationError::SettleTimeTooShort {
+                settle_time: options.settle_time,
+            });
+        }
+        self.set_measurements_interval(options.interval)?;
+        self.trigger_cont_measurements_with_pressure(options.pressure)?;
+
+        // Readings covering the settle time, and the most that fit in the timeout
+        let interval = Duration::from_secs(u64::from(options.interval));
+        let window = windows(options.settle_time, interval).max(2);
+        let limit = windows(options.timeout, interval).max(window);
+
+        let policy = RecoveryPolicy::Retry {
+            attempts: 3,
+            wait: interval,
+        };
+        let mut co2 = Vec::with_capacity(limit);
+        let mut spread = f32::INFINITY;
+        for measurement in self.measurements().with_policy(policy).take(limit) {
+            co2.push(measurement?.co2_ppm);
+            if co2.len() < window {
+                continue;
+            }
+            let last = &co2[co2.len() - window..];
+            let min = last.iter().copied().fold(f32::INFINITY, f32::min);
+            let max = last.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+            spread = max - min;
+            if spread <= options.tolerance_ppm {
+                let co2_before = last.iter().sum::<f32>() / window as f32;
+                let readings = co2.len();
+                self.set_force_recalibration_value(reference_ppm)?;
+                let verified_ppm = self.get_forced_value()?;
+                if verified_ppm != reference_ppm {
+                    return Err(CalibrationError::VerificationFailed {
+                        expected: reference_ppm,
+                        actual: verified_ppm,
+                    });
+                }
+                return Ok(FrcReport {
+                    reference_ppm,
+                    verified_ppm,
+                    co2_before,
+                    co2_range: (min, max),
+                    readings,
+                    settled_after: interval * readings as u32,
+                    interval: options.interval,
+                });
+            }
+        }
+        Err(CalibrationError::NotSettled {
+            readings: co2.len(),
+            spread_ppm: spread,
+        })
+    }
+
+    /// Runs the temperature offset calibration with the temperature in Celsius of a
+    /// reference thermometer next to the device.
+    ///
+    /// Averages the given number of readings and adds the difference with the reference
+    /// to the offset already stored in the device. Fails without changing the offset if
+    /// the result is negative, the device can only lower the measured temperature,
+    /// and without reading the device if the number of readings is 0.
+    /// The continous measurements must be already triggered.
+    ///
+    pub fn calibrate_temperature_offset(
+        &mut self,
+        reference_c: f32,
+        readings: usize,
+    ) -> Result<OffsetReport, CalibrationError<I::Error>> {
+        if readings == 0 {
+            return Err(CalibrationError::NoReadings);
+        }
+        let previous_offset_c = self.get_temperature_offset_celsius()?;
+        let interval = Duration::from_secs(u64::from(self.get_measurements_interval()?));
+        let policy = RecoveryPolicy::Retry {
+            attempts: 3,
+            wait: interval,
+        };
+        let mut sum = 0.0;
+        for measurement in self.measurements().with_policy(policy).take(readings) {
+            sum += measurement?.temperature_c;
+        }
+        let measured_c = sum / readings as f32;
+        let offset_c = previous_offset_c + measured_c - reference_c;
+        let ticks = offset_ticks::<I::Error>(offset_c)
+            .map_err(|_| CalibrationError::InvalidOffset { offset_c })?;
+        self.set_temperature_offset(ticks)?;
+        Ok(OffsetReport {
+            reference_c,
+            measured_c,
+            previous_offset_c,
+            offset_c: (offset_c * 100.0).round() / 100.0,
+            readings,
+        })
+    }
+}
+
+/// Number of measurements needed to cover the given time
+fn windows(time: Duration, interval: Duration) -> usize {
+    let count = time.as_nanos().div_ceil(interval.as_nanos().max(1));
+    usize::try_from(count).unwrap_or(usize::MAX)
+}
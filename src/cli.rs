@@ -0,0 +1,147 @@
+// Copyright 2024, F. Stan
+//
+// Licensed under the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Command line arguments of the `scd30` tool
+//!
+//! The arguments are parsed with clap, so they can be checked without hardware:
+//!
+//!```
+//!use clap::Parser;
+//!use scd30_i2c::cli::{Action, Cli, Command};
+//!
+//!let cli = Cli::try_parse_from(["scd30", "--address", "0x62", "altitude", "set", "540"]).unwrap();
+//!assert_eq!(cli.address, 0x62);
+//!assert!(matches!(cli.command, Command::Altitude { action: Action::Set { value: 540 } }));
+//!```
+
+use crate::scd30::DEFAULT_BUS;
+use clap::{Parser, Subcommand};
+
+/// Arguments of the `scd30` tool
+///
+#[derive(Debug, Parser)]
+#[command(
+    name = "scd30",
+    version,
+    about = "Inspect and configure a SCD30 CO2 sensor"
+)]
+pub struct Cli {
+    /// I2C bus device
+    #[arg(long, default_value = DEFAULT_BUS)]
+    pub bus: String,
+    /// 7-bit I2C address of the sensor, decimal or hexadecimal with 0x prefix
+    #[arg(long, default_value = "0x61", value_parser = parse_address)]
+    pub address: u8,
+    /// Print the output as JSON
+    #[arg(long)]
+    pub json: bool,
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+/// Operation to run on the device
+///
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Wait for the next measurement and print it
+    Read,
+    /// Print measurements as they are ready
+    Watch {
+        /// Stop after this number of measurements
+        #[arg(long)]
+        count: Option<usize>,
+    },
+    /// Print the firmware version
+    Firmware,
+    /// Measurement interval in seconds
+    Interval {
+        #[command(subcommand)]
+        action: Action,
+    },
+    /// Altitude compensation in meters above sea level
+    Altitude {
+        #[command(subcommand)]
+        action: Action,
+    },
+    /// Temperature offset in Celsius
+    TempOffset {
+        #[command(subcommand)]
+        action: OffsetAction,
+    },
+    /// Automatic self calibration
+    Asc {
+        #[command(subcommand)]
+        action: AscAction,
+    },
+    /// Forced recalibration value in ppm
+    Frc {
+        #[command(subcommand)]
+        action: Action,
+    },
+    /// Soft reset the sensor
+    Reset,
+    /// Start the continuous measurements
+    Start {
+        /// Ambient pressure in mbar for compensation, 0 disables it
+        #[arg(long, default_value_t = 0)]
+        pressure: u16,
+    },
+    /// Stop the continuous measurements
+    Stop,
+}
+
+/// Read or write of a numeric setting
+///
+#[derive(Debug, Subcommand)]
+pub enum Action {
+    /// Print the current value
+    Get,
+    /// Write a new value
+    Set { value: u16 },
+}
+
+/// Read or write of the temperature offset
+///
+#[derive(Debug, Subcommand)]
+pub enum OffsetAction {
+    /// Print the current offset
+    Get,
+    /// Write a new offset in Celsius, rounded to 0.01
+    Set { value: f32 },
+}
+
+/// Automatic self calibration operations
+///
+#[derive(Debug, Subcommand)]
+pub enum AscAction {
+    /// Activate the automatic self calibration
+    On,
+    /// Deactivate the automatic self calibration
+    Off,
+    /// Print if the automatic self calibration is active
+    Status,
+}
+
+/// Highest 7-bit I2C address
+pub const MAX_ADDRESS: u8 = 0x7F;
+
+/// Parses a 7-bit I2C address, in decimal or hexadecimal with 0x prefix
+///
+pub fn parse_address(address: &str) -> Result<u8, String> {
+    let parsed = match address.strip_prefix("0x") {
+        Some(hex) => u8::from_str_radix(hex, 16),
+        None => address.parse(),
+    };
+    match parsed {
+        Ok(parsed) if parsed <= MAX_ADDRESS => Ok(parsed),
+        Ok(_) => Err(format!(
+            "invalid address {}: not a 7-bit address, the highest is 0x{:02X}",
+            address, MAX_ADDRESS
+        )),
+        Err(e) => Err(format!("invalid address {}: {}", address, e)),
+    }
+}
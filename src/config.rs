@@ -0,0 +1,369 @@
+// Copyright 2024, F. Stan
+//
+// Licensed under the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Snapshot and restore of the SCD30 configuration
+//!
+//! [`Scd30::read_config`](crate::scd30::Scd30::read_config) reads all the settings of the
+//! device in a [`Scd30Config`](crate::config::Scd30Config), and
+//! [`Scd30::apply_config`](crate::scd30::Scd30::apply_config) writes only the ones that
+//! are set and differ from the device, reading each one back to verify it, and returns
+//! them in a [`Settings`](crate::config::Settings) set. The settings left out are not
+//! changed. With the `serde` feature the configuration can be serialized, for example
+//! to TOML:
+//!
+//!```toml
+//!measurement_interval = 5
+//!altitude = 120
+//!temperature_offset = 150
+//!self_calibration = false
+//!```
+//!
+//!```no_run
+//!use scd30_i2c::config::Scd30Config;
+//!use scd30_i2c::scd30::Scd30;
+//!
+//!let mut scd = Scd30::new().unwrap();
+//!let config = Scd30Config {
+//!    measurement_interval: Some(5),
+//!    altitude: Some(120),
+//!    ..Scd30Config::default()
+//!};
+//!let changed = scd.apply_config(&config).unwrap();
+//!println!("Changed {:?}", changed);
+//!```
+
+use crate::scd30::{check_forced_value, check_interval, Scd30, Scd30Error};
+use core::error::Error;
+use core::fmt;
+use embedded_hal::delay::DelayNs;
+use embedded_hal::i2c::I2c;
+
+/// Configuration stored in the device. Only the settings that are set
+/// are applied, so a partial configuration leaves the others as they are
+/// on the device.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Scd30Config {
+    /// Measurement interval in seconds, from 2 to 1800
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub measurement_interval: Option<u16>,
+    /// Altitude in meters
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub altitude: Option<u16>,
+    /// Temperature offset in ticks of 0.01 Celsius
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub temperature_offset: Option<u16>,
+    /// If automatic self calibration is active
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub self_calibration: Option<bool>,
+    /// Forced recalibration value in ppm, from 400 to 2000. It is never read
+    /// from the device since it must be measured on each device. The device must
+    /// be measuring continuously for at least 2 minutes in a stable environment
+    /// before it is applied, so [`Scd30::apply_config`] refuses it and only
+    /// [`Scd30::apply_config_with_recalibration`] applies it. See
+    /// [`Scd30::force_recalibration`] for the guided procedure.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub forced_recalibration: Option<u16>,
+}
+
+impl Scd30Config {
+    /// Sets the settings that are set in the other configuration
+    ///
+    pub(crate) fn update(&mut self, other: &Scd30Config) {
+        self.measurement_interval = other.measurement_interval.or(self.measurement_interval);
+        self.altitude = other.altitude.or(self.altitude);
+        self.temperature_offset = other.temperature_offset.or(self.temperature_offset);
+        self.self_calibration = other.self_calibration.or(self.self_calibration);
+        self.forced_recalibration = other.forced_recalibration.or(self.forced_recalibration);
+    }
+}
+
+/// One setting of the device configuration
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Setting {
+    /// Measurement interval in seconds
+    MeasurementInterval,
+    /// Altitude in meters
+    Altitude,
+    /// Temperature offset in ticks of 0.01 Celsius
+    TemperatureOffset,
+    /// Automatic self calibration, 1 if active
+    SelfCalibration,
+    /// Forced recalibration value in ppm
+    ForcedRecalibration,
+}
+
+///Implementation of display for Setting
+impl fmt::Display for Setting {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Setting::MeasurementInterval => fmt::Display::fmt("measurement interval", f),
+            Setting::Altitude => fmt::Display::fmt("altitude", f),
+            Setting::TemperatureOffset => fmt::Display::fmt("temperature offset", f),
+            Setting::SelfCalibration => fmt::Display::fmt("self calibration", f),
+            Setting::ForcedRecalibration => fmt::Display::fmt("forced recalibration", f),
+        }
+    }
+}
+
+impl Setting {
+    /// Every setting, in the order they are applied
+    const ALL: [Setting; 5] = [
+        Setting::MeasurementInterval,
+        Setting::Altitude,
+        Setting::TemperatureOffset,
+        Setting::SelfCalibration,
+        Setting::ForcedRecalibration,
+    ];
+
+    /// Bit of the setting in a [`Settings`] set
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+/// Set of settings of the device configuration, like the ones changed
+/// by [`Scd30::apply_config`]. It is a bitmask, so it does not need alloc.
+///
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub struct Settings(u8);
+
+impl Settings {
+    /// Empty set
+    ///
+    pub fn new() -> Settings {
+        Settings(0)
+    }
+
+    /// Adds the setting to the set
+    ///
+    pub fn insert(&mut self, setting: Setting) {
+        self.0 |= setting.bit();
+    }
+
+    /// If the setting is in the set
+    ///
+    pub fn contains(&self, setting: Setting) -> bool {
+        self.0 & setting.bit() != 0
+    }
+
+    /// If the set has no settings
+    ///
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Number of settings in the set
+    ///
+    pub fn len(&self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    /// Iterates the settings of the set, in the order they are applied
+    ///
+    pub fn iter(&self) -> impl Iterator<Item = Setting> {
+        let settings = *self;
+        Setting::ALL
+            .into_iter()
+            .filter(move |&setting| settings.contains(setting))
+    }
+}
+
+///Implementation of debug for Settings, as the list of settings
+impl fmt::Debug for Settings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+///Implementation of a set of settings from the settings
+impl FromIterator<Setting> for Settings {
+    fn from_iter<T: IntoIterator<Item = Setting>>(iter: T) -> Self {
+        let mut settings = Settings::new();
+        for setting in iter {
+            settings.insert(setting);
+        }
+        settings
+    }
+}
+
+///
+///Configuration error enum. Device when an operation with the
+///SCD30 fails, the settings written before the failure are kept.
+///VerificationFailed when a setting read back is different from
+///the written one. RecalibrationNotAllowed when the configuration
+///has a forced recalibration value and it was not explicitly allowed
+///
+#[derive(Debug)]
+pub enum ConfigError<E> {
+    /// Error from the device
+    Device(Scd30Error<E>),
+    /// Value read back from the device does not match the written one
+    VerificationFailed {
+        setting: Setting,
+        expected: u16,
+        actual: u16,
+    },
+    /// Forced recalibration value given to [`Scd30::apply_config`], nothing is written
+    RecalibrationNotAllowed,
+}
+
+///Implementation for Scd30Error to ConfigError
+impl<E> From<Scd30Error<E>> for ConfigError<E> {
+    fn from(e: Scd30Error<E>) -> Self {
+        ConfigError::Device(e)
+    }
+}
+
+///Implementation of display for ConfigError
+impl<E> fmt::Display for ConfigError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConfigError::Device(ref e) => fmt::Display::fmt(e, f),
+            ConfigError::VerificationFailed {
+                setting,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Setting {} not applied, expected {} but device reports {}",
+                setting, expected, actual
+            ),
+            ConfigError::RecalibrationNotAllowed => write!(
+                f,
+                "Forced recalibration is only applied with apply_config_with_recalibration"
+            ),
+        }
+    }
+}
+
+///Implementation for Error to ConfigError, the source is the device error
+impl<E: Error + 'static> Error for ConfigError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            ConfigError::Device(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl<I: I2c, D: DelayNs> Scd30<I, D> {
+    /// Reads the configuration of the device, every setting is set
+    /// but the forced recalibration value, that is not read.
+    ///
+    pub fn read_config(&mut self) -> Result<Scd30Config, Scd30Error<I::Error>> {
+        Ok(Scd30Config {
+            measurement_interval: Some(self.get_measurements_interval()?),
+            altitude: Some(self.get_altitude()?),
+            temperature_offset: Some(self.get_temperature_offset()?),
+            self_calibration: Some(self.get_self_calibration_status()?),
+            forced_recalibration: None,
+        })
+    }
+
+    /// Writes the settings set in the configuration that differ from the device,
+    /// verifying each one by reading it back. The values are checked before
+    /// writing anything, so an invalid configuration does not change the device.
+    /// A forced recalibration value fails with RecalibrationNotAllowed, also
+    /// before writing anything.
+    /// Returns the settings changed.
+    ///
+    pub fn apply_config(
+        &mut self,
+        config: &Scd30Config,
+    ) -> Result<Settings, ConfigError<I::Error>> {
+        if config.forced_recalibration.is_some() {
+            return Err(ConfigError::RecalibrationNotAllowed);
+        }
+        self.apply(config)
+    }
+
+    /// Same as [`Scd30::apply_config`], but also applies the forced recalibration
+    /// value when set. It is sent on every call, even if the device already reports
+    /// the same value. The continuous measurements must have been running for at
+    /// least 2 minutes in a stable environment, never call it on a device that
+    /// just booted.
+    ///
+    pub fn apply_config_with_recalibration(
+        &mut self,
+        config: &Scd30Config,
+    ) -> Result<Settings, ConfigError<I::Error>> {
+        self.apply(config)
+    }
+
+    /// Writes and verifies the settings set in the configuration
+    fn apply(&mut self, config: &Scd30Config) -> Result<Settings, ConfigError<I::Error>> {
+        if let Some(interval) = config.measurement_interval {
+            check_interval(interval)?;
+        }
+        if let Some(forced_value) = config.forced_recalibration {
+            check_forced_value(forced_value)?;
+        }
+
+        let mut changed = Settings::new();
+        if let Some(interval) = config.measurement_interval {
+            if self.get_measurements_interval()? != interval {
+                self.set_measurements_interval(interval)?;
+                let actual = self.get_measurements_interval()?;
+                verify(Setting::MeasurementInterval, interval, actual)?;
+                changed.insert(Setting::MeasurementInterval);
+            }
+        }
+        if let Some(altitude) = config.altitude {
+            if self.get_altitude()? != altitude {
+                self.set_altitude(altitude)?;
+                let actual = self.get_altitude()?;
+                verify(Setting::Altitude, altitude, actual)?;
+                changed.insert(Setting::Altitude);
+            }
+        }
+        if let Some(offset) = config.temperature_offset {
+            if self.get_temperature_offset()? != offset {
+                self.set_temperature_offset(offset)?;
+                let actual = self.get_temperature_offset()?;
+                verify(Setting::TemperatureOffset, offset, actual)?;
+                changed.insert(Setting::TemperatureOffset);
+            }
+        }
+        if let Some(active) = config.self_calibration {
+            if self.get_self_calibration_status()? != active {
+                self.set_self_calibration(active)?;
+                let actual = self.get_self_calibration_status()?;
+                verify(
+                    Setting::SelfCalibration,
+                    u16::from(active),
+                    u16::from(actual),
+                )?;
+                changed.insert(Setting::SelfCalibration);
+            }
+        }
+        // The forced recalibration is an action, not a stored setting, so it is
+        // sent even if the device already reports the same value
+        if let Some(forced_value) = config.forced_recalibration {
+            self.set_force_recalibration_value(forced_value)?;
+            let actual = self.get_forced_value()?;
+            verify(Setting::ForcedRecalibration, forced_value, actual)?;
+            changed.insert(Setting::ForcedRecalibration);
+        }
+        Ok(changed)
+    }
+}
+
+/// Fails with VerificationFailed if the value read back is not the written one
+fn verify<E>(setting: Setting, expected: u16, actual: u16) -> Result<(), ConfigError<E>> {
+    if expected == actual {
+        Ok(())
+    } else {
+        Err(ConfigError::VerificationFailed {
+            setting,
+            expected,
+            actual,
+        })
+    }
+}
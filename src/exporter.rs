@@ -0,0 +1,302 @@
+// Copyright 2024, F. Stan
+//
+// Licensed under the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Prometheus exporter for the SCD30 readings
+//!
+//! [`Metrics`](crate::exporter::Metrics) keeps the last measurement, the device metadata and
+//! the error counters, and renders them in the Prometheus text format. The measurement
+//! gauges are dropped when a read fails, so a failing device does not keep reporting
+//! its last CO2, and the time of the last measurement tells how old the data is.
+//! [`MetricsServer`](crate::exporter::MetricsServer) serves them on `/metrics`, while
+//! [`poll`](crate::exporter::poll) feeds them from the measurements of a
+//! [`Scd30`](crate::scd30::Scd30).
+//!
+//!```no_run
+//!use scd30_i2c::exporter::{self, Metrics, MetricsServer};
+//!use scd30_i2c::scd30::Scd30;
+//!use std::sync::Arc;
+//!use std::thread;
+//!
+//!let mut scd = Scd30::open("/dev/i2c-1", 0x61).unwrap();
+//!let metrics = Arc::new(Metrics::new("/dev/i2c-1", 0x61));
+//!let server = MetricsServer::bind("0.0.0.0:9630", Arc::clone(&metrics)).unwrap();
+//!thread::spawn(move || server.run());
+//!
+//!scd.trigger_cont_measurements().unwrap();
+//!exporter::read_metadata(&mut scd, &metrics);
+//!exporter::poll(&mut scd, &metrics);
+//!```
+
+use crate::measurement::Measurement;
+use crate::scd30::{Scd30, Scd30Error};
+use crate::stream::RecoveryPolicy;
+use embedded_hal::delay::DelayNs;
+use embedded_hal::i2c::I2c;
+use std::error::Error;
+use std::fmt::Write;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tiny_http::{Header, Response, Server};
+
+/// Last known values of the device
+#[derive(Debug, Default)]
+struct State {
+    measurement: Option<Measurement>,
+    last_timestamp_ms: Option<u64>,
+    firmware: Option<u16>,
+    interval: Option<u16>,
+    altitude: Option<u16>,
+    self_calibration: Option<bool>,
+    checksum_errors: u64,
+    communication_errors: u64,
+    frame_errors: u64,
+    timeouts: u64,
+}
+
+/// Metrics of one SCD30 device, identified by its bus and address labels
+///
+#[derive(Debug)]
+pub struct Metrics {
+    labels: String,
+    state: Mutex<State>,
+}
+
+impl Metrics {
+    /// Create the metrics for the device on the given bus and address
+    ///
+    pub fn new(bus: &str, address: u8) -> Metrics {
+        let bus = bus
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n");
+        Metrics {
+            labels: format!("bus=\"{}\",address=\"0x{:02x}\"", bus, address),
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Records a new measurement
+    ///
+    pub fn record_measurement(&self, measurement: &Measurement) {
+        let mut state = self.state.lock().unwrap();
+        state.measurement = Some(*measurement);
+        state.last_timestamp_ms = measurement.timestamp_ms.or(state.last_timestamp_ms);
+    }
+
+    /// Records an error of the device, increasing its counter. Values
+    /// rejected before reaching the device are not counted.
+    ///
+    pub fn record_error<E>(&self, error: &Scd30Error<E>) {
+        let mut state = self.state.lock().unwrap();
+        match error {
+            Scd30Error::ChecksumError => state.checksum_errors += 1,
+            Scd30Error::Frame(_) => state.frame_errors += 1,
+            Scd30Error::Timeout => state.timeouts += 1,
+            Scd30Error::ComunicationError { .. } | Scd30Error::Io(_) => {
+                state.communication_errors += 1
+            }
+            _ => {}
+        }
+    }
+
+    /// Records the result of a measurement read. A failed read drops
+    /// the last measurement, keeping only its time.
+    ///
+    pub fn record<E>(&self, result: &Result<Measurement, Scd30Error<E>>) {
+        match result {
+            Ok(measurement) => self.record_measurement(measurement),
+            Err(e) => {
+                self.state.lock().unwrap().measurement = None;
+                self.record_error(e);
+            }
+        }
+    }
+
+    /// Records the device metadata, only the known values are updated
+    ///
+    pub fn record_metadata(
+        &self,
+        firmware: Option<u16>,
+        interval: Option<u16>,
+        altitude: Option<u16>,
+        self_calibration: Option<bool>,
+    ) {
+        let mut state = self.state.lock().unwrap();
+        state.firmware = firmware.or(state.firmware);
+        state.interval = interval.or(state.interval);
+        state.altitude = altitude.or(state.altitude);
+        state.self_calibration = self_calibration.or(state.self_calibration);
+    }
+
+    /// Renders the metrics in the Prometheus text format
+    ///
+    pub fn render(&self) -> String {
+        let state = self.state.lock().unwrap();
+        let mut out = String::new();
+        let labels = &self.labels;
+        let mut gauge = |name: &str, help: &str, value: Option<f64>| {
+            if let Some(value) = value {
+                let _ = writeln!(out, "# HELP {} {}", name, help);
+                let _ = writeln!(out, "# TYPE {} gauge", name);
+                let _ = writeln!(out, "{}{{{}}} {}", name, labels, value);
+            }
+        };
+        let measurement = state.measurement.as_ref();
+        gauge(
+            "scd30_co2_ppm",
+            "CO2 concentration in ppm.",
+            measurement.map(|m| f64::from(m.co2_ppm)),
+        );
+        gauge(
+            "scd30_temperature_celsius",
+            "Temperature in Celsius degrees.",
+            measurement.map(|m| f64::from(m.temperature_c)),
+        );
+        gauge(
+            "scd30_relative_humidity_percent",
+            "Relative humidity in percent.",
+            measurement.map(|m| f64::from(m.humidity_pct)),
+        );
+        gauge(
+            "scd30_last_measurement_timestamp_seconds",
+            "Time of the last measurement, in seconds since the epoch.",
+            state.last_timestamp_ms.map(|ms| ms as f64 / 1000.0),
+        );
+        gauge(
+            "scd30_measurement_interval_seconds",
+            "Configured measurement interval in seconds.",
+            state.interval.map(f64::from),
+        );
+        gauge(
+            "scd30_altitude_meters",
+            "Configured altitude compensation in meters.",
+            state.altitude.map(f64::from),
+        );
+        gauge(
+            "scd30_asc_enabled",
+            "1 if the automatic self calibration is active.",
+            state
+                .self_calibration
+                .map(|active| f64::from(u8::from(active))),
+        );
+        if let Some(firmware) = state.firmware {
+            let [major, minor] = firmware.to_be_bytes();
+            let _ = writeln!(out, "# HELP scd30_info Device information.");
+            let _ = writeln!(out, "# TYPE scd30_info gauge");
+            let _ = writeln!(
+                out,
+                "scd30_info{{{},firmware=\"{}.{}\"}} 1",
+                labels, major, minor
+            );
+        }
+        let mut counter = |name: &str, help: &str, value: u64| {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} counter", name);
+            let _ = writeln!(out, "{}{{{}}} {}", name, labels, value);
+        };
+        counter(
+            "scd30_checksum_errors_total",
+            "Responses with a wrong checksum.",
+            state.checksum_errors,
+        );
+        counter(
+            "scd30_communication_errors_total",
+            "Failed reads or writes on the bus.",
+            state.communication_errors,
+        );
+        counter(
+            "scd30_frame_errors_total",
+            "Responses that are not well formed.",
+            state.frame_errors,
+        );
+        counter(
+            "scd30_timeouts_total",
+            "Times the device did not have data ready in time.",
+            state.timeouts,
+        );
+        out
+    }
+}
+
+/// HTTP server exposing the metrics on `/metrics`
+///
+pub struct MetricsServer {
+    server: Server,
+    metrics: Arc<Metrics>,
+}
+
+impl MetricsServer {
+    /// Binds the server on the given address
+    ///
+    pub fn bind<A: ToSocketAddrs>(
+        address: A,
+        metrics: Arc<Metrics>,
+    ) -> Result<MetricsServer, Box<dyn Error + Send + Sync>> {
+        let server = Server::http(address)?;
+        Ok(MetricsServer { server, metrics })
+    }
+
+    /// Address where the server is listening
+    ///
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.server.server_addr().to_ip()
+    }
+
+    /// Serves the requests, blocking the current thread
+    ///
+    pub fn run(self) {
+        let content_type = Header::from_bytes(
+            &b"Content-Type"[..],
+            &b"text/plain; version=0.0.4; charset=utf-8"[..],
+        )
+        .unwrap();
+        for request in self.server.incoming_requests() {
+            // Compare only the path, scrapers may add query parameters
+            let path = request.url().split('?').next().unwrap_or_default();
+            let response = if path == "/metrics" {
+                Response::from_string(self.metrics.render()).with_header(content_type.clone())
+            } else {
+                Response::from_string("Not found").with_status_code(404)
+            };
+            let _ = request.respond(response);
+        }
+    }
+}
+
+/// Reads the firmware version, interval, altitude and self calibration
+/// state of the device into the metrics. Values that fail to be read are
+/// counted as errors and left as they were.
+///
+pub fn read_metadata<I: I2c, D: DelayNs>(scd: &mut Scd30<I, D>, metrics: &Metrics) {
+    let firmware = scd.check_firmware().map_err(|e| metrics.record_error(&e));
+    let interval = scd
+        .get_measurements_interval()
+        .map_err(|e| metrics.record_error(&e));
+    let altitude = scd.get_altitude().map_err(|e| metrics.record_error(&e));
+    let self_calibration = scd
+        .get_self_calibration_status()
+        .map_err(|e| metrics.record_error(&e));
+    metrics.record_metadata(
+        firmware.ok(),
+        interval.ok(),
+        altitude.ok(),
+        self_calibration.ok(),
+    );
+}
+
+/// Polls the measurements of the device forever, recording them in the metrics.
+/// The continous measurements must be already triggered.
+///
+pub fn poll<I: I2c, D: DelayNs>(scd: &mut Scd30<I, D>, metrics: &Metrics) {
+    let policy = RecoveryPolicy::Continue {
+        wait: Duration::from_secs(1),
+    };
+    for result in scd.measurements().with_policy(policy) {
+        metrics.record(&result);
+    }
+}
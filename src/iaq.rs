@@ -0,0 +1,400 @@
+// Copyright 2024, F. Stan
+//
+// Licensed under the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Indoor air quality classification of the CO2 readings
+//!
+//! An [`IaqScheme`](crate::iaq::IaqScheme) splits the CO2 concentration in ordered
+//! [`Band`](crate::iaq::Band)s, each one with an optional ventilation advice.
+//! [`IaqScheme::traffic_light`](crate::iaq::IaqScheme::traffic_light) and
+//! [`IaqScheme::en16798`](crate::iaq::IaqScheme::en16798) are ready to use, and with
+//! the `serde` feature schemes can be loaded from a file:
+//!
+//!```toml
+//!hysteresis_ppm = 50.0
+//!
+//![[bands]]
+//!name = "good"
+//!upper_ppm = 1000.0
+//!
+//![[bands]]
+//!name = "poor"
+//!advice = "Open the window"
+//!```
+//!
+//! A [`Classifier`](crate::iaq::Classifier) tracks the current band with hysteresis: a
+//! band is entered as soon as its threshold is reached, but only left downwards when the
+//! concentration falls the hysteresis below it, so readings around a threshold do not
+//! flap. Each change of band is reported as a [`Transition`](crate::iaq::Transition),
+//! also from the measurements iterator:
+//!
+//!```no_run
+//!use scd30_i2c::iaq::{Classifier, IaqScheme};
+//!use scd30_i2c::scd30::Scd30;
+//!
+//!let mut scd = Scd30::new().unwrap();
+//!scd.trigger_cont_measurements().unwrap();
+//!let classifier = Classifier::new(IaqScheme::traffic_light()).unwrap();
+//!for reading in scd.measurements().classified(classifier) {
+//!    if let Ok((_, Some(transition))) = reading {
+//!        if let (true, Some(advice)) = (transition.is_worse(), &transition.advice) {
+//!            println!("Air is {}: {}", transition.name, advice);
+//!        }
+//!    }
+//!}
+//!```
+
+use crate::measurement::Measurement;
+use crate::scd30::Scd30Error;
+use crate::stream::Measurements;
+use embedded_hal::delay::DelayNs;
+use embedded_hal::i2c::I2c;
+use std::error::Error;
+use std::fmt;
+
+/// CO2 concentration of the outdoor air used as reference, in ppm
+pub const OUTDOOR_CO2: f32 = 420.0;
+/// Default hysteresis of the ready to use schemes, in ppm
+pub const DEFAULT_HYSTERESIS: f32 = 50.0;
+
+/// Range of CO2 concentration with the same air quality
+///
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Band {
+    /// Name shown for the band, for example "green" or "II"
+    pub name: String,
+    /// Concentration in ppm where the next band starts, None for the last band
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub upper_ppm: Option<f32>,
+    /// What to do when the air gets into the band, for example "Open the window"
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub advice: Option<String>,
+}
+
+impl Band {
+    /// Band below the given concentration in ppm
+    ///
+    pub fn below(name: &str, upper_ppm: f32) -> Band {
+        Band {
+            name: name.to_string(),
+            upper_ppm: Some(upper_ppm),
+            advice: None,
+        }
+    }
+
+    /// Last band, without upper limit
+    ///
+    pub fn above(name: &str) -> Band {
+        Band {
+            name: name.to_string(),
+            upper_ppm: None,
+            advice: None,
+        }
+    }
+
+    /// Adds the ventilation advice to the band
+    ///
+    pub fn with_advice(mut self, advice: &str) -> Band {
+        self.advice = Some(advice.to_string());
+        self
+    }
+}
+
+/// Bands from the best to the worst air quality, and the hysteresis
+/// applied when going back to a better band
+///
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IaqScheme {
+    /// Bands ordered by concentration, only the last one without upper limit
+    pub bands: Vec<Band>,
+    /// Concentration in ppm the readings must fall below a threshold to go back
+    /// to the better band
+    #[cfg_attr(feature = "serde", serde(default = "default_hysteresis"))]
+    pub hysteresis_ppm: f32,
+}
+
+/// Default traffic light scheme
+impl Default for IaqScheme {
+    fn default() -> Self {
+        IaqScheme::traffic_light()
+    }
+}
+
+impl IaqScheme {
+    /// Green below 1000 ppm, amber below 1500 ppm and red above,
+    /// with 50 ppm of hysteresis
+    ///
+    pub fn traffic_light() -> IaqScheme {
+        IaqScheme {
+            bands: vec![
+                Band::below("green", 1000.0),
+                Band::below("amber", 1500.0).with_advice("Ventilate soon"),
+                Band::above("red").with_advice("Open the window"),
+            ],
+            hysteresis_ppm: DEFAULT_HYSTERESIS,
+        }
+    }
+
+    /// Categories I to IV of EN 16798-1, from the CO2 concentration above the
+    /// outdoor one: 550 ppm for I, 800 ppm for II, 1350 ppm for III and more for IV,
+    /// with 50 ppm of hysteresis. Use [`OUTDOOR_CO2`] if the outdoor concentration
+    /// is not measured.
+    ///
+    pub fn en16798(outdoor_ppm: f32) -> IaqScheme {
+        IaqScheme {
+            bands: vec![
+                Band::below("I", outdoor_ppm + 550.0),
+                Band::below("II", outdoor_ppm + 800.0),
+                Band::below("III", outdoor_ppm + 1350.0).with_advice("Increase the ventilation"),
+                Band::above("IV").with_advice("Open the window"),
+            ],
+            hysteresis_ppm: DEFAULT_HYSTERESIS,
+        }
+    }
+
+    /// Checks there is at least one band, the thresholds are ascending, only
+    /// the last band is open and the hysteresis is not negative
+    ///
+    pub fn validate(&self) -> Result<(), IaqError> {
+        if self.bands.is_empty() {
+            return Err(IaqError::NoBands);
+        }
+        if self.hysteresis_ppm.is_nan() || self.hysteresis_ppm < 0.0 {
+            return Err(IaqError::InvalidHysteresis(self.hysteresis_ppm));
+        }
+        let last = self.bands.len() - 1;
+        let mut previous = f32::NEG_INFINITY;
+        for (band, b) in self.bands.iter().enumerate() {
+            match b.upper_ppm {
+                Some(_) if band == last => return Err(IaqError::BoundedLastBand),
+                Some(upper) if upper.is_nan() || upper <= previous => {
+                    return Err(IaqError::UnorderedThreshold { band })
+                }
+                Some(upper) => previous = upper,
+                None if band != last => return Err(IaqError::MissingThreshold { band }),
+                None => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Position of the band of the concentration in ppm, without hysteresis
+    ///
+    pub fn band_of(&self, co2_ppm: f32) -> usize {
+        self.bands
+            .iter()
+            .position(|b| b.upper_ppm.is_some_and(|upper| co2_ppm < upper))
+            .unwrap_or(self.bands.len().saturating_sub(1))
+    }
+}
+
+/// Default hysteresis when not given in the serialized scheme
+#[cfg(feature = "serde")]
+fn default_hysteresis() -> f32 {
+    DEFAULT_HYSTERESIS
+}
+
+///
+///IAQ scheme error enum. NoBands when the scheme is empty.
+///MissingThreshold when a band other than the last one has
+///no upper limit, and BoundedLastBand when the last one has.
+///UnorderedThreshold when a threshold is not above the previous
+///one. InvalidHysteresis when it is negative or not a number
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum IaqError {
+    /// Scheme without bands
+    NoBands,
+    /// Band, other than the last one, without upper limit
+    MissingThreshold { band: usize },
+    /// Upper limit not above the one of the previous band
+    UnorderedThreshold { band: usize },
+    /// Last band with upper limit
+    BoundedLastBand,
+    /// Negative or not a number hysteresis
+    InvalidHysteresis(f32),
+}
+
+///Implementation of display for IaqError
+impl fmt::Display for IaqError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            IaqError::NoBands => fmt::Display::fmt("Scheme without bands", f),
+            IaqError::MissingThreshold { band } => {
+                write!(
+                    f,
+                    "Band {} has no upper limit and is not the last one",
+                    band
+                )
+            }
+            IaqError::UnorderedThreshold { band } => write!(
+                f,
+                "Upper limit of band {} is not above the previous one",
+                band
+            ),
+            IaqError::BoundedLastBand => fmt::Display::fmt("Last band has an upper limit", f),
+            IaqError::InvalidHysteresis(hysteresis) => {
+                write!(f, "Invalid hysteresis {} ppm", hysteresis)
+            }
+        }
+    }
+}
+
+///Implementation for Error to IaqError
+impl Error for IaqError {}
+
+/// Change of band of the air quality
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transition {
+    /// Position of the previous band, None on the first reading
+    pub from: Option<usize>,
+    /// Position of the new band
+    pub to: usize,
+    /// Name of the new band
+    pub name: String,
+    /// Advice of the new band
+    pub advice: Option<String>,
+    /// CO2 concentration in ppm of the reading that changed the band
+    pub co2_ppm: f32,
+}
+
+impl Transition {
+    /// If the air quality got worse, also true on the first reading
+    ///
+    pub fn is_worse(&self) -> bool {
+        self.from.map_or(true, |from| self.to > from)
+    }
+}
+
+/// Tracks the band of the air quality over the readings, with hysteresis
+///
+#[derive(Debug, Clone)]
+pub struct Classifier {
+    scheme: IaqScheme,
+    current: Option<usize>,
+}
+
+impl Classifier {
+    /// Creates a classifier for the scheme, failing if it is not valid
+    ///
+    pub fn new(scheme: IaqScheme) -> Result<Classifier, IaqError> {
+        scheme.validate()?;
+        Ok(Classifier {
+            scheme,
+            current: None,
+        })
+    }
+
+    /// Scheme used to classify the readings
+    ///
+    pub fn scheme(&self) -> &IaqScheme {
+        &self.scheme
+    }
+
+    /// Current band, None before the first reading
+    ///
+    pub fn current(&self) -> Option<&Band> {
+        self.current.map(|band| &self.scheme.bands[band])
+    }
+
+    /// Position of the current band, None before the first reading
+    ///
+    pub fn current_index(&self) -> Option<usize> {
+        self.current
+    }
+
+    /// Forgets the current band, the next reading is classified as the first one
+    ///
+    pub fn reset(&mut self) {
+        self.current = None;
+    }
+
+    /// Classifies the CO2 concentration in ppm, returning the transition if
+    /// the band changed. Readings that are not a number are ignored.
+    ///
+    pub fn update(&mut self, co2_ppm: f32) -> Option<Transition> {
+        if co2_ppm.is_nan() {
+            return None;
+        }
+        let band = self.scheme.band_of(co2_ppm);
+        let next = match self.current {
+            Some(current) if band < current => {
+                // Going back needs to fall the hysteresis below the threshold
+                self.scheme
+                    .band_of(co2_ppm + self.scheme.hysteresis_ppm)
+                    .min(current)
+            }
+            _ => band,
+        };
+        if self.current == Some(next) {
+            return None;
+        }
+        let from = self.current.replace(next);
+        let b = &self.scheme.bands[next];
+        Some(Transition {
+            from,
+            to: next,
+            name: b.name.clone(),
+            advice: b.advice.clone(),
+            co2_ppm,
+        })
+    }
+
+    /// Classifies the CO2 concentration of the measurement, see [`Classifier::update`]
+    ///
+    pub fn classify(&mut self, measurement: &Measurement) -> Option<Transition> {
+        self.update(measurement.co2_ppm)
+    }
+}
+
+/// Measurements iterator that classifies each reading, created with
+/// [`Measurements::classified`]. Yields the measurement and the transition,
+/// if the band changed.
+///
+pub struct Classified<'a, I, D> {
+    measurements: Measurements<'a, I, D>,
+    classifier: Classifier,
+}
+
+impl<'a, I: I2c, D: DelayNs> Classified<'a, I, D> {
+    /// Classifier with the current band
+    ///
+    pub fn classifier(&self) -> &Classifier {
+        &self.classifier
+    }
+
+    /// Gives access to the underlying measurements iterator between items
+    ///
+    pub fn measurements_mut(&mut self) -> &mut Measurements<'a, I, D> {
+        &mut self.measurements
+    }
+}
+
+impl<I: I2c, D: DelayNs> Iterator for Classified<'_, I, D> {
+    type Item = Result<(Measurement, Option<Transition>), Scd30Error<I::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let measurement = self.measurements.next()?;
+        Some(measurement.map(|m| {
+            let transition = self.classifier.classify(&m);
+            (m, transition)
+        }))
+    }
+}
+
+impl<'a, I: I2c, D: DelayNs> Measurements<'a, I, D> {
+    /// Classifies each measurement with the classifier
+    ///
+    pub fn classified(self, classifier: Classifier) -> Classified<'a, I, D> {
+        Classified {
+            measurements: self,
+            classifier,
+        }
+    }
+}
@@ -8,13 +8,13 @@
 //! SCD30 trait implementing basic SCD30 I2C CO2 sensor operations
 //!
 //! Operations taken from [interface description](https://sensirion.com/media/documents/D7CEEF4A/6165372F/Sensirion_CO2_Sensors_SCD30_Interface_Description.pdf)
-//! At current version 1.0.0 we support all basic operations from the interface
+//! At current version 2.0.0 we support all basic operations from the interface
 //! ## Basic Example
 //!
 //! Obtaining measurements, co2, temperature and humidity
 //!
 //!
-//!```
+//!```no_run
 //!use scd30_i2c::scd30::Scd30;
 //!use std::thread;
 //!use std::time::Duration;
@@ -23,14 +23,16 @@
 //!    // Open the I2C device
 //!    let mut scd = Scd30::new().unwrap();
 //!    let mut counter = 0;
-//!    scd.trigger_cont_measurements();
-//!
-//!    scd.set_measurements_interval(2);
+//!    scd.set_measurements_interval(2).unwrap();
+//!    scd.trigger_cont_measurements().unwrap();
 //!
 //!    loop {
 //!        match scd.get_measurements() {
-//!            Ok((a, b, c)) => {
-//!                println!("Co2: {} ppm Temp: {} C RH: {} %", a, b, c);
+//!            Ok(m) => {
+//!                println!(
+//!                    "Co2: {} ppm Temp: {} C RH: {} %",
+//!                    m.co2_ppm, m.temperature_c, m.humidity_pct
+//!                );
 //!                thread::sleep(Duration::from_secs(2));
 //!                counter += 1;
 //!                println!("{}", counter);
@@ -47,6 +49,112 @@
 //!}
 //!```
 //!
+//! ## Measurements iterator
+//!
+//! Instead of sleeping between reads, the iterator waits for the data ready flag
+//! of the device and respects the configured interval.
+//!
+//!```no_run
+//!use scd30_i2c::scd30::Scd30;
+//!use scd30_i2c::stream::RecoveryPolicy;
+//!use std::time::Duration;
+//!
+//!let mut scd = Scd30::new().unwrap();
+//!scd.set_measurements_interval(2).unwrap();
+//!scd.trigger_cont_measurements().unwrap();
+//!
+//!let policy = RecoveryPolicy::Retry {
+//!    attempts: 3,
+//!    wait: Duration::from_secs(1),
+//!};
+//!for measurement in scd.measurements().with_policy(policy) {
+//!    match measurement {
+//!        Ok(m) => println!("Co2: {} ppm", m.co2_ppm),
+//!        Err(e) => println!("Error obtaining measurements: {}", e),
+//!    }
+//!}
+//!```
+//!
+//! ## Async
+//!
+//! With the `async` feature, [`asynch::Scd30Async`] offers the same operations over
+//! embedded-hal-async and an async `Stream` of measurements. The `tokio` feature adds the
+//! Linux bus and a tokio delay provider.
+//!
+//! ## Other platforms
+//!
+//! The driver is generic over any bus implementing the [embedded-hal](https://docs.rs/embedded-hal)
+//! `I2c` trait and any `DelayNs` delay provider, so it can be used on microcontrollers too.
+//!
+//!```ignore
+//!use scd30_i2c::scd30::Scd30;
+//!
+//!let mut scd = Scd30::from_i2c(i2c, delay);
+//!let firmware = scd.check_firmware();
+//!```
+//!
+//! ## no_std
+//!
+//! The driver, the measurements iterator, the configuration snapshot, the retry policy and
+//! the typestate layer only need `core`, without alloc. Disabling the default features builds
+//! the crate as `no_std`; the `std` feature adds the simulator, record and replay, Modbus,
+//! calibration and the measurement timestamps, and `linux` (default) the i2cdev bus.
+//!
+//!```toml
+//![dependencies]
+//!scd30_i2c = { version = "2", default-features = false }
+//!```
+//!
+
+#![cfg_attr(not(feature = "std"), no_std)]
 
+/// Threshold, rate of change and stale data alarms over the SCD30 readings
+#[cfg(feature = "alarms")]
+pub mod alarms;
+/// Async SCD30 driver over embedded-hal-async
+#[cfg(feature = "async")]
+pub mod asynch;
+/// Guided calibration procedures for the SCD30 device
+#[cfg(feature = "std")]
+pub mod calibration;
+/// Command line arguments of the scd30 tool
+#[cfg(feature = "cli")]
+pub mod cli;
+/// Snapshot and restore of the SCD30 configuration
+pub mod config;
+/// Prometheus exporter for the SCD30 readings
+#[cfg(feature = "prometheus")]
+pub mod exporter;
+/// Indoor air quality bands and ventilation advice from the CO2 readings
+#[cfg(feature = "iaq")]
+pub mod iaq;
+/// Linux I2C bus and delay provider for the SCD30 driver
+#[cfg(feature = "linux")]
+pub mod linux;
+/// Measurement type returned by the SCD30 device
+pub mod measurement;
+/// Modbus RTU transport for the SCD30 device
+#[cfg(feature = "std")]
+pub mod modbus;
+/// MQTT publisher with Home Assistant discovery
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+/// Psychrometric quantities derived from the SCD30 readings
+#[cfg(feature = "std")]
+pub mod psychrometrics;
+/// Record and replay of the I2C transactions with the SCD30 device
+#[cfg(feature = "std")]
+pub mod replay;
+/// Automatic retry and recovery of transient bus errors
+pub mod retry;
 /// Trait implementing SCD30 device related operations
 pub mod scd30;
+/// Word and checksum framing shared by the Sensirion sensors
+pub mod sensirion_frame;
+/// In-memory SCD30 device model, to use the driver without hardware
+#[cfg(feature = "std")]
+pub mod simulator;
+/// Blocking iterator over the SCD30 measurements
+pub mod stream;
+/// Typestate layer separating the idle and measuring modes
+pub mod typestate;
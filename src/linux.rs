@@ -0,0 +1,212 @@
+// Copyright 2024, F. Stan
+//
+// Licensed under the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::i2c::{self, ErrorKind, ErrorType, I2c, NoAcknowledgeSource, Operation};
+use i2cdev::core::I2CDevice;
+use i2cdev::linux::{LinuxI2CDevice, LinuxI2CError};
+use std::error::Error;
+use std::fmt;
+#[cfg(feature = "tokio")]
+use std::io;
+#[cfg(feature = "tokio")]
+use std::panic;
+#[cfg(feature = "tokio")]
+use std::sync::{Arc, Mutex, PoisonError};
+use std::{thread, time};
+
+/// Errno returned by the kernel when no device answers on the address
+const ENXIO: i32 = 6;
+/// Errno returned by some bus drivers when the slave does not acknowledge
+const EREMOTEIO: i32 = 121;
+
+///
+/// Wrapper of the LinuxI2CError from i2cdev library,
+/// so it can be used as an embedded-hal I2C error.
+///
+#[derive(Debug)]
+pub struct LinuxI2cError(pub LinuxI2CError);
+
+///Implementation of display for LinuxI2cError
+impl fmt::Display for LinuxI2cError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+///Implementation for Error to LinuxI2cError
+impl Error for LinuxI2cError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.0)
+    }
+}
+
+///Implementation for embedded-hal I2C error to LinuxI2cError
+impl i2c::Error for LinuxI2cError {
+    fn kind(&self) -> ErrorKind {
+        match self.0 {
+            LinuxI2CError::Errno(ENXIO) | LinuxI2CError::Errno(EREMOTEIO) => {
+                ErrorKind::NoAcknowledge(NoAcknowledgeSource::Unknown)
+            }
+            LinuxI2CError::Io(ref e)
+                if e.raw_os_error() == Some(ENXIO) || e.raw_os_error() == Some(EREMOTEIO) =>
+            {
+                ErrorKind::NoAcknowledge(NoAcknowledgeSource::Unknown)
+            }
+            _ => ErrorKind::Other,
+        }
+    }
+}
+
+/// Linux I2C bus, wraps a LinuxI2CDevice struct
+/// and implements the embedded-hal I2C trait for it
+///
+pub struct LinuxI2c {
+    pub i2cdev: LinuxI2CDevice,
+    address: u8,
+}
+
+impl LinuxI2c {
+    /// Wraps a LinuxI2CDevice already opened on the given address
+    ///
+    pub fn new(i2cdev: LinuxI2CDevice, address: u8) -> LinuxI2c {
+        LinuxI2c { i2cdev, address }
+    }
+}
+
+impl ErrorType for LinuxI2c {
+    type Error = LinuxI2cError;
+}
+
+/// Implementation of the embedded-hal I2C trait.
+/// Each operation is sent as its own transfer, SCD30 device
+/// expects a stop condition between the write and the read phases.
+///
+impl I2c for LinuxI2c {
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        if address != self.address {
+            self.i2cdev
+                .set_slave_address(u16::from(address))
+                .map_err(LinuxI2cError)?;
+            self.address = address;
+        }
+        for operation in operations {
+            match operation {
+                Operation::Read(buffer) => self.i2cdev.read(buffer).map_err(LinuxI2cError)?,
+                Operation::Write(bytes) => self.i2cdev.write(bytes).map_err(LinuxI2cError)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Delay provider using the standard library thread sleep
+///
+#[derive(Debug, Default, Clone, Copy)]
+pub struct StdDelay;
+
+impl DelayNs for StdDelay {
+    fn delay_ns(&mut self, ns: u32) {
+        thread::sleep(time::Duration::from_nanos(u64::from(ns)));
+    }
+}
+
+/// Async Linux I2C bus for tokio, wraps a [`LinuxI2c`] and runs each
+/// transaction on the blocking threads of tokio with `spawn_blocking`,
+/// so the ioctl of the transfers does not block the executor
+///
+#[cfg(feature = "tokio")]
+pub struct TokioI2c {
+    bus: Arc<Mutex<LinuxI2c>>,
+}
+
+#[cfg(feature = "tokio")]
+impl TokioI2c {
+    /// Wraps the Linux I2C bus
+    ///
+    pub fn new(bus: LinuxI2c) -> TokioI2c {
+        TokioI2c {
+            bus: Arc::new(Mutex::new(bus)),
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl ErrorType for TokioI2c {
+    type Error = LinuxI2cError;
+}
+
+/// Implementation of the embedded-hal-async I2C trait.
+/// The blocking task can not borrow the buffers of the operations,
+/// it works on copies and the read bytes are copied back.
+///
+#[cfg(feature = "tokio")]
+impl embedded_hal_async::i2c::I2c for TokioI2c {
+    async fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        let mut buffers: Vec<(bool, Vec<u8>)> = operations
+            .iter()
+            .map(|operation| match operation {
+                Operation::Read(buffer) => (true, vec![0; buffer.len()]),
+                Operation::Write(bytes) => (false, bytes.to_vec()),
+            })
+            .collect();
+        let bus = Arc::clone(&self.bus);
+        let task = tokio::task::spawn_blocking(move || {
+            // A panic in a previous transfer does not leave the bus in a bad state
+            let mut bus = bus.lock().unwrap_or_else(PoisonError::into_inner);
+            let mut copies: Vec<Operation<'_>> = buffers
+                .iter_mut()
+                .map(|(read, buffer)| {
+                    if *read {
+                        Operation::Read(buffer)
+                    } else {
+                        Operation::Write(buffer)
+                    }
+                })
+                .collect();
+            let result = I2c::transaction(&mut *bus, address, &mut copies);
+            drop(copies);
+            (result, buffers)
+        });
+        let (result, buffers) = match task.await {
+            Ok(done) => done,
+            Err(e) if e.is_panic() => panic::resume_unwind(e.into_panic()),
+            // Cancelled, for example while the runtime shuts down
+            Err(e) => {
+                let e = io::Error::new(io::ErrorKind::Interrupted, e);
+                return Err(LinuxI2cError(LinuxI2CError::from(e)));
+            }
+        };
+        for (operation, (_, buffer)) in operations.iter_mut().zip(buffers) {
+            if let Operation::Read(read) = operation {
+                read.copy_from_slice(&buffer);
+            }
+        }
+        result
+    }
+}
+
+/// Async delay provider using tokio sleep
+///
+#[cfg(feature = "tokio")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TokioDelay;
+
+#[cfg(feature = "tokio")]
+impl embedded_hal_async::delay::DelayNs for TokioDelay {
+    async fn delay_ns(&mut self, ns: u32) {
+        tokio::time::sleep(time::Duration::from_nanos(u64::from(ns))).await;
+    }
+}
@@ -0,0 +1,98 @@
+// Copyright 2024, F. Stan
+//
+// Licensed under the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use crate::sensirion_frame;
+#[cfg(feature = "std")]
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Molar mass of CO2 in g/mol
+const CO2_MOLAR_MASS: f32 = 44.01;
+/// Universal gas constant in J/(mol K)
+pub(crate) const GAS_CONSTANT: f32 = 8.314_463;
+/// Standard atmospheric pressure in hPa (mbar)
+pub const STANDARD_PRESSURE: f32 = 1013.25;
+
+/// Milliseconds since the Unix epoch from the system clock
+#[cfg(feature = "std")]
+fn now_ms() -> Option<u64> {
+    let elapsed = SystemTime::now().duration_since(UNIX_EPOCH).ok()?;
+    u64::try_from(elapsed.as_millis()).ok()
+}
+
+/// Without a clock the time of the measurements is not known
+#[cfg(not(feature = "std"))]
+fn now_ms() -> Option<u64> {
+    None
+}
+
+/// One SCD30 reading, with CO2, temperature and relative humidity
+/// and the time when the reading was obtained from the device
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Measurement {
+    /// CO2 concentration in ppm
+    pub co2_ppm: f32,
+    /// Temperature in Celsius degrees
+    pub temperature_c: f32,
+    /// Relative humidity in %
+    pub humidity_pct: f32,
+    /// Time when the measurement was read from the device, in milliseconds
+    /// since the Unix epoch. Only known with the `std` feature, None without a clock
+    pub timestamp_ms: Option<u64>,
+}
+
+/// Implementation of the unit conversions for a measurement
+///
+impl Measurement {
+    /// Measurement from the six data words of the read measurement command,
+    /// CO2, temperature and humidity as floats of two words each
+    pub(crate) fn from_words(words: &[u16; 6]) -> Measurement {
+        Measurement {
+            co2_ppm: sensirion_frame::f32_from_words(words[0], words[1]),
+            temperature_c: sensirion_frame::f32_from_words(words[2], words[3]),
+            humidity_pct: sensirion_frame::f32_from_words(words[4], words[5]),
+            timestamp_ms: now_ms(),
+        }
+    }
+
+    /// Time when the measurement was read from the device, if known
+    ///
+    #[cfg(feature = "std")]
+    pub fn timestamp(&self) -> Option<SystemTime> {
+        self.timestamp_ms
+            .map(|ms| UNIX_EPOCH + Duration::from_millis(ms))
+    }
+
+    /// Temperature in Fahrenheit degrees
+    ///
+    pub fn temperature_f(&self) -> f32 {
+        self.temperature_c * 9.0 / 5.0 + 32.0
+    }
+
+    /// Temperature in Kelvin
+    ///
+    pub fn temperature_k(&self) -> f32 {
+        self.temperature_c + 273.15
+    }
+
+    /// CO2 concentration in mg/m³ at the measured temperature
+    /// and standard atmospheric pressure (1013.25 mbar)
+    ///
+    pub fn co2_mg_m3(&self) -> f32 {
+        self.co2_mg_m3_at(STANDARD_PRESSURE)
+    }
+
+    /// CO2 concentration in mg/m³ at the measured temperature
+    /// and the given ambient pressure in mbar, using the ideal gas law
+    ///
+    pub fn co2_mg_m3_at(&self, pressure_mbar: f32) -> f32 {
+        // ppm * M * p / (R * T), p in Pa, result in mg/m³
+        self.co2_ppm * CO2_MOLAR_MASS * pressure_mbar * 100.0
+            / (GAS_CONSTANT * self.temperature_k())
+            / 1000.0
+    }
+}
@@ -0,0 +1,453 @@
+// Copyright 2024, F. Stan
+//
+// Licensed under the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Modbus RTU transport for the SCD30 device
+//!
+//! Besides I2C, the SCD30 speaks Modbus RTU over its UART interface (19200 baud, 8 data bits,
+//! no parity, 1 stop bit, device address 0x61). [`Scd30Modbus`](crate::modbus::Scd30Modbus)
+//! exposes the same operations as [`Scd30`](crate::scd30::Scd30) over any `Read + Write` port,
+//! using function code 3 to read registers and 6 to write them, with Modbus CRC16 framing.
+//!
+//! Like the I2C driver, it waits through a `DelayNs` delay provider, for example after a
+//! soft reset while the device boots. With the `serialport` feature, `Scd30Modbus::open`
+//! opens a serial port with the settings required by the device.
+
+use crate::measurement::Measurement;
+use crate::scd30::{
+    check_forced_value, check_interval, check_pressure, offset_ticks, Scd30Error, Timing,
+};
+use crate::stream::millis;
+use embedded_hal::delay::DelayNs;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read, Write};
+
+/// Default Modbus address of the SCD30 device
+pub const MODBUS_ADDRESS: u8 = 0x61;
+/// Baud rate of the SCD30 UART interface
+pub const MODBUS_BAUD_RATE: u32 = 19200;
+
+/// Read holding registers function code
+const READ_HOLDING_REGISTERS: u8 = 0x03;
+/// Write single register function code
+const WRITE_SINGLE_REGISTER: u8 = 0x06;
+
+/// Register addresses from the interface description
+const FIRMWARE_REGISTER: u16 = 0x0020;
+const MEASUREMENTS_INTERVAL_REGISTER: u16 = 0x0025;
+const DATA_READY_REGISTER: u16 = 0x0027;
+const MEASUREMENTS_REGISTER: u16 = 0x0028;
+const SOFT_RESET_REGISTER: u16 = 0x0034;
+const TRIGGER_CONT_MEASUREMENTS_REGISTER: u16 = 0x0036;
+const STOP_CONT_MEASUREMENTS_REGISTER: u16 = 0x0037;
+const ALTITUDE_REGISTER: u16 = 0x0038;
+const FORCED_RECALIBRATION_REGISTER: u16 = 0x0039;
+const SELF_CALIBRATION_REGISTER: u16 = 0x003A;
+const TEMPERATURE_OFFSET_REGISTER: u16 = 0x003B;
+
+///
+/// Modbus error enum. Io error from the serial port,
+/// Exception when the device answers with an exception code
+/// and UnexpectedResponse when the response does not match the request
+///
+#[derive(Debug)]
+pub enum ModbusError {
+    /// Input/output error from the serial port
+    Io(io::Error),
+    /// Exception response from the device, with the function and the exception code
+    Exception { function: u8, code: u8 },
+    /// Response with a different address, function or length than expected
+    UnexpectedResponse,
+}
+
+///Implementation of display for ModbusError
+impl fmt::Display for ModbusError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ModbusError::Io(ref e) => fmt::Display::fmt(e, f),
+            ModbusError::Exception { function, code } => write!(
+                f,
+                "Modbus exception 0x{:02X} for function 0x{:02X}",
+                code, function
+            ),
+            ModbusError::UnexpectedResponse => fmt::Display::fmt("Unexpected Modbus response", f),
+        }
+    }
+}
+
+///Implementation for Error to ModbusError
+impl Error for ModbusError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            ModbusError::Io(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Modbus CRC16 (polynomial 0xA001 reflected, init 0xFFFF).
+/// It is sent low byte first at the end of each frame.
+///
+pub fn crc16(message: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+    for byte in message {
+        crc ^= u16::from(*byte);
+        for _ in 0..8 {
+            if (crc & 0x0001) != 0 {
+                crc = (crc >> 1) ^ 0xA001;
+            } else {
+                crc >>= 1;
+            }
+        }
+    }
+    crc
+}
+
+/// SCD30 over Modbus RTU, wraps a serial port and a delay
+/// provider and has implemented related SCD30 operations
+///
+/// The `command` of the communication errors is the register address.
+///
+pub struct Scd30Modbus<S, D> {
+    pub port: S,
+    pub delay: D,
+    pub address: u8,
+    /// Waits of the transport, only the boot time after a soft reset is used
+    pub timing: Timing,
+}
+
+/// Serial port specific constructors
+///
+#[cfg(feature = "serialport")]
+impl<D: DelayNs> Scd30Modbus<Box<dyn serialport::SerialPort>, D> {
+    /// Opens the serial port with the settings of the device,
+    /// 19200 baud, 8 data bits, no parity and 1 stop bit
+    ///
+    pub fn open(
+        path: &str,
+        delay: D,
+    ) -> Result<Scd30Modbus<Box<dyn serialport::SerialPort>, D>, serialport::Error> {
+        let port = serialport::new(path, MODBUS_BAUD_RATE)
+            .data_bits(serialport::DataBits::Eight)
+            .parity(serialport::Parity::None)
+            .stop_bits(serialport::StopBits::One)
+            .timeout(std::time::Duration::from_millis(500))
+            .open()?;
+        Ok(Scd30Modbus::from_port(port, delay))
+    }
+}
+
+/// Implementation of SCD30 related
+/// operations over Modbus
+///
+impl<S: Read + Write, D: DelayNs> Scd30Modbus<S, D> {
+    /// Create a new SCD30 Modbus Struct from any serial port and delay provider
+    ///
+    /// The device is expected on standard address 0x61.
+    ///
+    pub fn from_port(port: S, delay: D) -> Scd30Modbus<S, D> {
+        Scd30Modbus::from_port_with_address(port, delay, MODBUS_ADDRESS)
+    }
+
+    /// Create a new SCD30 Modbus Struct from any serial port and delay provider,
+    /// with the device on the given address
+    ///
+    pub fn from_port_with_address(port: S, delay: D, address: u8) -> Scd30Modbus<S, D> {
+        Scd30Modbus {
+            port,
+            delay,
+            address,
+            timing: Timing::default(),
+        }
+    }
+
+    /// Changes the waits of the transport
+    ///
+    pub fn with_timing(mut self, timing: Timing) -> Scd30Modbus<S, D> {
+        self.timing = timing;
+        self
+    }
+
+    /// Releases the serial port
+    ///
+    pub fn release(self) -> S {
+        self.port
+    }
+
+    /// Sends a request frame of function, register and value,
+    /// followed by the CRC16
+    fn send(
+        &mut self,
+        function: u8,
+        register: u16,
+        value: u16,
+    ) -> Result<(), Scd30Error<ModbusError>> {
+        let register_in_bytes = register.to_be_bytes();
+        let value_in_bytes = value.to_be_bytes();
+        let mut frame: [u8; 8] = [
+            self.address,
+            function,
+            register_in_bytes[0],
+            register_in_bytes[1],
+            value_in_bytes[0],
+            value_in_bytes[1],
+            0,
+            0,
+        ];
+        let crc = crc16(&frame[..6]).to_le_bytes();
+        frame[6] = crc[0];
+        frame[7] = crc[1];
+        match self.port.write_all(&frame).and_then(|_| self.port.flush()) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(Scd30Error::write(register, ModbusError::Io(e))),
+        }
+    }
+
+    /// Receives a response frame of the given length, checking address,
+    /// function, exception and CRC16
+    fn receive(
+        &mut self,
+        function: u8,
+        register: u16,
+        response: &mut [u8],
+    ) -> Result<(), Scd30Error<ModbusError>> {
+        if let Err(e) = self.port.read_exact(&mut response[..2]) {
+            return Err(Scd30Error::read(register, ModbusError::Io(e)));
+        }
+        if response[0] != self.address {
+            return Err(Scd30Error::read(register, ModbusError::UnexpectedResponse));
+        }
+        if response[1] == function | 0x80 {
+            // Exception frame: address, function, code and CRC16
+            let mut exception: [u8; 5] = [response[0], response[1], 0, 0, 0];
+            if let Err(e) = self.port.read_exact(&mut exception[2..]) {
+                return Err(Scd30Error::read(register, ModbusError::Io(e)));
+            }
+            if crc16(&exception[..3]).to_le_bytes() != [exception[3], exception[4]] {
+                return Err(Scd30Error::ChecksumError);
+            }
+            return Err(Scd30Error::read(
+                register,
+                ModbusError::Exception {
+                    function,
+                    code: exception[2],
+                },
+            ));
+        }
+        if response[1] != function {
+            return Err(Scd30Error::read(register, ModbusError::UnexpectedResponse));
+        }
+        if let Err(e) = self.port.read_exact(&mut response[2..]) {
+            return Err(Scd30Error::read(register, ModbusError::Io(e)));
+        }
+        let length = response.len();
+        if crc16(&response[..length - 2]).to_le_bytes()
+            != [response[length - 2], response[length - 1]]
+        {
+            return Err(Scd30Error::ChecksumError);
+        }
+        Ok(())
+    }
+
+    /// Reads `words.len()` registers starting at the given one
+    fn read_registers(
+        &mut self,
+        function: u8,
+        register: u16,
+        words: &mut [u16],
+    ) -> Result<(), Scd30Error<ModbusError>> {
+        self.send(function, register, words.len() as u16)?;
+        // Address, function, byte count, data and CRC16
+        let mut response: [u8; 17] = [0; 17];
+        let response = &mut response[..5 + words.len() * 2];
+        self.receive(function, register, response)?;
+        if usize::from(response[2]) != words.len() * 2 {
+            return Err(Scd30Error::read(register, ModbusError::UnexpectedResponse));
+        }
+        for (word, bytes) in words.iter_mut().zip(response[3..].chunks(2)) {
+            *word = u16::from_be_bytes([bytes[0], bytes[1]]);
+        }
+        Ok(())
+    }
+
+    /// Reads one holding register
+    fn read_register(&mut self, register: u16) -> Result<u16, Scd30Error<ModbusError>> {
+        let mut words = [0; 1];
+        self.read_registers(READ_HOLDING_REGISTERS, register, &mut words)?;
+        Ok(words[0])
+    }
+
+    /// Writes one register, the device answers with an echo of the request
+    fn write_register(&mut self, register: u16, value: u16) -> Result<(), Scd30Error<ModbusError>> {
+        self.send(WRITE_SINGLE_REGISTER, register, value)?;
+        let mut response: [u8; 8] = [0; 8];
+        self.receive(WRITE_SINGLE_REGISTER, register, &mut response)?;
+        let register_in_bytes = register.to_be_bytes();
+        let value_in_bytes = value.to_be_bytes();
+        if response[2..6]
+            != [
+                register_in_bytes[0],
+                register_in_bytes[1],
+                value_in_bytes[0],
+                value_in_bytes[1],
+            ]
+        {
+            return Err(Scd30Error::read(register, ModbusError::UnexpectedResponse));
+        }
+        Ok(())
+    }
+
+    /// Checks the firmware version of the SCD30 device.
+    /// If fails, return SCD30Error.
+    /// Else returns the firmware version.
+    ///
+    pub fn check_firmware(&mut self) -> Result<u16, Scd30Error<ModbusError>> {
+        self.read_register(FIRMWARE_REGISTER)
+    }
+
+    /// Trigger the continous measurements for SCD30 device.
+    /// Ambient pressure compensation is disabled.
+    ///
+    pub fn trigger_cont_measurements(&mut self) -> Result<(), Scd30Error<ModbusError>> {
+        self.trigger_cont_measurements_with_pressure(0)
+    }
+
+    /// Trigger the continous measurements for SCD30 device
+    /// with ambient pressure compensation.
+    /// Pressure is in mbar, between 700 and 1400, 0 disables the compensation.
+    ///
+    pub fn trigger_cont_measurements_with_pressure(
+        &mut self,
+        pressure: u16,
+    ) -> Result<(), Scd30Error<ModbusError>> {
+        check_pressure(pressure)?;
+        self.write_register(TRIGGER_CONT_MEASUREMENTS_REGISTER, pressure)
+    }
+
+    /// Updates the ambient pressure used for compensation while the
+    /// continous measurements are running.
+    ///
+    pub fn set_ambient_pressure(&mut self, pressure: u16) -> Result<(), Scd30Error<ModbusError>> {
+        self.trigger_cont_measurements_with_pressure(pressure)
+    }
+
+    /// Stops the continous measurements for SCD30 device.
+    ///
+    pub fn stop_cont_measurements(&mut self) -> Result<(), Scd30Error<ModbusError>> {
+        self.write_register(STOP_CONT_MEASUREMENTS_REGISTER, 0x0001)
+    }
+
+    /// Sets the measurements interval for the device, between 2 and 1800 seconds.
+    ///
+    pub fn set_measurements_interval(
+        &mut self,
+        seconds: u16,
+    ) -> Result<(), Scd30Error<ModbusError>> {
+        check_interval(seconds)?;
+        self.write_register(MEASUREMENTS_INTERVAL_REGISTER, seconds)
+    }
+
+    /// Gets the measurements interval of the device in seconds.
+    ///
+    pub fn get_measurements_interval(&mut self) -> Result<u16, Scd30Error<ModbusError>> {
+        self.read_register(MEASUREMENTS_INTERVAL_REGISTER)
+    }
+
+    /// Gets if the device is ready for reading a measurement.
+    ///
+    pub fn get_data_ready(&mut self) -> Result<bool, Scd30Error<ModbusError>> {
+        Ok(self.read_register(DATA_READY_REGISTER)? == 0x0001)
+    }
+
+    /// Get CO2, Temperature and Humidity for the device as a Measurement.
+    ///
+    pub fn get_measurements(&mut self) -> Result<Measurement, Scd30Error<ModbusError>> {
+        let mut words = [0; 6];
+        self.read_registers(READ_HOLDING_REGISTERS, MEASUREMENTS_REGISTER, &mut words)?;
+        Ok(Measurement::from_words(&words))
+    }
+
+    /// Gets if the devive is in self calibration procedure or not.
+    ///
+    pub fn get_self_calibration_status(&mut self) -> Result<bool, Scd30Error<ModbusError>> {
+        Ok(self.read_register(SELF_CALIBRATION_REGISTER)? == 0x0001)
+    }
+
+    /// Set self calibration configuration.
+    ///
+    pub fn set_self_calibration(&mut self, active: bool) -> Result<(), Scd30Error<ModbusError>> {
+        self.write_register(SELF_CALIBRATION_REGISTER, u16::from(active))
+    }
+
+    /// Soft reset the sensor device, and waits for the device to boot.
+    ///
+    pub fn soft_reset(&mut self) -> Result<(), Scd30Error<ModbusError>> {
+        self.write_register(SOFT_RESET_REGISTER, 0x0001)?;
+        // The device does not answer until it boots again
+        self.delay.delay_ms(millis(self.timing.boot_time));
+        Ok(())
+    }
+
+    /// Gets the set altitude of the device in meters from sea level.
+    ///
+    pub fn get_altitude(&mut self) -> Result<u16, Scd30Error<ModbusError>> {
+        self.read_register(ALTITUDE_REGISTER)
+    }
+
+    /// Sets the altitude for the device in meters from sea level.
+    ///
+    pub fn set_altitude(&mut self, altitude: u16) -> Result<(), Scd30Error<ModbusError>> {
+        self.write_register(ALTITUDE_REGISTER, altitude)
+    }
+
+    /// Gets the temperature offset of the device in ticks of 0.01 Celsius.
+    ///
+    pub fn get_temperature_offset(&mut self) -> Result<u16, Scd30Error<ModbusError>> {
+        self.read_register(TEMPERATURE_OFFSET_REGISTER)
+    }
+
+    /// Sets the temperature offset of the device in ticks of 0.01 Celsius.
+    ///
+    pub fn set_temperature_offset(&mut self, offset: u16) -> Result<(), Scd30Error<ModbusError>> {
+        self.write_register(TEMPERATURE_OFFSET_REGISTER, offset)
+    }
+
+    /// Gets the temperature offset of the device in Celsius.
+    ///
+    pub fn get_temperature_offset_celsius(&mut self) -> Result<f32, Scd30Error<ModbusError>> {
+        Ok(f32::from(self.get_temperature_offset()?) / 100.0)
+    }
+
+    /// Sets the temperature offset of the device in Celsius, rounded to the
+    /// 0.01 Celsius ticks of the device. Values outside 0 to
+    /// [`MAX_TEMPERATURE_OFFSET`](crate::scd30::MAX_TEMPERATURE_OFFSET) return
+    /// an OutOfRange error without writing to the device.
+    ///
+    pub fn set_temperature_offset_celsius(
+        &mut self,
+        offset_c: f32,
+    ) -> Result<(), Scd30Error<ModbusError>> {
+        let ticks = offset_ticks(offset_c)?;
+        self.set_temperature_offset(ticks)
+    }
+
+    /// Checks the forced calibration value of the device in ppm.
+    ///
+    pub fn get_forced_value(&mut self) -> Result<u16, Scd30Error<ModbusError>> {
+        self.read_register(FORCED_RECALIBRATION_REGISTER)
+    }
+
+    /// Sets a force recalibration value to the device in ppm, between 400 and 2000.
+    ///
+    pub fn set_force_recalibration_value(
+        &mut self,
+        forced_value: u16,
+    ) -> Result<(), Scd30Error<ModbusError>> {
+        check_forced_value(forced_value)?;
+        self.write_register(FORCED_RECALIBRATION_REGISTER, forced_value)
+    }
+}
@@ -0,0 +1,449 @@
+// Copyright 2024, F. Stan
+//
+// Licensed under the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! MQTT publisher for the SCD30 readings, with Home Assistant discovery
+//!
+//! [`publish`](crate::mqtt::publish) polls the measurements of a [`Scd30`](crate::scd30::Scd30)
+//! and publishes them as JSON on `<base_topic>/state`. On every connection to the broker it
+//! announces the device on `<base_topic>/availability` (with a last will of `offline`),
+//! publishes the Home Assistant discovery config of the CO2, temperature and humidity
+//! entities and subscribes to the command topics:
+//!
+//! * `<base_topic>/set/interval`: measurement interval in seconds
+//! * `<base_topic>/set/altitude`: altitude in meters
+//! * `<base_topic>/set/frc`: forced recalibration value in ppm
+//!
+//! Commands are applied while waiting for the next measurement, and their results are
+//! published on `<base_topic>/result`. Errors reading the device are published on
+//! `<base_topic>/error`. While the broker is not reachable, the messages beyond the
+//! capacity of the client queue are dropped instead of stopping the measurements.
+//!
+//!```no_run
+//!use scd30_i2c::mqtt::{self, MqttConfig};
+//!use scd30_i2c::scd30::Scd30;
+//!
+//!let mut scd = Scd30::new().unwrap();
+//!scd.trigger_cont_measurements().unwrap();
+//!let config = MqttConfig::new("localhost", "living_room");
+//!mqtt::publish(&mut scd, &config).unwrap();
+//!```
+
+use crate::measurement::Measurement;
+use crate::scd30::{Scd30, Scd30Error};
+use crate::stream::{self, MeasurementSource, RecoveryPolicy, StreamState, DEFAULT_POLL_INTERVAL};
+use embedded_hal::delay::DelayNs;
+use embedded_hal::i2c::I2c;
+use rumqttc::{Client, Event, LastWill, MqttOptions, Packet, QoS};
+use serde_json::{json, Value};
+use std::error::Error;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Payload of the availability topic when the device is online
+pub const ONLINE: &str = "online";
+/// Payload of the availability topic when the device is offline
+pub const OFFLINE: &str = "offline";
+
+/// Configuration of the MQTT publisher
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MqttConfig {
+    /// Broker host
+    pub host: String,
+    /// Broker port
+    pub port: u16,
+    /// MQTT client id
+    pub client_id: String,
+    /// Optional user name and password
+    pub credentials: Option<(String, String)>,
+    /// Node id, used in the discovery topics and unique ids
+    pub node_id: String,
+    /// Name of the device in Home Assistant
+    pub device_name: String,
+    /// Base of the state, availability and command topics
+    pub base_topic: String,
+    /// Home Assistant discovery prefix
+    pub discovery_prefix: String,
+}
+
+impl MqttConfig {
+    /// Create a configuration for the given broker host and node id,
+    /// with port 1883, base topic `scd30/<node_id>` and discovery
+    /// prefix `homeassistant`
+    ///
+    pub fn new(host: &str, node_id: &str) -> MqttConfig {
+        MqttConfig {
+            host: host.to_string(),
+            port: 1883,
+            client_id: format!("scd30-{}", node_id),
+            credentials: None,
+            node_id: node_id.to_string(),
+            device_name: format!("SCD30 {}", node_id),
+            base_topic: format!("scd30/{}", node_id),
+            discovery_prefix: "homeassistant".to_string(),
+        }
+    }
+
+    /// Topic where the measurements are published
+    pub fn state_topic(&self) -> String {
+        format!("{}/state", self.base_topic)
+    }
+
+    /// Topic with the online/offline state of the device
+    pub fn availability_topic(&self) -> String {
+        format!("{}/availability", self.base_topic)
+    }
+
+    /// Topic where the results of the commands are published
+    pub fn result_topic(&self) -> String {
+        format!("{}/result", self.base_topic)
+    }
+
+    /// Topic where the errors reading the device are published
+    pub fn error_topic(&self) -> String {
+        format!("{}/error", self.base_topic)
+    }
+
+    /// Topic of the given command, `interval`, `altitude` or `frc`
+    pub fn command_topic(&self, command: &str) -> String {
+        format!("{}/set/{}", self.base_topic, command)
+    }
+
+    /// Home Assistant discovery topics and their config payloads,
+    /// one per entity: CO2, temperature and humidity
+    ///
+    pub fn discovery(&self) -> Vec<(String, Value)> {
+        let entities = [
+            ("co2", "CO2", "carbon_dioxide", "ppm", "co2_ppm"),
+            (
+                "temperature",
+                "Temperature",
+                "temperature",
+                "°C",
+                "temperature_c",
+            ),
+            ("humidity", "Humidity", "humidity", "%", "humidity_pct"),
+        ];
+        entities
+            .iter()
+            .map(|(object, name, class, unit, field)| {
+                let topic = format!(
+                    "{}/sensor/{}/{}/config",
+                    self.discovery_prefix, self.node_id, object
+                );
+                let config = json!({
+                    "name": name,
+                    "unique_id": format!("{}_{}", self.node_id, object),
+                    "object_id": format!("{}_{}", self.node_id, object),
+                    "device_class": class,
+                    "state_class": "measurement",
+                    "unit_of_measurement": unit,
+                    "state_topic": self.state_topic(),
+                    "value_template": format!("{{{{ value_json.{} }}}}", field),
+                    "availability_topic": self.availability_topic(),
+                    "payload_available": ONLINE,
+                    "payload_not_available": OFFLINE,
+                    "device": {
+                        "identifiers": [format!("scd30_{}", self.node_id)],
+                        "name": self.device_name,
+                        "manufacturer": "Sensirion",
+                        "model": "SCD30",
+                    },
+                });
+                (topic, config)
+            })
+            .collect()
+    }
+
+    /// Decodes a message on a command topic. Returns None if the topic
+    /// is not a command topic, and an error if the payload is not valid
+    ///
+    pub fn parse_command(&self, topic: &str, payload: &[u8]) -> Option<Result<Command, String>> {
+        let command = topic.strip_prefix(&format!("{}/set/", self.base_topic))?;
+        let value = match std::str::from_utf8(payload)
+            .ok()
+            .and_then(|payload| payload.trim().parse::<u16>().ok())
+        {
+            Some(value) => value,
+            None => {
+                return Some(Err(format!(
+                    "invalid value for {}: {}",
+                    command,
+                    String::from_utf8_lossy(payload)
+                )))
+            }
+        };
+        match command {
+            "interval" => Some(Ok(Command::SetInterval(value))),
+            "altitude" => Some(Ok(Command::SetAltitude(value))),
+            "frc" => Some(Ok(Command::SetForcedRecalibration(value))),
+            _ => Some(Err(format!("unknown command {}", command))),
+        }
+    }
+}
+
+/// Commands received on the command topics
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    /// Sets the measurement interval in seconds
+    SetInterval(u16),
+    /// Sets the altitude in meters
+    SetAltitude(u16),
+    /// Sets the forced recalibration value in ppm
+    SetForcedRecalibration(u16),
+}
+
+impl Command {
+    /// Applies the command to the device
+    ///
+    pub fn apply<I: I2c, D: DelayNs>(
+        &self,
+        scd: &mut Scd30<I, D>,
+    ) -> Result<(), Scd30Error<I::Error>> {
+        match *self {
+            Command::SetInterval(seconds) => scd.set_measurements_interval(seconds),
+            Command::SetAltitude(altitude) => scd.set_altitude(altitude),
+            Command::SetForcedRecalibration(value) => scd.set_force_recalibration_value(value),
+        }
+    }
+}
+
+/// JSON payload of a measurement on the state topic
+///
+pub fn state_payload(measurement: &Measurement) -> Value {
+    json!({
+        "co2_ppm": measurement.co2_ppm,
+        "temperature_c": measurement.temperature_c,
+        "humidity_pct": measurement.humidity_pct,
+        "timestamp": measurement.timestamp_ms.map(|ms| ms as f64 / 1000.0),
+    })
+}
+
+/// Polls the measurements of the device forever and publishes them on the broker.
+/// The continous measurements must be already triggered.
+/// Publishing never blocks the measurements: while the broker is not reachable
+/// the messages are queued up to the capacity of the client, the rest are dropped.
+/// Returns only if the connection with the broker stops.
+///
+pub fn publish<I: I2c, D: DelayNs>(
+    scd: &mut Scd30<I, D>,
+    config: &MqttConfig,
+) -> Result<(), Box<dyn Error>> {
+    let mut options = MqttOptions::new(&config.client_id, &config.host, config.port);
+    options.set_keep_alive(Duration::from_secs(30));
+    options.set_last_will(LastWill::new(
+        config.availability_topic(),
+        OFFLINE,
+        QoS::AtLeastOnce,
+        true,
+    ));
+    if let Some((user, password)) = &config.credentials {
+        options.set_credentials(user, password);
+    }
+    let (client, mut connection) = Client::new(options, 16);
+
+    // The connection runs in its own thread, forwarding the connections and the
+    // commands to the polling loop. It is the only one emptying the request channel
+    // of the client, so it never publishes itself, it could wait forever for space.
+    let (messages, received) = mpsc::channel();
+    let connection_config = config.clone();
+    thread::spawn(move || {
+        for event in connection.iter() {
+            let message = match event {
+                Ok(Event::Incoming(Packet::ConnAck(_))) => Some(Message::Connected),
+                Ok(Event::Incoming(Packet::Publish(message))) => connection_config
+                    .parse_command(&message.topic, &message.payload)
+                    .map(Message::Command),
+                Ok(_) => None,
+                Err(_) => {
+                    thread::sleep(Duration::from_secs(1));
+                    None
+                }
+            };
+            if let Some(message) = message {
+                if messages.send(message).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+
+    let mut publisher = Publisher {
+        scd,
+        client: &client,
+        config,
+        received,
+        announce: false,
+        closed: false,
+    };
+    publisher.run()
+}
+
+/// JSON payload of a device error on the error topic
+///
+pub fn error_payload<E>(error: &Scd30Error<E>, time: SystemTime) -> Value {
+    json!({
+        "error": error.to_string(),
+        "timestamp": time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs_f64(),
+    })
+}
+
+/// Message from the connection thread to the measurements loop
+enum Message {
+    /// Connected to the broker, the device must be announced
+    Connected,
+    /// Command received, or the error parsing it
+    Command(Result<Command, String>),
+}
+
+/// Measurements loop of the publisher
+struct Publisher<'a, I, D> {
+    scd: &'a mut Scd30<I, D>,
+    client: &'a Client,
+    config: &'a MqttConfig,
+    received: Receiver<Message>,
+    announce: bool,
+    closed: bool,
+}
+
+impl<I: I2c, D: DelayNs> Publisher<'_, I, D> {
+    /// Publishes the measurements and the errors until the connection thread stops
+    fn run(&mut self) -> Result<(), Box<dyn Error>> {
+        let mut state = StreamState::new(
+            RecoveryPolicy::Continue {
+                wait: Duration::from_secs(1),
+            },
+            DEFAULT_POLL_INTERVAL,
+        );
+        while let Some(result) = state.next_item(self) {
+            if self.closed {
+                return Err("connection with the broker closed".into());
+            }
+            let (topic, payload) = match result {
+                Ok(measurement) => (self.config.state_topic(), state_payload(&measurement)),
+                Err(e) => (
+                    self.config.error_topic(),
+                    error_payload(&e, SystemTime::now()),
+                ),
+            };
+            self.try_publish(topic, false, payload.to_string());
+        }
+        Ok(())
+    }
+
+    /// Queues the message without waiting. While the broker is not reachable
+    /// the request channel fills up, then the message is dropped.
+    fn try_publish(&self, topic: String, retain: bool, payload: String) -> bool {
+        self.client
+            .try_publish(topic, QoS::AtLeastOnce, retain, payload)
+            .is_ok()
+    }
+
+    /// Sleeps the given duration in steps of the poll interval, handling the
+    /// received messages between them. Stops early, returning true, if a command
+    /// changed the measurement interval.
+    fn sleep_handling_messages(&mut self, duration: Duration) -> bool {
+        let mut remaining = duration;
+        loop {
+            match self.handle_messages() {
+                Ok(true) => return true,
+                Ok(false) => {}
+                Err(_) => {
+                    self.closed = true;
+                    return false;
+                }
+            }
+            if remaining.is_zero() {
+                return false;
+            }
+            let step = remaining.min(DEFAULT_POLL_INTERVAL);
+            self.scd.delay.delay_ms(stream::millis(step));
+            remaining -= step;
+        }
+    }
+
+    /// Announces the device after a connection, applies the received commands and
+    /// publishes their results. Returns true if the measurement interval was changed.
+    fn handle_messages(&mut self) -> Result<bool, TryRecvError> {
+        let mut interval_changed = false;
+        loop {
+            let command = match self.received.try_recv() {
+                Ok(Message::Connected) => {
+                    self.announce = true;
+                    continue;
+                }
+                Ok(Message::Command(command)) => command,
+                Err(TryRecvError::Empty) => break,
+                Err(e) => return Err(e),
+            };
+            let result = match command {
+                Ok(command) => {
+                    let result = command.apply(self.scd);
+                    if let (Command::SetInterval(_), Ok(_)) = (command, &result) {
+                        interval_changed = true;
+                    }
+                    result
+                        .map(|_| format!("{:?} applied", command))
+                        .map_err(|e| format!("{:?} failed: {}", command, e))
+                }
+                Err(e) => Err(e),
+            };
+            let payload = match result {
+                Ok(message) => json!({ "ok": true, "message": message }),
+                Err(message) => json!({ "ok": false, "message": message }),
+            };
+            self.try_publish(self.config.result_topic(), false, payload.to_string());
+        }
+        // Announcing waits for space in the request channel, it may still hold
+        // the messages queued while the broker was not reachable
+        if self.announce && self.try_announce() {
+            self.announce = false;
+        }
+        Ok(interval_changed)
+    }
+
+    /// Publishes the availability and the discovery config, and subscribes to the
+    /// commands. Returns false if the request channel is full, to try again later.
+    fn try_announce(&self) -> bool {
+        for (topic, payload) in self.config.discovery() {
+            if !self.try_publish(topic, true, payload.to_string()) {
+                return false;
+            }
+        }
+        self.try_publish(self.config.availability_topic(), true, ONLINE.to_string())
+            && self
+                .client
+                .try_subscribe(
+                    format!("{}/set/+", self.config.base_topic),
+                    QoS::AtLeastOnce,
+                )
+                .is_ok()
+    }
+}
+
+/// The measurements loop of the publisher handles the MQTT messages while it waits
+impl<I: I2c, D: DelayNs> MeasurementSource for Publisher<'_, I, D> {
+    type Error = I::Error;
+
+    fn read_interval(&mut self) -> Result<u16, Scd30Error<I::Error>> {
+        self.scd.get_measurements_interval()
+    }
+
+    fn data_ready(&mut self) -> Result<bool, Scd30Error<I::Error>> {
+        self.scd.get_data_ready()
+    }
+
+    fn read_measurement(&mut self) -> Result<Measurement, Scd30Error<I::Error>> {
+        self.scd.get_measurements()
+    }
+
+    fn sleep(&mut self, duration: Duration) -> bool {
+        self.sleep_handling_messages(duration)
+    }
+}
@@ -0,0 +1,201 @@
+// Copyright 2024, F. Stan
+//
+// Licensed under the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Psychrometric quantities derived from the temperature and relative humidity
+//!
+//! The functions take the temperature in Celsius and the relative humidity in %, as
+//! returned in a [`Measurement`](crate::measurement::Measurement), and the quantities
+//! depending on the pressure take the ambient pressure in mbar,
+//! [`STANDARD_PRESSURE`](crate::measurement::STANDARD_PRESSURE) when not known. Saturation vapour
+//! pressure uses the Magnus formula with the coefficients of Alduchov and Eskridge (1996),
+//! within 0.4 % of the reference tables from -40 to 50 Celsius, which covers the SCD30
+//! operating range of 0 to 50 Celsius. Outside that range the results are extrapolations.
+//!
+//!```no_run
+//!use scd30_i2c::scd30::Scd30;
+//!
+//!let mut scd = Scd30::new().unwrap();
+//!scd.trigger_cont_measurements().unwrap();
+//!let measurement = scd.get_measurements().unwrap();
+//!let derived = measurement.psychrometrics_at(950.0);
+//!println!(
+//!    "Dew point: {:.1} C VPD: {:.2} kPa",
+//!    derived.dew_point_c, derived.vapour_pressure_deficit_kpa
+//!);
+//!```
+
+use crate::measurement::{Measurement, GAS_CONSTANT, STANDARD_PRESSURE};
+
+/// Magnus formula coefficient, dimensionless
+const MAGNUS_A: f32 = 17.625;
+/// Magnus formula coefficient in Celsius
+const MAGNUS_B: f32 = 243.04;
+/// Magnus formula saturation vapour pressure at 0 Celsius, in hPa (mbar)
+const MAGNUS_C: f32 = 6.1094;
+/// Molar mass of water in g/mol
+const WATER_MOLAR_MASS: f32 = 18.015;
+/// Ratio of the molar masses of water and dry air
+const MOLAR_MASS_RATIO: f32 = 0.621_945;
+
+/// Quantities derived from one reading, see the functions of this module
+/// for the formula and validity range of each one
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Psychrometrics {
+    /// Dew point in Celsius
+    pub dew_point_c: f32,
+    /// Partial pressure of the water vapour in hPa (mbar)
+    pub vapour_pressure_hpa: f32,
+    /// Water vapour density in g/m³
+    pub absolute_humidity_g_m3: f32,
+    /// Mass of water vapour per mass of dry air in g/kg
+    pub humidity_ratio_g_kg: f32,
+    /// Vapour pressure deficit in kPa
+    pub vapour_pressure_deficit_kpa: f32,
+    /// Heat index in Celsius
+    pub heat_index_c: f32,
+}
+
+impl Psychrometrics {
+    /// Derives all the quantities from the temperature in Celsius, the relative
+    /// humidity in % and the ambient pressure in mbar
+    ///
+    pub fn new(temperature_c: f32, humidity_pct: f32, pressure_mbar: f32) -> Psychrometrics {
+        Psychrometrics {
+            dew_point_c: dew_point_c(temperature_c, humidity_pct),
+            vapour_pressure_hpa: vapour_pressure_hpa(temperature_c, humidity_pct),
+            absolute_humidity_g_m3: absolute_humidity_g_m3(temperature_c, humidity_pct),
+            humidity_ratio_g_kg: humidity_ratio_g_kg(temperature_c, humidity_pct, pressure_mbar),
+            vapour_pressure_deficit_kpa: vapour_pressure_deficit_kpa(temperature_c, humidity_pct),
+            heat_index_c: heat_index_c(temperature_c, humidity_pct),
+        }
+    }
+}
+
+/// Implementation of the psychrometric quantities for a measurement
+///
+impl Measurement {
+    /// Psychrometric quantities at standard atmospheric pressure (1013.25 mbar)
+    ///
+    pub fn psychrometrics(&self) -> Psychrometrics {
+        self.psychrometrics_at(STANDARD_PRESSURE)
+    }
+
+    /// Psychrometric quantities at the given ambient pressure in mbar
+    ///
+    pub fn psychrometrics_at(&self, pressure_mbar: f32) -> Psychrometrics {
+        Psychrometrics::new(self.temperature_c, self.humidity_pct, pressure_mbar)
+    }
+}
+
+/// Saturation vapour pressure over water in hPa (mbar), Magnus formula
+///
+/// `es = 6.1094 * exp(17.625 * T / (T + 243.04))`
+///
+/// Valid from -40 to 50 Celsius, within 0.4 % of the reference tables.
+///
+pub fn saturation_vapour_pressure_hpa(temperature_c: f32) -> f32 {
+    MAGNUS_C * (MAGNUS_A * temperature_c / (temperature_c + MAGNUS_B)).exp()
+}
+
+/// Partial pressure of the water vapour in hPa (mbar)
+///
+/// `e = RH / 100 * es(T)`
+///
+/// Same validity as [`saturation_vapour_pressure_hpa`], relative humidity from 0 to 100 %.
+///
+pub fn vapour_pressure_hpa(temperature_c: f32, humidity_pct: f32) -> f32 {
+    humidity_pct / 100.0 * saturation_vapour_pressure_hpa(temperature_c)
+}
+
+/// Dew point in Celsius, inverse of the Magnus formula
+///
+/// `γ = ln(RH / 100) + 17.625 * T / (T + 243.04)`, `Td = 243.04 * γ / (17.625 - γ)`
+///
+/// Valid from -40 to 50 Celsius and relative humidity from 1 to 100 %, within
+/// 0.1 Celsius of the reference tables. A relative humidity of 0 % has no dew
+/// point and returns NaN.
+///
+pub fn dew_point_c(temperature_c: f32, humidity_pct: f32) -> f32 {
+    let gamma = (humidity_pct / 100.0).ln() + MAGNUS_A * temperature_c / (temperature_c + MAGNUS_B);
+    MAGNUS_B * gamma / (MAGNUS_A - gamma)
+}
+
+/// Absolute humidity, the water vapour density, in g/m³ from the ideal gas law
+///
+/// `AH = e * Mw / (R * T)`, with e in Pa, Mw = 18.015 g/mol and T in Kelvin
+///
+/// Same validity as [`vapour_pressure_hpa`], within 0.5 % of the reference tables.
+///
+pub fn absolute_humidity_g_m3(temperature_c: f32, humidity_pct: f32) -> f32 {
+    let vapour_pressure_pa = vapour_pressure_hpa(temperature_c, humidity_pct) * 100.0;
+    vapour_pressure_pa * WATER_MOLAR_MASS / (GAS_CONSTANT * (temperature_c + 273.15))
+}
+
+/// Humidity ratio, or mixing ratio, in grams of water vapour per kilogram of dry air
+///
+/// `W = 621.945 * e / (p - e)`, with e and p in the same unit
+///
+/// Same validity as [`vapour_pressure_hpa`], for pressures well above the vapour
+/// pressure. Without the enhancement factor of moist air it is within 1 % of the
+/// ASHRAE tables.
+///
+pub fn humidity_ratio_g_kg(temperature_c: f32, humidity_pct: f32, pressure_mbar: f32) -> f32 {
+    let vapour_pressure = vapour_pressure_hpa(temperature_c, humidity_pct);
+    1000.0 * MOLAR_MASS_RATIO * vapour_pressure / (pressure_mbar - vapour_pressure)
+}
+
+/// Vapour pressure deficit in kPa, the difference between the saturation and
+/// the actual vapour pressure
+///
+/// `VPD = es(T) * (1 - RH / 100) / 10`
+///
+/// Same validity as [`saturation_vapour_pressure_hpa`].
+///
+pub fn vapour_pressure_deficit_kpa(temperature_c: f32, humidity_pct: f32) -> f32 {
+    saturation_vapour_pressure_hpa(temperature_c) * (1.0 - humidity_pct / 100.0) / 10.0
+}
+
+/// Heat index in Celsius, following the algorithm of the US National Weather Service
+///
+/// With the temperature T in Fahrenheit, it starts with the Steadman approximation
+/// `HI = 0.5 * (T + 61 + (T - 68) * 1.2 + RH * 0.094)`. If the average of it and T
+/// is 80 Fahrenheit or more, it uses the Rothfusz regression, adjusted for relative
+/// humidity below 13 % or above 85 %:
+///
+///```text
+///HI = -42.379 + 2.04901523 T + 10.14333127 RH - 0.22475541 T RH - 0.00683783 T²
+///     - 0.05481717 RH² + 0.00122874 T² RH + 0.00085282 T RH² - 0.00000199 T² RH²
+///```
+///
+/// Meaningful from 27 to 50 Celsius with relative humidity of 40 % or more, within
+/// 1 Fahrenheit of the NWS table. Below 27 Celsius it stays within about 1 Celsius
+/// of the temperature.
+///
+pub fn heat_index_c(temperature_c: f32, humidity_pct: f32) -> f32 {
+    let t = temperature_c * 9.0 / 5.0 + 32.0;
+    let rh = humidity_pct;
+    let simple = 0.5 * (t + 61.0 + (t - 68.0) * 1.2 + rh * 0.094);
+    let heat_index_f = if (simple + t) / 2.0 < 80.0 {
+        simple
+    } else {
+        let mut hi = -42.379 + 2.049_015_2 * t + 10.143_331 * rh
+            - 0.224_755_4 * t * rh
+            - 0.006_837_83 * t * t
+            - 0.054_817_17 * rh * rh
+            + 0.001_228_74 * t * t * rh
+            + 0.000_852_82 * t * rh * rh
+            - 0.000_001_99 * t * t * rh * rh;
+        if rh < 13.0 && (80.0..=112.0).contains(&t) {
+            hi -= (13.0 - rh) / 4.0 * ((17.0 - (t - 95.0).abs()) / 17.0).sqrt();
+        } else if rh > 85.0 && (80.0..=87.0).contains(&t) {
+            hi += (rh - 85.0) / 10.0 * (87.0 - t) / 5.0;
+        }
+        hi
+    };
+    (heat_index_f - 32.0) * 5.0 / 9.0
+}
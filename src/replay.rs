@@ -0,0 +1,416 @@
+// Copyright 2024, F. Stan
+//
+// Licensed under the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Record and replay of the I2C transactions with the SCD30 device
+//!
+//! [`Recorder`](crate::replay::Recorder) wraps any I2C bus and logs every write and read frame, with the time
+//! since the recording started, the address, the bytes and the bus error if any.
+//! [`Replay`](crate::replay::Replay) is an I2C bus that feeds a log back into the driver, checking that the
+//! driver writes the same frames, so a session from a field unit can be reproduced
+//! byte by byte, bad checksums and NACKs included.
+//!
+//! The log is plain text, one frame per line:
+//!
+//!```text
+//!0.000000 61 w d100
+//!0.030105 61 r 0342f3
+//!0.031002 61 w 0202
+//!0.061230 61 r 000181 nack-address
+//!```
+//!
+//!```no_run
+//!use scd30_i2c::linux::{LinuxI2c, StdDelay};
+//!use scd30_i2c::replay::{NoDelay, Recorder, Replay};
+//!use scd30_i2c::scd30::Scd30;
+//!use i2cdev::linux::LinuxI2CDevice;
+//!
+//!// On the field unit
+//!let device = LinuxI2CDevice::new("/dev/i2c-1", 0x61).unwrap();
+//!let bus = Recorder::create(LinuxI2c::new(device, 0x61), "scd30.log").unwrap();
+//!let mut scd = Scd30::from_i2c(bus, StdDelay);
+//!let firmware = scd.check_firmware();
+//!
+//!// At the desk
+//!let mut scd = Scd30::from_i2c(Replay::open("scd30.log").unwrap(), NoDelay);
+//!assert_eq!(scd.check_firmware().ok(), firmware.ok());
+//!```
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::i2c::{self, ErrorKind, ErrorType, I2c, NoAcknowledgeSource, Operation};
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::Instant;
+
+/// Direction of a recorded frame
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Frame written to the device
+    Write,
+    /// Frame read from the device
+    Read,
+}
+
+/// One frame of the log
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    /// Seconds since the recording started
+    pub time: f64,
+    /// Address of the device
+    pub address: u8,
+    /// Write or read
+    pub direction: Direction,
+    /// Bytes written, or read into the buffer
+    pub bytes: Vec<u8>,
+    /// Error reported by the bus for the transaction of this frame
+    pub error: Option<ErrorKind>,
+}
+
+impl Frame {
+    /// Parses a line of the log
+    ///
+    pub fn parse(line: &str) -> Result<Frame, String> {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() != 4 && fields.len() != 5 {
+            return Err(format!("expected 4 or 5 fields, found {}", fields.len()));
+        }
+        let time = fields[0]
+            .parse()
+            .map_err(|_| format!("invalid time {}", fields[0]))?;
+        let address = u8::from_str_radix(fields[1], 16)
+            .map_err(|_| format!("invalid address {}", fields[1]))?;
+        let direction = match fields[2] {
+            "w" => Direction::Write,
+            "r" => Direction::Read,
+            other => return Err(format!("invalid direction {}", other)),
+        };
+        let bytes = decode_hex(fields[3]).ok_or_else(|| format!("invalid bytes {}", fields[3]))?;
+        let error = match fields.get(4) {
+            Some(kind) => Some(parse_kind(kind).ok_or_else(|| format!("invalid error {}", kind))?),
+            None => None,
+        };
+        Ok(Frame {
+            time,
+            address,
+            direction,
+            bytes,
+            error,
+        })
+    }
+}
+
+///Implementation of display for Frame, a line of the log
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let direction = match self.direction {
+            Direction::Write => "w",
+            Direction::Read => "r",
+        };
+        write!(
+            f,
+            "{:.6} {:02x} {} {}",
+            self.time,
+            self.address,
+            direction,
+            encode_hex(&self.bytes)
+        )?;
+        if let Some(kind) = self.error {
+            write!(f, " {}", kind_name(kind))?;
+        }
+        Ok(())
+    }
+}
+
+/// I2C bus wrapper logging every frame to a writer.
+/// Failures writing the log are ignored, so they never break the bus.
+///
+pub struct Recorder<I, W> {
+    inner: I,
+    log: W,
+    start: Instant,
+}
+
+impl<I> Recorder<I, BufWriter<File>> {
+    /// Wraps the bus, logging to a new file at the given path
+    ///
+    pub fn create<P: AsRef<Path>>(inner: I, path: P) -> io::Result<Self> {
+        Ok(Recorder::new(inner, BufWriter::new(File::create(path)?)))
+    }
+}
+
+impl<I, W: Write> Recorder<I, W> {
+    /// Wraps the bus, logging to the given writer
+    ///
+    pub fn new(inner: I, log: W) -> Self {
+        Recorder {
+            inner,
+            log,
+            start: Instant::now(),
+        }
+    }
+
+    /// Gives access to the wrapped bus
+    ///
+    pub fn inner_mut(&mut self) -> &mut I {
+        &mut self.inner
+    }
+
+    /// Releases the bus and the log writer
+    ///
+    pub fn into_inner(mut self) -> (I, W) {
+        let _ = self.log.flush();
+        (self.inner, self.log)
+    }
+}
+
+impl<I: ErrorType, W> ErrorType for Recorder<I, W> {
+    type Error = I::Error;
+}
+
+impl<I: I2c, W: Write> I2c for Recorder<I, W> {
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        let result = self.inner.transaction(address, operations);
+        let time = self.start.elapsed().as_secs_f64();
+        let error = result.as_ref().err().map(i2c::Error::kind);
+        for operation in operations.iter() {
+            let (direction, bytes) = match operation {
+                Operation::Write(bytes) => (Direction::Write, bytes.to_vec()),
+                Operation::Read(buffer) => (Direction::Read, buffer.to_vec()),
+            };
+            let frame = Frame {
+                time,
+                address,
+                direction,
+                bytes,
+                error,
+            };
+            let _ = writeln!(self.log, "{}", frame);
+        }
+        let _ = self.log.flush();
+        result
+    }
+}
+
+///
+///Replay error enum. Recorded when the log has a bus error for
+///the transaction. Mismatch when the driver does not send the
+///same frame as the log, keeping the line of the log. Exhausted
+///when there are no more frames in the log
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayError {
+    /// Error reported by the bus in the recording
+    Recorded(ErrorKind),
+    /// Frame different from the one in the log
+    Mismatch {
+        line: usize,
+        expected: String,
+        actual: String,
+    },
+    /// No more frames in the log
+    Exhausted,
+}
+
+///Implementation of display for ReplayError
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ReplayError::Recorded(kind) => write!(f, "Recorded bus error: {}", kind),
+            ReplayError::Mismatch {
+                line,
+                ref expected,
+                ref actual,
+            } => write!(
+                f,
+                "Frame mismatch on line {}, expected {} but found {}",
+                line, expected, actual
+            ),
+            ReplayError::Exhausted => fmt::Display::fmt("No more frames in the log", f),
+        }
+    }
+}
+
+///Implementation for Error to ReplayError
+impl Error for ReplayError {}
+
+///Implementation for embedded-hal I2C error to ReplayError
+impl i2c::Error for ReplayError {
+    fn kind(&self) -> ErrorKind {
+        match *self {
+            ReplayError::Recorded(kind) => kind,
+            _ => ErrorKind::Other,
+        }
+    }
+}
+
+/// I2C bus replaying a log made by [`Recorder`]
+///
+#[derive(Debug, Clone)]
+pub struct Replay {
+    frames: VecDeque<(usize, Frame)>,
+}
+
+impl Replay {
+    /// Reads the log from the file at the given path
+    ///
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Replay> {
+        Replay::from_log(&std::fs::read_to_string(path)?)
+    }
+
+    /// Parses the log text. Empty lines and lines starting with # are skipped
+    ///
+    pub fn from_log(log: &str) -> io::Result<Replay> {
+        let mut frames = VecDeque::new();
+        for (i, line) in log.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let frame = Frame::parse(line).map_err(|e| {
+                io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", i + 1, e))
+            })?;
+            frames.push_back((i + 1, frame));
+        }
+        Ok(Replay { frames })
+    }
+
+    /// Frames not replayed yet
+    ///
+    pub fn remaining(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Takes the next frame, checking it matches the operation of the driver
+    fn next_frame(
+        &mut self,
+        address: u8,
+        operation: &mut Operation<'_>,
+    ) -> Result<Option<ErrorKind>, ReplayError> {
+        let (line, frame) = self.frames.pop_front().ok_or(ReplayError::Exhausted)?;
+        let mismatch = |actual: String| ReplayError::Mismatch {
+            line,
+            expected: frame.to_string(),
+            actual,
+        };
+        match operation {
+            Operation::Write(bytes) => {
+                if frame.direction != Direction::Write
+                    || frame.address != address
+                    || frame.bytes != *bytes
+                {
+                    return Err(mismatch(format!(
+                        "write of {} to {:02x}",
+                        encode_hex(bytes),
+                        address
+                    )));
+                }
+            }
+            Operation::Read(buffer) => {
+                if frame.direction != Direction::Read
+                    || frame.address != address
+                    || frame.bytes.len() != buffer.len()
+                {
+                    return Err(mismatch(format!(
+                        "read of {} bytes from {:02x}",
+                        buffer.len(),
+                        address
+                    )));
+                }
+                buffer.copy_from_slice(&frame.bytes);
+            }
+        }
+        Ok(frame.error)
+    }
+}
+
+impl ErrorType for Replay {
+    type Error = ReplayError;
+}
+
+impl I2c for Replay {
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        let mut error = None;
+        for operation in operations.iter_mut() {
+            error = error.or(self.next_frame(address, operation)?);
+        }
+        match error {
+            Some(kind) => Err(ReplayError::Recorded(kind)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Delay provider that does not wait, to replay a log at full speed
+///
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoDelay;
+
+impl DelayNs for NoDelay {
+    fn delay_ns(&mut self, _ns: u32) {}
+}
+
+/// Name of the error kind in the log
+fn kind_name(kind: ErrorKind) -> &'static str {
+    match kind {
+        ErrorKind::Bus => "bus",
+        ErrorKind::ArbitrationLoss => "arbitration-loss",
+        ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address) => "nack-address",
+        ErrorKind::NoAcknowledge(NoAcknowledgeSource::Data) => "nack-data",
+        ErrorKind::NoAcknowledge(_) => "nack",
+        ErrorKind::Overrun => "overrun",
+        _ => "other",
+    }
+}
+
+/// Error kind from its name in the log
+fn parse_kind(name: &str) -> Option<ErrorKind> {
+    match name {
+        "bus" => Some(ErrorKind::Bus),
+        "arbitration-loss" => Some(ErrorKind::ArbitrationLoss),
+        "nack-address" => Some(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address)),
+        "nack-data" => Some(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Data)),
+        "nack" => Some(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Unknown)),
+        "overrun" => Some(ErrorKind::Overrun),
+        "other" => Some(ErrorKind::Other),
+        _ => None,
+    }
+}
+
+/// Lower case hex of the bytes, - if empty
+fn encode_hex(bytes: &[u8]) -> String {
+    if bytes.is_empty() {
+        return "-".to_string();
+    }
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Bytes from their hex, - is empty
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex == "-" {
+        return Some(Vec::new());
+    }
+    hex.as_bytes()
+        .chunks(2)
+        .map(|pair| match pair {
+            [high, low] => u8::from_str_radix(std::str::from_utf8(&[*high, *low]).ok()?, 16).ok(),
+            _ => None,
+        })
+        .collect()
+}
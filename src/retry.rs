@@ -0,0 +1,571 @@
+// Copyright 2024, F. Stan
+//
+// Licensed under the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Automatic retry and recovery of transient bus errors
+//!
+//! [`Retrying`](crate::retry::Retrying) wraps a [`Scd30`](crate::scd30::Scd30) and retries
+//! the failed operations following a [`RetryPolicy`](crate::retry::RetryPolicy), waiting an
+//! increasing backoff between attempts. After several operations fail in a row it escalates:
+//! soft resets the device, applies again the last known configuration and restarts the
+//! continuous measurements, so long running daemons heal themselves.
+//!
+//! Every operation of the driver has a retrying version with the same name, and
+//! [`Retrying::measurements`](crate::retry::Retrying::measurements) retries each read of
+//! the measurements iterator. Any other operation, like the calibration procedures, runs
+//! with the same policy through [`Retrying::run`](crate::retry::Retrying::run).
+//!
+//!```no_run
+//!use scd30_i2c::retry::RetryPolicy;
+//!use scd30_i2c::scd30::Scd30;
+//!
+//!let mut scd = Scd30::new().unwrap().with_retry(RetryPolicy::default());
+//!scd.remember_config().unwrap();
+//!scd.trigger_cont_measurements().unwrap();
+//!let firmware = scd.run(|scd| scd.check_firmware()).unwrap();
+//!println!("Firmware: {:#06x}", firmware);
+//!for measurement in scd.measurements() {
+//!    match measurement {
+//!        Ok(m) => println!("Co2: {} ppm", m.co2_ppm),
+//!        Err(e) => println!("Error obtaining measurements: {}", e),
+//!    }
+//!}
+//!```
+
+use crate::config::{ConfigError, Scd30Config, Settings};
+use crate::measurement::Measurement;
+use crate::scd30::{offset_ticks, Scd30, Scd30Error};
+use crate::stream::{
+    millis, MeasurementSource, RecoveryPolicy, StreamState, DEFAULT_POLL_INTERVAL,
+};
+use core::time::Duration;
+use embedded_hal::delay::DelayNs;
+use embedded_hal::i2c::I2c;
+
+/// How the failed operations are retried
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Tries of each operation, including the first one
+    pub attempts: u32,
+    /// Wait before the first retry, doubled on each of the next ones
+    pub backoff: Duration,
+    /// Longest wait between retries
+    pub max_backoff: Duration,
+    /// If responses with a wrong checksum are retried
+    pub retry_checksum: bool,
+    /// If failed reads and writes on the bus are retried
+    pub retry_communication: bool,
+    /// Operations failing in a row before soft resetting the device,
+    /// None to never reset it
+    pub reset_after: Option<u32>,
+}
+
+/// Default policy: 3 attempts waiting 100 ms, then 200 ms, retrying checksum
+/// and communication errors, and resetting the device after 3 failed operations
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            attempts: 3,
+            backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(2),
+            retry_checksum: true,
+            retry_communication: true,
+            reset_after: Some(3),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Policy that never retries nor resets the device
+    ///
+    pub fn none() -> RetryPolicy {
+        RetryPolicy {
+            attempts: 1,
+            reset_after: None,
+            ..RetryPolicy::default()
+        }
+    }
+
+    /// If the error is transient and can be retried
+    ///
+    pub fn is_retryable<E>(&self, error: &Scd30Error<E>) -> bool {
+        match error {
+            Scd30Error::ChecksumError => self.retry_checksum,
+            Scd30Error::ComunicationError { .. } => self.retry_communication,
+            _ => false,
+        }
+    }
+
+    /// Wait before the given retry, starting on 1
+    fn backoff(&self, retry: u32) -> Duration {
+        let factor = 1u32
+            .checked_shl(retry.saturating_sub(1))
+            .unwrap_or(u32::MAX);
+        self.backoff
+            .checked_mul(factor)
+            .map_or(self.max_backoff, |backoff| backoff.min(self.max_backoff))
+    }
+}
+
+/// SCD30 device retrying the failed operations, created with [`Scd30::with_retry`]
+///
+pub struct Retrying<I, D> {
+    scd: Scd30<I, D>,
+    policy: RetryPolicy,
+    config: Option<Scd30Config>,
+    pressure: Option<u16>,
+    failures: u32,
+    retries: u64,
+    recoveries: u64,
+}
+
+impl<I: I2c, D: DelayNs> Retrying<I, D> {
+    /// Wraps the device with the given policy
+    ///
+    pub fn new(scd: Scd30<I, D>, policy: RetryPolicy) -> Retrying<I, D> {
+        Retrying {
+            scd,
+            policy,
+            config: None,
+            pressure: None,
+            failures: 0,
+            retries: 0,
+            recoveries: 0,
+        }
+    }
+
+    /// Policy used to retry the operations
+    ///
+    pub fn policy(&self) -> &RetryPolicy {
+        &self.policy
+    }
+
+    /// Changes the policy
+    ///
+    pub fn set_policy(&mut self, policy: RetryPolicy) {
+        self.policy = policy;
+    }
+
+    /// Gives access to the device, the operations made directly are not retried
+    /// and do not update the known configuration
+    ///
+    pub fn device_mut(&mut self) -> &mut Scd30<I, D> {
+        &mut self.scd
+    }
+
+    /// Releases the device
+    ///
+    pub fn into_inner(self) -> Scd30<I, D> {
+        self.scd
+    }
+
+    /// Attempts retried since the device was wrapped
+    ///
+    pub fn retries(&self) -> u64 {
+        self.retries
+    }
+
+    /// Soft resets made to recover the device
+    ///
+    pub fn recoveries(&self) -> u64 {
+        self.recoveries
+    }
+
+    /// Configuration applied again after a soft reset, if known
+    ///
+    pub fn known_config(&self) -> Option<&Scd30Config> {
+        self.config.as_ref()
+    }
+
+    /// Runs the operation following the retry policy. If it fails on every
+    /// attempt and too many operations failed in a row, recovers the device
+    /// and runs the operation once more.
+    /// This is the entry point for the operations without a retrying version,
+    /// the changes made in the configuration are not remembered.
+    ///
+    pub fn run<T, F>(&mut self, mut operation: F) -> Result<T, Scd30Error<I::Error>>
+    where
+        F: FnMut(&mut Scd30<I, D>) -> Result<T, Scd30Error<I::Error>>,
+    {
+        let error = match self.attempt(&mut operation) {
+            Ok(value) => return Ok(value),
+            Err(e) => e,
+        };
+        if !self.policy.is_retryable(&error) {
+            return Err(error);
+        }
+        self.failures += 1;
+        match self.policy.reset_after {
+            Some(limit) if self.failures >= limit => {
+                self.failures = 0;
+                self.recover()?;
+                let result = operation(&mut self.scd);
+                if result.is_ok() {
+                    self.failures = 0;
+                }
+                result
+            }
+            _ => Err(error),
+        }
+    }
+
+    /// Tries the operation up to the policy attempts
+    fn attempt<T, F>(&mut self, operation: &mut F) -> Result<T, Scd30Error<I::Error>>
+    where
+        F: FnMut(&mut Scd30<I, D>) -> Result<T, Scd30Error<I::Error>>,
+    {
+        let mut retry = 0;
+        loop {
+            match operation(&mut self.scd) {
+                Ok(value) => {
+                    self.failures = 0;
+                    return Ok(value);
+                }
+                Err(e) => {
+                    retry += 1;
+                    if retry >= self.policy.attempts || !self.policy.is_retryable(&e) {
+                        return Err(e);
+                    }
+                    self.retries += 1;
+                    let backoff = self.policy.backoff(retry);
+                    self.scd.delay.delay_ms(millis(backoff));
+                }
+            }
+        }
+    }
+
+    /// Soft resets the device, writes again the known
+    /// configuration and restarts the continuous measurements if they were running
+    ///
+    pub fn recover(&mut self) -> Result<(), Scd30Error<I::Error>> {
+        self.recoveries += 1;
+        self.scd.soft_reset()?;
+        if let Some(config) = self.config {
+            if let Some(interval) = config.measurement_interval {
+                self.scd.set_measurements_interval(interval)?;
+            }
+            if let Some(altitude) = config.altitude {
+                self.scd.set_altitude(altitude)?;
+            }
+            if let Some(offset) = config.temperature_offset {
+                self.scd.set_temperature_offset(offset)?;
+            }
+            if let Some(active) = config.self_calibration {
+                self.scd.set_self_calibration(active)?;
+            }
+        }
+        if let Some(pressure) = self.pressure {
+            self.scd.trigger_cont_measurements_with_pressure(pressure)?;
+        }
+        Ok(())
+    }
+
+    /// Reads the configuration of the device and keeps it, to apply it again
+    /// after a soft reset
+    ///
+    pub fn remember_config(&mut self) -> Result<Scd30Config, Scd30Error<I::Error>> {
+        let config = self.run(|scd| scd.read_config())?;
+        self.config = Some(config);
+        Ok(config)
+    }
+
+    /// Applies the configuration and keeps the settings set in it, to apply them
+    /// again after a soft reset. Returns the settings changed.
+    ///
+    pub fn apply_config(
+        &mut self,
+        config: &Scd30Config,
+    ) -> Result<Settings, ConfigError<I::Error>> {
+        self.apply(config, Scd30::apply_config)
+    }
+
+    /// Applies the configuration with its forced recalibration value, see
+    /// [`Scd30::apply_config_with_recalibration`], and keeps the other settings
+    /// set in it. Returns the settings changed.
+    ///
+    pub fn apply_config_with_recalibration(
+        &mut self,
+        config: &Scd30Config,
+    ) -> Result<Settings, ConfigError<I::Error>> {
+        self.apply(config, Scd30::apply_config_with_recalibration)
+    }
+
+    /// Retries applying the configuration and updates the known one
+    fn apply<F>(
+        &mut self,
+        config: &Scd30Config,
+        apply: F,
+    ) -> Result<Settings, ConfigError<I::Error>>
+    where
+        F: Fn(&mut Scd30<I, D>, &Scd30Config) -> Result<Settings, ConfigError<I::Error>>,
+    {
+        // Only device errors are retried, the other errors are returned as is
+        let mut rejected = None;
+        let changed = self.run(|scd| match apply(scd, config) {
+            Ok(changed) => Ok(changed),
+            Err(ConfigError::Device(e)) => Err(e),
+            Err(e) => {
+                rejected = Some(e);
+                Ok(Settings::new())
+            }
+        })?;
+        if let Some(e) = rejected {
+            return Err(e);
+        }
+        // The forced recalibration is never applied again
+        let known = Scd30Config {
+            forced_recalibration: None,
+            ..*config
+        };
+        self.config
+            .get_or_insert_with(Scd30Config::default)
+            .update(&known);
+        Ok(changed)
+    }
+
+    /// Triggers the continuous measurements, restarted after a soft reset
+    ///
+    pub fn trigger_cont_measurements(&mut self) -> Result<(), Scd30Error<I::Error>> {
+        self.trigger_cont_measurements_with_pressure(0)
+    }
+
+    /// Triggers the continuous measurements with the ambient pressure in mbar,
+    /// restarted after a soft reset
+    ///
+    pub fn trigger_cont_measurements_with_pressure(
+        &mut self,
+        pressure: u16,
+    ) -> Result<(), Scd30Error<I::Error>> {
+        self.run(|scd| scd.trigger_cont_measurements_with_pressure(pressure))?;
+        self.pressure = Some(pressure);
+        Ok(())
+    }
+
+    /// Updates the ambient pressure in mbar of the continuous measurements,
+    /// used when they are restarted after a soft reset
+    ///
+    pub fn set_ambient_pressure(&mut self, pressure: u16) -> Result<(), Scd30Error<I::Error>> {
+        self.run(|scd| scd.set_ambient_pressure(pressure))?;
+        self.pressure = Some(pressure);
+        Ok(())
+    }
+
+    /// Stops the continuous measurements, not restarted after a soft reset
+    ///
+    pub fn stop_cont_measurements(&mut self) -> Result<(), Scd30Error<I::Error>> {
+        self.run(|scd| scd.stop_cont_measurements())?;
+        self.pressure = None;
+        Ok(())
+    }
+
+    /// Checks if there is a new measurement ready
+    ///
+    pub fn get_data_ready(&mut self) -> Result<bool, Scd30Error<I::Error>> {
+        self.run(|scd| scd.get_data_ready())
+    }
+
+    /// Reads the last measurement of the device
+    ///
+    pub fn get_measurements(&mut self) -> Result<Measurement, Scd30Error<I::Error>> {
+        self.run(|scd| scd.get_measurements())
+    }
+
+    /// Gets the firmware version of the device
+    ///
+    pub fn check_firmware(&mut self) -> Result<u16, Scd30Error<I::Error>> {
+        self.run(|scd| scd.check_firmware())
+    }
+
+    /// Soft resets the device
+    ///
+    pub fn soft_reset(&mut self) -> Result<(), Scd30Error<I::Error>> {
+        self.run(|scd| scd.soft_reset())
+    }
+
+    /// Gets the measurement interval in seconds
+    ///
+    pub fn get_measurements_interval(&mut self) -> Result<u16, Scd30Error<I::Error>> {
+        self.run(|scd| scd.get_measurements_interval())
+    }
+
+    /// Sets the measurement interval in seconds, updating the known configuration
+    ///
+    pub fn set_measurements_interval(&mut self, seconds: u16) -> Result<(), Scd30Error<I::Error>> {
+        self.run(|scd| scd.set_measurements_interval(seconds))?;
+        self.config
+            .get_or_insert_with(Scd30Config::default)
+            .measurement_interval = Some(seconds);
+        Ok(())
+    }
+
+    /// Gets the altitude in meters
+    ///
+    pub fn get_altitude(&mut self) -> Result<u16, Scd30Error<I::Error>> {
+        self.run(|scd| scd.get_altitude())
+    }
+
+    /// Sets the altitude in meters, updating the known configuration
+    ///
+    pub fn set_altitude(&mut self, altitude: u16) -> Result<(), Scd30Error<I::Error>> {
+        self.run(|scd| scd.set_altitude(altitude))?;
+        self.config
+            .get_or_insert_with(Scd30Config::default)
+            .altitude = Some(altitude);
+        Ok(())
+    }
+
+    /// Gets the temperature offset in ticks of 0.01 Celsius
+    ///
+    pub fn get_temperature_offset(&mut self) -> Result<u16, Scd30Error<I::Error>> {
+        self.run(|scd| scd.get_temperature_offset())
+    }
+
+    /// Sets the temperature offset in ticks of 0.01 Celsius, updating the known configuration
+    ///
+    pub fn set_temperature_offset(&mut self, offset: u16) -> Result<(), Scd30Error<I::Error>> {
+        self.run(|scd| scd.set_temperature_offset(offset))?;
+        self.config
+            .get_or_insert_with(Scd30Config::default)
+            .temperature_offset = Some(offset);
+        Ok(())
+    }
+
+    /// Gets the temperature offset in Celsius
+    ///
+    pub fn get_temperature_offset_celsius(&mut self) -> Result<f32, Scd30Error<I::Error>> {
+        self.run(|scd| scd.get_temperature_offset_celsius())
+    }
+
+    /// Sets the temperature offset in Celsius, rounded to the 0.01 Celsius ticks
+    /// of the device, updating the known configuration
+    ///
+    pub fn set_temperature_offset_celsius(
+        &mut self,
+        offset_c: f32,
+    ) -> Result<(), Scd30Error<I::Error>> {
+        let ticks = offset_ticks(offset_c)?;
+        self.set_temperature_offset(ticks)
+    }
+
+    /// Gets if the automatic self calibration is active
+    ///
+    pub fn get_self_calibration_status(&mut self) -> Result<bool, Scd30Error<I::Error>> {
+        self.run(|scd| scd.get_self_calibration_status())
+    }
+
+    /// Activates or deactivates the automatic self calibration, updating the known configuration
+    ///
+    pub fn set_self_calibration(&mut self, active: bool) -> Result<(), Scd30Error<I::Error>> {
+        self.run(|scd| scd.set_self_calibration(active))?;
+        self.config
+            .get_or_insert_with(Scd30Config::default)
+            .self_calibration = Some(active);
+        Ok(())
+    }
+
+    /// Gets the forced recalibration value in ppm
+    ///
+    pub fn get_forced_value(&mut self) -> Result<u16, Scd30Error<I::Error>> {
+        self.run(|scd| scd.get_forced_value())
+    }
+
+    /// Sets the forced recalibration value in ppm, between 400 and 2000
+    ///
+    pub fn set_force_recalibration_value(
+        &mut self,
+        forced_value: u16,
+    ) -> Result<(), Scd30Error<I::Error>> {
+        self.run(|scd| scd.set_force_recalibration_value(forced_value))
+    }
+
+    /// Blocking iterator over the measurements of the device, retrying each
+    /// read with the policy and recovering the device when needed.
+    /// Errors left after the retries are handled with the default [`RecoveryPolicy`].
+    ///
+    pub fn measurements(&mut self) -> RetryingMeasurements<'_, I, D> {
+        RetryingMeasurements::new(self)
+    }
+}
+
+/// Blocking iterator over the measurements of a [`Retrying`] device, created
+/// with [`Retrying::measurements`]. Works like the [`Measurements`](crate::stream::Measurements)
+/// iterator, but every access to the device follows the retry policy.
+///
+pub struct RetryingMeasurements<'a, I, D> {
+    scd: &'a mut Retrying<I, D>,
+    state: StreamState,
+}
+
+impl<'a, I: I2c, D: DelayNs> RetryingMeasurements<'a, I, D> {
+    /// Create a new iterator over the given device
+    ///
+    pub fn new(scd: &'a mut Retrying<I, D>) -> RetryingMeasurements<'a, I, D> {
+        RetryingMeasurements {
+            scd,
+            state: StreamState::new(RecoveryPolicy::default(), DEFAULT_POLL_INTERVAL),
+        }
+    }
+
+    /// Sets the policy used when the device returns an error after the retries
+    ///
+    pub fn with_policy(mut self, policy: RecoveryPolicy) -> Self {
+        self.state.set_policy(policy);
+        self
+    }
+
+    /// Sets the time between data ready checks
+    ///
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.state.set_poll_interval(poll_interval);
+        self
+    }
+
+    /// Gives access to the device between items, for example to change
+    /// its configuration. The measurement interval is read again on the next item.
+    ///
+    pub fn device_mut(&mut self) -> &mut Retrying<I, D> {
+        self.state.reset_interval();
+        self.scd
+    }
+}
+
+impl<I: I2c, D: DelayNs> Iterator for RetryingMeasurements<'_, I, D> {
+    type Item = Result<Measurement, Scd30Error<I::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.state.next_item(self.scd)
+    }
+}
+
+/// Every access to the device of the measurements loop follows the retry policy
+impl<I: I2c, D: DelayNs> MeasurementSource for Retrying<I, D> {
+    type Error = I::Error;
+
+    fn read_interval(&mut self) -> Result<u16, Scd30Error<I::Error>> {
+        self.get_measurements_interval()
+    }
+
+    fn data_ready(&mut self) -> Result<bool, Scd30Error<I::Error>> {
+        self.get_data_ready()
+    }
+
+    fn read_measurement(&mut self) -> Result<Measurement, Scd30Error<I::Error>> {
+        self.get_measurements()
+    }
+
+    fn sleep(&mut self, duration: Duration) -> bool {
+        self.scd.sleep(duration)
+    }
+}
+
+impl<I: I2c, D: DelayNs> Scd30<I, D> {
+    /// Wraps the device to retry the failed operations with the given policy
+    ///
+    pub fn with_retry(self, policy: RetryPolicy) -> Retrying<I, D> {
+        Retrying::new(self, policy)
+    }
+}
@@ -5,289 +5,610 @@
 // This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use i2cdev::core::*;
+#[cfg(feature = "linux")]
+use crate::linux::{LinuxI2c, StdDelay};
+use crate::measurement::Measurement;
+use crate::sensirion_frame::{self, FrameError, COMMAND_LEN, WORD_LEN};
+use core::error::Error;
+use core::fmt;
+use core::time::Duration;
+use embedded_hal::delay::DelayNs;
+use embedded_hal::i2c::{self, ErrorKind, I2c};
+#[cfg(feature = "linux")]
 use i2cdev::linux::{LinuxI2CDevice, LinuxI2CError};
-use std::error::Error;
-use std::fmt;
+#[cfg(feature = "std")]
 use std::io;
-use std::{thread, time};
+#[cfg(feature = "linux")]
+use std::path::Path;
+
+// The checksum was `Scd30::crc8` in 1.x
+pub use crate::sensirion_frame::crc8;
+
+/// Default I2C address of the SCD30 device
+pub const DEFAULT_ADDRESS: u8 = 0x61;
+/// Default Linux I2C bus, the one exposed on Raspberry Pi pins
+#[cfg(feature = "linux")]
+pub const DEFAULT_BUS: &str = "/dev/i2c-1";
+
+/// Minimum measurement interval accepted by the device, in seconds
+pub const MIN_MEASUREMENTS_INTERVAL: u16 = 2;
+/// Maximum measurement interval accepted by the device, in seconds
+pub const MAX_MEASUREMENTS_INTERVAL: u16 = 1800;
+
+/// Minimum ambient pressure accepted by the device, in mbar
+pub const MIN_AMBIENT_PRESSURE: u16 = 700;
+/// Maximum ambient pressure accepted by the device, in mbar
+pub const MAX_AMBIENT_PRESSURE: u16 = 1400;
+
+/// Minimum forced recalibration value accepted by the device, in ppm
+pub const MIN_FORCED_RECALIBRATION_VALUE: u16 = 400;
+/// Maximum forced recalibration value accepted by the device, in ppm
+pub const MAX_FORCED_RECALIBRATION_VALUE: u16 = 2000;
+
+/// Maximum temperature offset that fits in the device register, in Celsius
+pub const MAX_TEMPERATURE_OFFSET: f32 = u16::MAX as f32 / 100.0;
+
+/// Minimum wait between writing a command and reading its response,
+/// from the interface description
+pub const READ_DELAY: Duration = Duration::from_millis(3);
+/// Time the device needs to boot after a soft reset
+pub const BOOT_TIME: Duration = Duration::from_secs(2);
+
+/// Waits of the driver around the I2C transactions
+///
+/// The default follows the interface description. Slow multiplexers or masters
+/// that do not support clock stretching may need longer waits.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timing {
+    /// Wait between writing a command and reading its response
+    pub read_delay: Duration,
+    /// Wait after a command without response
+    pub write_delay: Duration,
+    /// Wait after a soft reset, while the device boots
+    pub boot_time: Duration,
+}
+
+/// Timing from the interface description: 3 ms before reading,
+/// no wait after writing and 2 seconds to boot
+impl Default for Timing {
+    fn default() -> Self {
+        Timing {
+            read_delay: READ_DELAY,
+            write_delay: Duration::ZERO,
+            boot_time: BOOT_TIME,
+        }
+    }
+}
+
+impl Timing {
+    /// Timing of the previous versions of the driver, waiting 30 ms after every
+    /// command, for buses where the default one is too fast
+    ///
+    pub fn relaxed() -> Timing {
+        Timing {
+            read_delay: Duration::from_millis(30),
+            write_delay: Duration::from_millis(30),
+            boot_time: BOOT_TIME,
+        }
+    }
+}
+
+/// Longest response of the device, the measurement of six data words
+pub(crate) const RESPONSE_LEN: usize = sensirion_frame::frame_len(6);
+
+/// Write frame of a command word with its argument, if any, and the length
+/// of the frame. Shared by the blocking and async drivers.
+pub(crate) fn command_frame(
+    command: u16,
+    argument: Option<u16>,
+) -> ([u8; COMMAND_LEN + WORD_LEN], usize) {
+    match argument {
+        Some(argument) => (
+            sensirion_frame::encode_command_with_argument(command, argument),
+            COMMAND_LEN + WORD_LEN,
+        ),
+        None => {
+            let [high, low] = sensirion_frame::encode_command(command);
+            ([high, low, 0, 0, 0], COMMAND_LEN)
+        }
+    }
+}
+
+/// Fails with OutOfRange if the value is not between min and max
+pub(crate) fn check_range<E>(value: u16, min: u16, max: u16) -> Result<(), Scd30Error<E>> {
+    if (min..=max).contains(&value) {
+        Ok(())
+    } else {
+        Err(Scd30Error::OutOfRange {
+            value: i32::from(value),
+            min: i32::from(min),
+            max: i32::from(max),
+        })
+    }
+}
+
+/// Temperature offset in Celsius as ticks of 0.01 Celsius, rounded to the
+/// nearest one. Fails with OutOfRange if the device can not store it.
+pub(crate) fn offset_ticks<E>(offset_c: f32) -> Result<u16, Scd30Error<E>> {
+    let scaled = offset_c * 100.0;
+    // Rounded by hand, f32::round needs std. NaN is out of range too
+    let ticks = if scaled.is_nan() {
+        i32::MIN
+    } else if scaled < 0.0 {
+        (scaled - 0.5) as i32
+    } else {
+        (scaled + 0.5) as i32
+    };
+    u16::try_from(ticks).map_err(|_| Scd30Error::OutOfRange {
+        value: ticks,
+        min: 0,
+        max: i32::from(u16::MAX),
+    })
+}
+
+/// Checks an ambient pressure in mbar, 0 disables the compensation
+pub(crate) fn check_pressure<E>(pressure: u16) -> Result<(), Scd30Error<E>> {
+    if pressure == 0 {
+        return Ok(());
+    }
+    check_range(pressure, MIN_AMBIENT_PRESSURE, MAX_AMBIENT_PRESSURE)
+}
+
+/// Checks a measurement interval in seconds
+pub(crate) fn check_interval<E>(seconds: u16) -> Result<(), Scd30Error<E>> {
+    check_range(
+        seconds,
+        MIN_MEASUREMENTS_INTERVAL,
+        MAX_MEASUREMENTS_INTERVAL,
+    )
+}
+
+/// Checks a forced recalibration value in ppm
+pub(crate) fn check_forced_value<E>(forced_value: u16) -> Result<(), Scd30Error<E>> {
+    check_range(
+        forced_value,
+        MIN_FORCED_RECALIBRATION_VALUE,
+        MAX_FORCED_RECALIBRATION_VALUE,
+    )
+}
+
+/// Phase of an I2C transaction with the SCD30 device
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Writing the command word, and its argument if any
+    Write,
+    /// Reading the response of the command
+    Read,
+}
+
+///Implementation of display for Phase
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Phase::Write => fmt::Display::fmt("writing", f),
+            Phase::Read => fmt::Display::fmt("reading", f),
+        }
+    }
+}
+
+///
+/// Bus error of a [`Scd30Error`] when not given, wraps the embedded-hal
+/// error kind so the error can be returned as a `dyn Error`
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BusErrorKind(pub ErrorKind);
+
+///Implementation of display for BusErrorKind
+impl fmt::Display for BusErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+///Implementation for Error to BusErrorKind
+impl Error for BusErrorKind {}
+
+///Implementation for embedded-hal I2C error to BusErrorKind
+impl i2c::Error for BusErrorKind {
+    fn kind(&self) -> ErrorKind {
+        self.0
+    }
+}
+
+///Implementation for embedded-hal error kind to BusErrorKind
+impl From<ErrorKind> for BusErrorKind {
+    fn from(kind: ErrorKind) -> Self {
+        BusErrorKind(kind)
+    }
+}
+
+/// Input/output error of a [`Scd30Error`], when the bus can not be opened.
+/// It is only produced with the `std` feature, wrapping a `std::io::Error`
+///
+#[derive(Debug)]
+pub struct IoError {
+    #[cfg(feature = "std")]
+    error: io::Error,
+    #[cfg(not(feature = "std"))]
+    never: core::convert::Infallible,
+}
+
+#[cfg(feature = "std")]
+impl IoError {
+    /// Gets a reference to the wrapped error
+    ///
+    pub fn get_ref(&self) -> &io::Error {
+        &self.error
+    }
+
+    /// Gets the wrapped error
+    ///
+    pub fn into_inner(self) -> io::Error {
+        self.error
+    }
+}
+
+///Implementation for io Error to IoError
+#[cfg(feature = "std")]
+impl From<io::Error> for IoError {
+    fn from(error: io::Error) -> Self {
+        IoError { error }
+    }
+}
+
+///Implementation of display for IoError
+impl fmt::Display for IoError {
+    #[cfg(feature = "std")]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.error, f)
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn fmt(&self, _: &mut fmt::Formatter) -> fmt::Result {
+        match self.never {}
+    }
+}
+
+///Implementation for Error to IoError
+impl Error for IoError {}
 
 ///
 ///SCD30 error enum, including Io error from
 ///i2cdev library. ChecksumError when a crc 8
-///checksum does not correspond with the calculated
-///one. CommunicationError when read or write operations
-///fails
+///checksum, or the crc 16 of a Modbus response, does not
+///correspond with the calculated one. Frame when a response
+///is not well formed. CommunicationError when read or write operations
+///fails, keeping the error from the I2C bus. OutOfRange when
+///an argument is outside the range accepted by the device.
+///Timeout when the device does not have data ready in time
 ///
 #[derive(Debug)]
-pub enum Scd30Error {
+pub enum Scd30Error<E = BusErrorKind> {
     /// Input/output error
-    Io(io::Error),
+    Io(IoError),
     /// ChecksumError when the checksum does not correspond to calculated checksum using crc
     /// algorithm
     ChecksumError,
-    /// Communication error when the trait tries to read or write to scd30 device
-    ComunicationError,
+    /// Frame error when a response from the device is not well formed, for example
+    /// shorter than expected
+    Frame(FrameError),
+    /// Communication error when the trait tries to read or write to scd30 device.
+    /// Keeps the command word, the phase that failed and the I2C bus error
+    ComunicationError {
+        command: u16,
+        phase: Phase,
+        source: E,
+    },
+    /// OutOfRange when a value is rejected before being sent to the device,
+    /// because it is outside the valid range from the interface description.
+    /// The values are in the units of the device, ticks for the temperature offset
+    OutOfRange { value: i32, min: i32, max: i32 },
+    /// Timeout when the device does not report new data ready in the expected time,
+    /// usually because the continous measurements are not running
+    Timeout,
 }
+
+impl<E> Scd30Error<E> {
+    /// Communication error while writing the given command
+    pub(crate) fn write(command: u16, source: E) -> Self {
+        Scd30Error::ComunicationError {
+            command,
+            phase: Phase::Write,
+            source,
+        }
+    }
+
+    /// Communication error while reading the response of the given command
+    pub(crate) fn read(command: u16, source: E) -> Self {
+        Scd30Error::ComunicationError {
+            command,
+            phase: Phase::Read,
+            source,
+        }
+    }
+}
+
 ///Implementation for Io error to Scd30Error
-impl From<io::Error> for Scd30Error {
+#[cfg(feature = "std")]
+impl<E> From<io::Error> for Scd30Error<E> {
     fn from(e: io::Error) -> Self {
-        Scd30Error::Io(e)
+        Scd30Error::Io(IoError::from(e))
     }
 }
+///Implementation for FrameError to Scd30Error, a failed
+///checksum is still reported as ChecksumError
+impl<E> From<FrameError> for Scd30Error<E> {
+    fn from(e: FrameError) -> Self {
+        match e {
+            FrameError::Checksum { .. } => Scd30Error::ChecksumError,
+            e => Scd30Error::Frame(e),
+        }
+    }
+}
+
 ///Implementation of display for SCD30Error
-impl fmt::Display for Scd30Error {
+impl<E> fmt::Display for Scd30Error<E> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             Scd30Error::ChecksumError => fmt::Display::fmt("Checksum Error found", f),
+            Scd30Error::Frame(ref e) => fmt::Display::fmt(e, f),
             Scd30Error::Io(ref e) => fmt::Display::fmt(e, f),
-            Scd30Error::ComunicationError => fmt::Display::fmt("Comunication error with device", f),
+            Scd30Error::ComunicationError { command, phase, .. } => write!(
+                f,
+                "Comunication error with device {} command 0x{:04X}",
+                phase, command
+            ),
+            Scd30Error::OutOfRange { value, min, max } => write!(
+                f,
+                "Value {} out of range, valid range is {} to {}",
+                value, min, max
+            ),
+            Scd30Error::Timeout => fmt::Display::fmt("Timeout waiting for data ready", f),
+        }
+    }
+}
+///Implementation for Error to SCD30, the source is the I2C bus error
+impl<E: Error + 'static> Error for Scd30Error<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            Scd30Error::Io(ref e) => Some(e),
+            Scd30Error::Frame(ref e) => Some(e),
+            Scd30Error::ComunicationError { ref source, .. } => Some(source),
+            _ => None,
         }
     }
 }
-///Implementation for Error to SCD30
-impl Error for Scd30Error {}
 
-/// SCD30 Struct, wraps a LinuxI2CDevice structs
-/// and has implemented related SCD30 operations
+/// SCD30 Struct, wraps any I2C bus implementing the embedded-hal
+/// I2C trait and a delay provider, and has implemented related SCD30 operations
 ///
-pub struct Scd30 {
-    pub i2cdev: LinuxI2CDevice,
+pub struct Scd30<I, D> {
+    pub i2c: I,
+    pub delay: D,
+    pub address: u8,
+    pub timing: Timing,
+}
+
+/// Linux specific constructors, using i2cdev as I2C bus
+/// and thread sleep as delay provider
+///
+#[cfg(feature = "linux")]
+impl Scd30<LinuxI2c, StdDelay> {
+    /// Create a new SCD30 Struct
+    ///
+    /// Tries to create the device on bus /dev/i2c-1 and standard address 0x61.
+    /// If fails, return an LinuxI2CError from i2cdev
+    ///
+    pub fn new() -> Result<Scd30<LinuxI2c, StdDelay>, LinuxI2CError> {
+        Scd30::open(DEFAULT_BUS, DEFAULT_ADDRESS)
+    }
+
+    /// Create a new SCD30 Struct on the given bus path and address
+    ///
+    /// Useful when the device is not on the Raspberry Pi bus, for example
+    /// /dev/i2c-3 or a bus behind a multiplexer.
+    /// If fails, return an LinuxI2CError from i2cdev
+    ///
+    pub fn open<P: AsRef<Path>>(
+        path: P,
+        address: u8,
+    ) -> Result<Scd30<LinuxI2c, StdDelay>, LinuxI2CError> {
+        let device = LinuxI2CDevice::new(path, u16::from(address))?;
+        Ok(Scd30::from_linux_device(device, address))
+    }
+
+    /// Create a new SCD30 Struct wrapping an already opened LinuxI2CDevice
+    ///
+    /// The address must be the slave address the device was opened with.
+    ///
+    pub fn from_linux_device(device: LinuxI2CDevice, address: u8) -> Scd30<LinuxI2c, StdDelay> {
+        Scd30::from_i2c_with_address(LinuxI2c::new(device, address), StdDelay, address)
+    }
 }
 
 /// Implementation of SCD30 related
 /// operations
 ///
 ///
-impl Scd30 {
-    /// Create a new SCD30 Struct
+impl<I, D> Scd30<I, D> {
+    /// Create a new SCD30 Struct from any I2C bus and delay provider
     ///
-    /// Tries to create the device on standard address 0x61.
-    /// If fails, return an LinuxI2CError from i2cdev
+    /// The device is expected on standard address 0x61.
     ///
-    pub fn new() -> Result<Scd30, LinuxI2CError> {
-        let device = LinuxI2CDevice::new("/dev/i2c-1", 0x61)?;
-        Ok(Scd30 { i2cdev: device })
+    pub fn from_i2c(i2c: I, delay: D) -> Scd30<I, D> {
+        Scd30::from_i2c_with_address(i2c, delay, DEFAULT_ADDRESS)
     }
 
-    /// Checksum checker function
-    /// Thanks to [RequestForCoffee](https://github.com/RequestForCoffee)
-    /// for the python version of scd30 communication.
-    /// This code is an adaptation of the python version.
-    /// More info regarding the [algorithm](https://en.wikipedia.org/wiki/Computation_of_cyclic_redundancy_checks)
+    /// Create a new SCD30 Struct from any I2C bus and delay provider,
+    /// with the device on the given address.
     ///
-    pub fn crc8(message: &Vec<u8>) -> u8 {
-        let mut rem = 0xFF;
-        let polynomial = 0x31;
-        for byte in message {
-            rem ^= byte;
-            for _ in 0..8 {
-                if (rem & 0x80) != 0 {
-                    rem = (rem << 1) ^ polynomial;
-                } else {
-                    rem = rem << 1
-                }
-                rem &= 0xFF;
-            }
+    pub fn from_i2c_with_address(i2c: I, delay: D, address: u8) -> Scd30<I, D> {
+        Scd30 {
+            i2c,
+            delay,
+            address,
+            timing: Timing::default(),
         }
-        rem
     }
 
-    /// Checks on 4 bytes data if the checksum is correct
+    /// Changes the waits around the I2C transactions
     ///
-    /// The parameter is a 6 byte array, the first two and the checksum
-    /// and the other two with the ckecksum
+    pub fn with_timing(mut self, timing: Timing) -> Scd30<I, D> {
+        self.timing = timing;
+        self
+    }
+
+    /// Releases the I2C bus and the delay provider
     ///
-    fn check_crc_in_bytes(co2: &[u8]) -> bool {
-        //Splited in two two bytes with checksum
-        let first_crc = Scd30::crc8(&vec![co2[0], co2[1]]);
-        let second_crc = Scd30::crc8(&vec![co2[3], co2[4]]);
+    pub fn release(self) -> (I, D) {
+        (self.i2c, self.delay)
+    }
+}
+
+/// Implementation of SCD30 related
+/// operations over the I2C bus
+///
+///
+impl<I: I2c, D: DelayNs> Scd30<I, D> {
+    /// Sleeps the given duration, if any
+    fn wait(&mut self, duration: Duration) {
+        if !duration.is_zero() {
+            let us = u32::try_from(duration.as_micros()).unwrap_or(u32::MAX);
+            self.delay.delay_us(us);
+        }
+    }
+
+    /// Writes a command word, with its argument and checksum if any,
+    /// and waits for the device to process it
+    fn send(&mut self, command: u16, argument: Option<u16>) -> Result<(), Scd30Error<I::Error>> {
+        self.write_command(command, argument)?;
+        self.wait(self.timing.write_delay);
+        Ok(())
+    }
+
+    /// Writes a command word, with its argument and checksum if any
+    fn write_command(
+        &mut self,
+        command: u16,
+        argument: Option<u16>,
+    ) -> Result<(), Scd30Error<I::Error>> {
+        let (frame, len) = command_frame(command, argument);
+        self.i2c
+            .write(self.address, &frame[..len])
+            .map_err(|e| Scd30Error::write(command, e))
+    }
+
+    /// Writes a command word and reads `words.len()` data words,
+    /// checking the checksum of each one
+    fn read_words(&mut self, command: u16, words: &mut [u16]) -> Result<(), Scd30Error<I::Error>> {
+        self.write_command(command, None)?;
+        self.wait(self.timing.read_delay);
+        let mut data_buffer = [0; RESPONSE_LEN];
+        let data_buffer = &mut data_buffer[..sensirion_frame::frame_len(words.len())];
+        match self.i2c.read(self.address, data_buffer) {
+            Ok(_) => Ok(sensirion_frame::decode_words(data_buffer, words)?),
+            Err(e) => Err(Scd30Error::read(command, e)),
+        }
+    }
 
-        first_crc == co2[2] && second_crc == co2[5]
+    /// Writes a command word and reads one data word
+    fn read_word(&mut self, command: u16) -> Result<u16, Scd30Error<I::Error>> {
+        let mut words = [0; 1];
+        self.read_words(command, &mut words)?;
+        Ok(words[0])
     }
 
     /// Checks the firmware version of the SCD30 device.
     /// If fails, return SCD30Error.
     /// Else returns the firmware version.
     ///
-    pub fn check_firmware(&mut self) -> Result<u16, Scd30Error> {
-        let buffer: [u8; 2] = [0xd1, 0x00];
-        match self.i2cdev.write(&buffer) {
-            Ok(_) => {
-                let ten_millis = time::Duration::from_millis(30);
-                thread::sleep(ten_millis);
-                // Read data from the selected register
-                let mut data_buffer: [u8; 3] = [0; 3];
-                match self.i2cdev.read(&mut data_buffer) {
-                    Ok(_) => {
-                        if data_buffer[2] == Scd30::crc8(&vec![data_buffer[0], data_buffer[1]]) {
-                            Ok(u16::from_be_bytes([data_buffer[0], data_buffer[1]]))
-                        } else {
-                            Err(Scd30Error::ChecksumError)
-                        }
-                    }
-                    Err(_) => Err(Scd30Error::ComunicationError),
-                }
-            }
-
-            Err(_) => Err(Scd30Error::ComunicationError),
-        }
+    pub fn check_firmware(&mut self) -> Result<u16, Scd30Error<I::Error>> {
+        self.read_word(0xD100)
     }
 
     /// Trigger the continous measurements for SCD30 device.
+    /// Ambient pressure compensation is disabled.
     /// If fails return a communication error.
     /// If succeds, does not return anything.
     ///
-    pub fn trigger_cont_measurements(&mut self) -> Result<(), Scd30Error> {
-        let buffer: [u8; 5] = [0x00, 0x10, 0x00, 0x00, 0x81];
-        match self.i2cdev.write(&buffer) {
-            Ok(_) => {
-                let ten_millis = time::Duration::from_millis(30);
-                thread::sleep(ten_millis);
-                Ok(())
-            }
-            Err(_) => Err(Scd30Error::ComunicationError),
-        }
+    pub fn trigger_cont_measurements(&mut self) -> Result<(), Scd30Error<I::Error>> {
+        self.trigger_cont_measurements_with_pressure(0)
+    }
+
+    /// Trigger the continous measurements for SCD30 device
+    /// with ambient pressure compensation.
+    /// Pressure is in mbar, between 700 and 1400. A pressure of 0 disables
+    /// the compensation. Values outside the range return an OutOfRange error
+    /// without writing to the device.
+    /// If fails return a communication error.
+    /// If succeds, does not return anything.
+    ///
+    pub fn trigger_cont_measurements_with_pressure(
+        &mut self,
+        pressure: u16,
+    ) -> Result<(), Scd30Error<I::Error>> {
+        check_pressure(pressure)?;
+        self.send(0x0010, Some(pressure))
+    }
+
+    /// Updates the ambient pressure used for compensation while the
+    /// continous measurements are running. The device takes the new value
+    /// by sending again the trigger command with the new pressure.
+    /// Pressure is in mbar, between 700 and 1400, 0 disables the compensation.
+    /// If fails returns SCD30Error,
+    /// else return nothing.
+    pub fn set_ambient_pressure(&mut self, pressure: u16) -> Result<(), Scd30Error<I::Error>> {
+        self.trigger_cont_measurements_with_pressure(pressure)
     }
 
     /// Stops the continous measurements for SCD30 device.
     /// If fails return a communication error.
     /// If succeds, does not return anything.
     ///
-    pub fn stop_cont_measurements(&mut self) -> Result<(), Scd30Error> {
-        let buffer: [u8; 2] = [0x01, 0x01];
-        match self.i2cdev.write(&buffer) {
-            Ok(_) => {
-                let ten_millis = time::Duration::from_millis(30);
-                thread::sleep(ten_millis);
-                Ok(())
-            }
-            Err(_) => Err(Scd30Error::ComunicationError),
-        }
+    pub fn stop_cont_measurements(&mut self) -> Result<(), Scd30Error<I::Error>> {
+        self.send(0x0104, None)
     }
 
     /// Sets the measurements interval for the device,
-    /// the default is 2 seconds. You can change it using the second parameter
+    /// the default is 2 seconds. You can change it using the second parameter.
+    /// Valid values are between 2 and 1800 seconds, other values return
+    /// an OutOfRange error without writing to the device.
     ///
-    pub fn set_measurements_interval(&mut self, seconds: u16) -> Result<(), Scd30Error> {
-        let time_in_bytes: [u8; 2] = seconds.to_be_bytes();
-        let checksum = Scd30::crc8(&vec![time_in_bytes[0], time_in_bytes[1]]);
-        let buffer: [u8; 5] = [0x46, 0x00, time_in_bytes[0], time_in_bytes[1], checksum];
-        match self.i2cdev.write(&buffer) {
-            Ok(_) => {
-                let ten_millis = time::Duration::from_millis(30);
-                thread::sleep(ten_millis);
-                Ok(())
-            }
-            Err(_) => Err(Scd30Error::ComunicationError),
-        }
+    pub fn set_measurements_interval(&mut self, seconds: u16) -> Result<(), Scd30Error<I::Error>> {
+        check_interval(seconds)?;
+        self.send(0x4600, Some(seconds))
+    }
+
+    /// Gets the measurements interval of the device.
+    /// If fails, return SCD30Error.
+    /// Else returns the interval in seconds.
+    ///
+    pub fn get_measurements_interval(&mut self) -> Result<u16, Scd30Error<I::Error>> {
+        self.read_word(0x4600)
     }
 
     /// Gets if the device is ready for reading
     /// a measurement. If not, returns false.
     /// If error, returns the error.
-    pub fn get_data_ready(&mut self) -> Result<bool, Scd30Error> {
-        let buffer: [u8; 2] = [0x02, 0x02];
-        match self.i2cdev.write(&buffer) {
-            Ok(_) => {
-                let thirty_millis = time::Duration::from_millis(30);
-                thread::sleep(thirty_millis);
-                let mut data_buffer: [u8; 3] = [0; 3];
-                match self.i2cdev.read(&mut data_buffer) {
-                    Ok(_) => {
-                        if Scd30::crc8(&vec![data_buffer[0], data_buffer[1]]) == data_buffer[2] {
-                            if data_buffer[1] == 0x01 {
-                                Ok(true)
-                            } else {
-                                Ok(false)
-                            }
-                        } else {
-                            Err(Scd30Error::ChecksumError)
-                        }
-                    }
-                    Err(_) => Err(Scd30Error::ComunicationError),
-                }
-            }
-            Err(_) => Err(Scd30Error::ComunicationError),
-        }
+    pub fn get_data_ready(&mut self) -> Result<bool, Scd30Error<I::Error>> {
+        Ok(self.read_word(0x0202)? == 0x01)
     }
 
-    /// Get CO2, Temperature and Humidity for the device as a f32 tuple.
-    /// Checks the checksum for each pair of bytes, if everything ok returns the tuple.
+    /// Get CO2, Temperature and Humidity for the device as a Measurement.
+    /// Checks the checksum for each word, if everything ok returns the
+    /// Measurement with the time of the reading.
     /// In case of any problem, returns the error.
-    pub fn get_measurements(&mut self) -> Result<(f32, f32, f32), Scd30Error> {
-        let buffer: [u8; 2] = [0x03, 0x00];
-        match self.i2cdev.write(&buffer) {
-            Ok(_) => {
-                let ten_millis = time::Duration::from_millis(30);
-                thread::sleep(ten_millis);
-                let mut data_buffer: [u8; 18] = [0; 18];
-                match self.i2cdev.read(&mut data_buffer) {
-                    Ok(_) => {
-                        let co2_measurement = &data_buffer[0..6];
-                        let temp_measurement = &data_buffer[6..12];
-                        let rh_measurement = &data_buffer[12..=17];
-
-                        if Scd30::check_crc_in_bytes(co2_measurement)
-                            && Scd30::check_crc_in_bytes(temp_measurement)
-                            && Scd30::check_crc_in_bytes(rh_measurement)
-                        {
-                            Ok((
-                                f32::from_be_bytes([
-                                    co2_measurement[0],
-                                    co2_measurement[1],
-                                    co2_measurement[3],
-                                    co2_measurement[4],
-                                ]),
-                                f32::from_be_bytes([
-                                    temp_measurement[0],
-                                    temp_measurement[1],
-                                    temp_measurement[3],
-                                    temp_measurement[4],
-                                ]),
-                                f32::from_be_bytes([
-                                    rh_measurement[0],
-                                    rh_measurement[1],
-                                    rh_measurement[3],
-                                    rh_measurement[4],
-                                ]),
-                            ))
-                        } else {
-                            Err(Scd30Error::ChecksumError)
-                        }
-                    }
-                    Err(_) => Err(Scd30Error::ComunicationError),
-                }
-            }
-            Err(_) => Err(Scd30Error::ComunicationError),
-        }
+    pub fn get_measurements(&mut self) -> Result<Measurement, Scd30Error<I::Error>> {
+        let mut words = [0; 6];
+        self.read_words(0x0300, &mut words)?;
+        Ok(Measurement::from_words(&words))
     }
     /// Gets if the devive is in self calibration procedure or not. In case it fails,
     /// returns and SCD30 error
-    pub fn get_self_calibration_status(&mut self) -> Result<bool, Scd30Error> {
-        let buffer: [u8; 2] = [0x53, 0x06];
-        match self.i2cdev.write(&buffer) {
-            Ok(_) => {
-                let thirty_millis = time::Duration::from_millis(30);
-                thread::sleep(thirty_millis);
-                let mut data_buffer: [u8; 3] = [0; 3];
-                match self.i2cdev.read(&mut data_buffer) {
-                    Ok(_) => {
-                        if Scd30::crc8(&vec![data_buffer[0], data_buffer[1]]) == data_buffer[2] {
-                            if data_buffer[1] == 0x01 {
-                                Ok(true)
-                            } else {
-                                Ok(false)
-                            }
-                        } else {
-                            Err(Scd30Error::ChecksumError)
-                        }
-                    }
-                    Err(_) => Err(Scd30Error::ComunicationError),
-                }
-            }
-            Err(_) => Err(Scd30Error::ComunicationError),
-        }
+    pub fn get_self_calibration_status(&mut self) -> Result<bool, Scd30Error<I::Error>> {
+        Ok(self.read_word(0x5306)? == 0x01)
     }
 
     ///Set self calibration configuration. In this configuration, the device
@@ -295,62 +616,26 @@ impl Scd30 {
     /// 1 hour of fresh air per day, after that, the found value will be setted in non
     /// volatile memory.
     /// If fails returns communication errors, else returns nothing
-    pub fn set_self_calibration(&mut self, active: bool) -> Result<(), Scd30Error> {
-        let activate_function = if active { 0x01 } else { 0x00 };
-        let checksum = Scd30::crc8(&vec![0x00, activate_function]);
-        let buffer: [u8; 5] = [0x53, 0x06, 0x00, activate_function, checksum];
-        match self.i2cdev.write(&buffer) {
-            Ok(_) => {
-                let ten_millis = time::Duration::from_millis(30);
-                thread::sleep(ten_millis);
-                Ok(())
-            }
-            Err(_) => Err(Scd30Error::ComunicationError),
-        }
+    pub fn set_self_calibration(&mut self, active: bool) -> Result<(), Scd30Error<I::Error>> {
+        self.send(0x5306, Some(u16::from(active)))
     }
 
     /// Soft reset the sensor device.
     /// If fails, return SCD30Error.
     ///
-    pub fn soft_reset(&mut self) -> Result<(), Scd30Error> {
-        let buffer: [u8; 2] = [0xd3, 0x04];
-        match self.i2cdev.write(&buffer) {
-            Ok(_) => {
-                let ten_millis = time::Duration::from_millis(30);
-                thread::sleep(ten_millis);
-                Ok(())
-            }
-
-            Err(_) => Err(Scd30Error::ComunicationError),
-        }
+    pub fn soft_reset(&mut self) -> Result<(), Scd30Error<I::Error>> {
+        self.write_command(0xD304, None)?;
+        // The device does not answer until it boots again
+        self.wait(self.timing.boot_time);
+        Ok(())
     }
 
     /// Gets the set altitude of the device.
     /// If fails, return SCD30Error.
     /// Else returns the altitue in meters from sea level (0 meters).
     ///
-    pub fn get_altitude(&mut self) -> Result<u16, Scd30Error> {
-        let buffer: [u8; 2] = [0x51, 0x02];
-        match self.i2cdev.write(&buffer) {
-            Ok(_) => {
-                let ten_millis = time::Duration::from_millis(30);
-                thread::sleep(ten_millis);
-                // Read data from the selected register
-                let mut data_buffer: [u8; 3] = [0; 3];
-                match self.i2cdev.read(&mut data_buffer) {
-                    Ok(_) => {
-                        if data_buffer[2] == Scd30::crc8(&vec![data_buffer[0], data_buffer[1]]) {
-                            Ok(u16::from_be_bytes([data_buffer[0], data_buffer[1]]))
-                        } else {
-                            Err(Scd30Error::ChecksumError)
-                        }
-                    }
-                    Err(_) => Err(Scd30Error::ComunicationError),
-                }
-            }
-
-            Err(_) => Err(Scd30Error::ComunicationError),
-        }
+    pub fn get_altitude(&mut self) -> Result<u16, Scd30Error<I::Error>> {
+        self.read_word(0x5102)
     }
 
     /// Sets the altitude for the device.
@@ -358,121 +643,66 @@ impl Scd30 {
     /// If fails returns SCD30Error,
     /// else return nothing.
     /// After the set you can check the saved value to be the same as expected
-    pub fn set_altitude(&mut self, altitude: u16) -> Result<(), Scd30Error> {
-        let altitude_in_bytes: [u8; 2] = altitude.to_be_bytes();
-        let checksum = Scd30::crc8(&vec![altitude_in_bytes[0], altitude_in_bytes[1]]);
-        let buffer: [u8; 5] = [
-            0x51,
-            0x02,
-            altitude_in_bytes[0],
-            altitude_in_bytes[1],
-            checksum,
-        ];
-        match self.i2cdev.write(&buffer) {
-            Ok(_) => {
-                let ten_millis = time::Duration::from_millis(30);
-                thread::sleep(ten_millis);
-                Ok(())
-            }
-            Err(_) => Err(Scd30Error::ComunicationError),
-        }
+    pub fn set_altitude(&mut self, altitude: u16) -> Result<(), Scd30Error<I::Error>> {
+        self.send(0x5102, Some(altitude))
     }
 
     /// Gets the temperature offset of the device.
     /// If fails, return SCD30Error.
     /// Else returns the temperature offset in shif ticks, each tick 0.01 Celsius.
     ///
-    pub fn get_temperature_offset(&mut self) -> Result<u16, Scd30Error> {
-        let buffer: [u8; 2] = [0x54, 0x03];
-        match self.i2cdev.write(&buffer) {
-            Ok(_) => {
-                let ten_millis = time::Duration::from_millis(30);
-                thread::sleep(ten_millis);
-                // Read data from the selected register
-                let mut data_buffer: [u8; 3] = [0; 3];
-                match self.i2cdev.read(&mut data_buffer) {
-                    Ok(_) => {
-                        if data_buffer[2] == Scd30::crc8(&vec![data_buffer[0], data_buffer[1]]) {
-                            Ok(u16::from_be_bytes([data_buffer[0], data_buffer[1]]))
-                        } else {
-                            Err(Scd30Error::ChecksumError)
-                        }
-                    }
-                    Err(_) => Err(Scd30Error::ComunicationError),
-                }
-            }
-
-            Err(_) => Err(Scd30Error::ComunicationError),
-        }
+    pub fn get_temperature_offset(&mut self) -> Result<u16, Scd30Error<I::Error>> {
+        self.read_word(0x5403)
     }
 
     /// Sets the temperature offset of the device.
     /// Offset is a u16 correspoding to one tick, each tick is 0.01 Celsius of offset
     /// If fails returns SCD30Error,
     /// else return nothing.
-    pub fn set_temperature_offset(&mut self, offset: u16) -> Result<(), Scd30Error> {
-        let offset_in_bytes: [u8; 2] = offset.to_be_bytes();
-        let checksum = Scd30::crc8(&vec![offset_in_bytes[0], offset_in_bytes[1]]);
-        let buffer: [u8; 5] = [0x54, 0x03, offset_in_bytes[0], offset_in_bytes[1], checksum];
-        match self.i2cdev.write(&buffer) {
-            Ok(_) => {
-                let ten_millis = time::Duration::from_millis(30);
-                thread::sleep(ten_millis);
-                Ok(())
-            }
-            Err(_) => Err(Scd30Error::ComunicationError),
-        }
+    pub fn set_temperature_offset(&mut self, offset: u16) -> Result<(), Scd30Error<I::Error>> {
+        self.send(0x5403, Some(offset))
+    }
+
+    /// Gets the temperature offset of the device in Celsius.
+    /// If fails, return SCD30Error.
+    ///
+    pub fn get_temperature_offset_celsius(&mut self) -> Result<f32, Scd30Error<I::Error>> {
+        Ok(f32::from(self.get_temperature_offset()?) / 100.0)
+    }
+
+    /// Sets the temperature offset of the device in Celsius, rounded to the
+    /// 0.01 Celsius ticks of the device. Valid values are between 0 and
+    /// [`MAX_TEMPERATURE_OFFSET`], other values return an OutOfRange error
+    /// with the offset in ticks, without writing to the device.
+    /// If fails returns SCD30Error,
+    /// else return nothing.
+    pub fn set_temperature_offset_celsius(
+        &mut self,
+        offset_c: f32,
+    ) -> Result<(), Scd30Error<I::Error>> {
+        let ticks = offset_ticks(offset_c)?;
+        self.set_temperature_offset(ticks)
     }
 
     /// Checks the forced calibration value of the device.
     /// If fails, return SCD30Error.
     /// Else returns the forced value in ppm units.
     ///
-    pub fn get_forced_value(&mut self) -> Result<u16, Scd30Error> {
-        let buffer: [u8; 2] = [0x52, 0x04];
-        match self.i2cdev.write(&buffer) {
-            Ok(_) => {
-                let ten_millis = time::Duration::from_millis(30);
-                thread::sleep(ten_millis);
-                // Read data from the selected register
-                let mut data_buffer: [u8; 3] = [0; 3];
-                match self.i2cdev.read(&mut data_buffer) {
-                    Ok(_) => {
-                        if data_buffer[2] == Scd30::crc8(&vec![data_buffer[0], data_buffer[1]]) {
-                            Ok(u16::from_be_bytes([data_buffer[0], data_buffer[1]]))
-                        } else {
-                            Err(Scd30Error::ChecksumError)
-                        }
-                    }
-                    Err(_) => Err(Scd30Error::ComunicationError),
-                }
-            }
-
-            Err(_) => Err(Scd30Error::ComunicationError),
-        }
+    pub fn get_forced_value(&mut self) -> Result<u16, Scd30Error<I::Error>> {
+        self.read_word(0x5204)
     }
 
     /// Sets a force recalibration value to the device.
     /// Usually this is use when no time for automatic self calibration is posible.
+    /// Valid values are between 400 and 2000 ppm, other values return
+    /// an OutOfRange error without writing to the device.
     /// If fails returns SCD30Error,
     /// else return nothing.
-    pub fn set_force_recalibration_value(&mut self, forced_value: u16) -> Result<(), Scd30Error> {
-        let forced_value_in_bytes: [u8; 2] = forced_value.to_be_bytes();
-        let checksum = Scd30::crc8(&vec![forced_value_in_bytes[0], forced_value_in_bytes[1]]);
-        let buffer: [u8; 5] = [
-            0x52,
-            0x04,
-            forced_value_in_bytes[0],
-            forced_value_in_bytes[1],
-            checksum,
-        ];
-        match self.i2cdev.write(&buffer) {
-            Ok(_) => {
-                let ten_millis = time::Duration::from_millis(30);
-                thread::sleep(ten_millis);
-                Ok(())
-            }
-            Err(_) => Err(Scd30Error::ComunicationError),
-        }
+    pub fn set_force_recalibration_value(
+        &mut self,
+        forced_value: u16,
+    ) -> Result<(), Scd30Error<I::Error>> {
+        check_forced_value(forced_value)?;
+        self.send(0x5204, Some(forced_value))
     }
 }
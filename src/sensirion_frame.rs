@@ -0,0 +1,223 @@
+// Copyright 2024, F. Stan
+//
+// Licensed under the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Word and checksum framing of the Sensirion sensors
+//!
+//! Sensirion sensors exchange 16 bit big endian words. A write frame is a command word,
+//! optionally followed by argument words, and a read frame is a sequence of data words.
+//! Every argument and data word is followed by its CRC-8 checksum, polynomial 0x31 and
+//! initialization 0xFF. The same framing is used by the SCD30, SCD4x, SHT4x or SGP40, so
+//! other drivers can reuse this module. It does not need `std` nor alloc.
+//!
+//!```
+//!use scd30_i2c::sensirion_frame::{self, FrameError};
+//!
+//!// Set altitude to 100 m
+//!let frame = sensirion_frame::encode_command_with_argument(0x5102, 100);
+//!assert_eq!(frame, [0x51, 0x02, 0x00, 0x64, 0xFE]);
+//!
+//!// Response of get firmware version, 3.66
+//!let mut words = [0; 1];
+//!sensirion_frame::decode_words(&[0x03, 0x42, 0xF3], &mut words).unwrap();
+//!assert_eq!(words, [0x0342]);
+//!
+//!// Second word with a wrong checksum
+//!assert_eq!(
+//!    sensirion_frame::decode_words(&[0x03, 0x42, 0xF3, 0x00, 0x00, 0x00], &mut [0; 2]),
+//!    Err(FrameError::Checksum { word: 1, expected: 0x81, actual: 0x00 })
+//!);
+//!```
+
+use core::error::Error;
+use core::fmt;
+
+/// Polynomial of the CRC-8 checksum, x^8 + x^5 + x^4 + 1
+pub const CRC8_POLYNOMIAL: u8 = 0x31;
+/// Initial value of the CRC-8 checksum
+pub const CRC8_INIT: u8 = 0xFF;
+/// Bytes of a data word followed by its checksum
+pub const WORD_LEN: usize = 3;
+/// Bytes of a command word, sent without checksum
+pub const COMMAND_LEN: usize = 2;
+
+///
+///Framing error enum. Checksum when the checksum of a word does
+///not correspond with the calculated one, keeping the position of
+///the word. TooShort when a buffer does not hold the whole frame
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameError {
+    /// Checksum of the word at position `word`, starting on 0, does not match
+    Checksum {
+        word: usize,
+        expected: u8,
+        actual: u8,
+    },
+    /// Buffer of `actual` bytes where the frame needs `expected` bytes
+    TooShort { expected: usize, actual: usize },
+}
+
+impl FrameError {
+    /// Bytes missing in the buffer, 0 for checksum errors
+    ///
+    pub fn missing(&self) -> usize {
+        match *self {
+            FrameError::TooShort { expected, actual } => expected - actual,
+            FrameError::Checksum { .. } => 0,
+        }
+    }
+}
+
+///Implementation of display for FrameError
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FrameError::Checksum {
+                word,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Checksum error in word {}, expected 0x{:02X} but found 0x{:02X}",
+                word, expected, actual
+            ),
+            FrameError::TooShort { expected, actual } => write!(
+                f,
+                "Frame is {} bytes short, expected {} bytes but found {}",
+                expected - actual,
+                expected,
+                actual
+            ),
+        }
+    }
+}
+
+///Implementation for Error to FrameError
+impl Error for FrameError {}
+
+/// CRC-8 checksum of the bytes, polynomial 0x31 and initialization 0xFF.
+/// Thanks to [RequestForCoffee](https://github.com/RequestForCoffee)
+/// for the python version of scd30 communication, this code is an adaptation of it.
+/// More info regarding the [algorithm](https://en.wikipedia.org/wiki/Computation_of_cyclic_redundancy_checks)
+///
+pub fn crc8(data: &[u8]) -> u8 {
+    let mut rem = CRC8_INIT;
+    for byte in data {
+        rem ^= byte;
+        for _ in 0..8 {
+            if (rem & 0x80) != 0 {
+                rem = (rem << 1) ^ CRC8_POLYNOMIAL;
+            } else {
+                rem <<= 1;
+            }
+        }
+    }
+    rem
+}
+
+/// Bytes of a frame with the given number of data words
+///
+pub const fn frame_len(words: usize) -> usize {
+    words * WORD_LEN
+}
+
+/// Encodes a command word without arguments
+///
+pub fn encode_command(command: u16) -> [u8; COMMAND_LEN] {
+    command.to_be_bytes()
+}
+
+/// Encodes a command word followed by one argument word and its checksum
+///
+pub fn encode_command_with_argument(command: u16, argument: u16) -> [u8; COMMAND_LEN + WORD_LEN] {
+    let [command_high, command_low] = command.to_be_bytes();
+    let [high, low] = argument.to_be_bytes();
+    [command_high, command_low, high, low, crc8(&[high, low])]
+}
+
+/// Encodes a command word followed by the argument words, each one with its
+/// checksum, at the start of the buffer. Returns the length of the frame.
+///
+pub fn encode_command_with_arguments(
+    command: u16,
+    arguments: &[u16],
+    buffer: &mut [u8],
+) -> Result<usize, FrameError> {
+    let expected = COMMAND_LEN + frame_len(arguments.len());
+    check_len(expected, buffer.len())?;
+    buffer[..COMMAND_LEN].copy_from_slice(&encode_command(command));
+    encode_words(arguments, &mut buffer[COMMAND_LEN..])?;
+    Ok(expected)
+}
+
+/// Encodes data words, each one followed by its checksum, at the start
+/// of the buffer, as the sensors send them. Returns the length of the frame.
+///
+pub fn encode_words(words: &[u16], buffer: &mut [u8]) -> Result<usize, FrameError> {
+    let expected = frame_len(words.len());
+    check_len(expected, buffer.len())?;
+    for (word, bytes) in words.iter().zip(buffer.chunks_exact_mut(WORD_LEN)) {
+        let [high, low] = word.to_be_bytes();
+        bytes.copy_from_slice(&[high, low, crc8(&[high, low])]);
+    }
+    Ok(expected)
+}
+
+/// Decodes one data word followed by its checksum
+///
+pub fn decode_word(bytes: &[u8]) -> Result<u16, FrameError> {
+    let mut words = [0; 1];
+    decode_words(bytes, &mut words)?;
+    Ok(words[0])
+}
+
+/// Decodes `words.len()` data words, checking the checksum of each one.
+/// The bytes after the frame are ignored, since the sensors allow reading
+/// only the first words of a response.
+///
+pub fn decode_words(bytes: &[u8], words: &mut [u16]) -> Result<(), FrameError> {
+    check_len(frame_len(words.len()), bytes.len())?;
+    for (i, (word, bytes)) in words
+        .iter_mut()
+        .zip(bytes.chunks_exact(WORD_LEN))
+        .enumerate()
+    {
+        let expected = crc8(&bytes[..2]);
+        if expected != bytes[2] {
+            return Err(FrameError::Checksum {
+                word: i,
+                expected,
+                actual: bytes[2],
+            });
+        }
+        *word = u16::from_be_bytes([bytes[0], bytes[1]]);
+    }
+    Ok(())
+}
+
+/// Decodes a float sent as two data words, the most significant first
+///
+pub fn decode_f32(bytes: &[u8]) -> Result<f32, FrameError> {
+    let mut words = [0; 2];
+    decode_words(bytes, &mut words)?;
+    Ok(f32_from_words(words[0], words[1]))
+}
+
+/// Float from its two words, the most significant first
+///
+pub fn f32_from_words(high: u16, low: u16) -> f32 {
+    f32::from_bits(u32::from(high) << 16 | u32::from(low))
+}
+
+/// Fails with TooShort if the buffer does not hold the frame
+fn check_len(expected: usize, actual: usize) -> Result<(), FrameError> {
+    if actual < expected {
+        Err(FrameError::TooShort { expected, actual })
+    } else {
+        Ok(())
+    }
+}
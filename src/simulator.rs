@@ -0,0 +1,455 @@
+// Copyright 2024, F. Stan
+//
+// Licensed under the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! In-memory model of the SCD30 device
+//!
+//! [`Scd30Simulator`](crate::simulator::Scd30Simulator) decodes the command words sent
+//! by the driver, keeps the device state (interval, altitude, temperature offset, self
+//! calibration...) and generates measurements with correct checksums, so the driver can
+//! be used without hardware. The simulator and its
+//! [`SimulatedDelay`](crate::simulator::SimulatedDelay) share a clock, time
+//! only moves forward when the driver waits. Like the device, it does not acknowledge
+//! reads sooner than 3 ms after the command, nor any transaction while it boots for
+//! 2 seconds after a soft reset.
+//!
+//!```
+//!use scd30_i2c::scd30::Scd30;
+//!use scd30_i2c::simulator::Scd30Simulator;
+//!
+//!let simulator = Scd30Simulator::new();
+//!let delay = simulator.delay();
+//!let mut scd = Scd30::from_i2c(simulator, delay);
+//!
+//!scd.trigger_cont_measurements().unwrap();
+//!while !scd.get_data_ready().unwrap() {}
+//!let measurement = scd.get_measurements().unwrap();
+//!assert!(measurement.co2_ppm > 400.0);
+//!```
+
+use crate::scd30::{BOOT_TIME, DEFAULT_ADDRESS, READ_DELAY};
+use crate::sensirion_frame;
+use embedded_hal::delay::DelayNs;
+use embedded_hal::i2c::{self, ErrorKind, ErrorType, I2c, NoAcknowledgeSource, Operation};
+use std::cell::Cell;
+use std::error::Error;
+use std::fmt;
+use std::rc::Rc;
+use std::time::Duration;
+
+/// Firmware version reported by the simulator
+pub const SIMULATED_FIRMWARE: u16 = 0x0342;
+
+///
+/// Simulator error, wraps the embedded-hal error kind
+/// that a real bus would report for the same failure
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SimulatorError(pub ErrorKind);
+
+///Implementation of display for SimulatorError
+impl fmt::Display for SimulatorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+///Implementation for Error to SimulatorError
+impl Error for SimulatorError {}
+
+///Implementation for embedded-hal I2C error to SimulatorError
+impl i2c::Error for SimulatorError {
+    fn kind(&self) -> ErrorKind {
+        self.0
+    }
+}
+
+/// Faults that can be injected in the simulator,
+/// to test how the driver reacts to a misbehaving device
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// The device does not acknowledge the next write
+    NackWrite,
+    /// The device does not acknowledge the next read
+    NackRead,
+    /// The next read returns a wrong checksum on the first word
+    BadCrc,
+}
+
+/// Delay provider for the simulator. Instead of sleeping,
+/// moves forward the clock shared with the simulator
+///
+#[derive(Debug, Clone)]
+pub struct SimulatedDelay {
+    clock: Rc<Cell<u64>>,
+}
+
+impl SimulatedDelay {
+    /// Elapsed simulated time since the simulator was created
+    ///
+    pub fn elapsed(&self) -> Duration {
+        Duration::from_nanos(self.clock.get())
+    }
+}
+
+impl DelayNs for SimulatedDelay {
+    fn delay_ns(&mut self, ns: u32) {
+        self.clock.set(self.clock.get() + u64::from(ns));
+    }
+}
+
+#[cfg(feature = "async")]
+impl embedded_hal_async::delay::DelayNs for SimulatedDelay {
+    async fn delay_ns(&mut self, ns: u32) {
+        DelayNs::delay_ns(self, ns);
+    }
+}
+
+/// Software model of the SCD30 device, implementing the
+/// embedded-hal I2C trait so it can be used as the bus of Scd30
+///
+#[derive(Debug)]
+pub struct Scd30Simulator {
+    clock: Rc<Cell<u64>>,
+    address: u8,
+    /// Response prepared by the last command, waiting to be read
+    response: Option<Vec<u8>>,
+    /// Time when the response can be read
+    ready_at: u64,
+    /// Time when the device answers again after a soft reset
+    booting_until: u64,
+    measuring: bool,
+    pressure: u16,
+    interval: u16,
+    altitude: u16,
+    temperature_offset: u16,
+    self_calibration: bool,
+    forced_value: u16,
+    next_sample_at: u64,
+    samples: u32,
+    data_ready: bool,
+    co2_correction: f32,
+    environment: (f32, f32, f32),
+    last_sample: (f32, f32, f32),
+    fault: Option<(Fault, u32)>,
+    commands: Vec<u16>,
+}
+
+impl Default for Scd30Simulator {
+    fn default() -> Self {
+        Scd30Simulator::new()
+    }
+}
+
+impl Scd30Simulator {
+    /// Create a new simulator with the factory configuration, answering
+    /// on the standard address 0x61 and with an indoor environment of
+    /// 650 ppm, 22.5 Celsius and 45 % of relative humidity
+    ///
+    pub fn new() -> Scd30Simulator {
+        Scd30Simulator {
+            clock: Rc::new(Cell::new(0)),
+            address: DEFAULT_ADDRESS,
+            response: None,
+            ready_at: 0,
+            booting_until: 0,
+            measuring: false,
+            pressure: 0,
+            interval: 2,
+            altitude: 0,
+            temperature_offset: 0,
+            self_calibration: false,
+            forced_value: 400,
+            next_sample_at: 0,
+            samples: 0,
+            data_ready: false,
+            co2_correction: 0.0,
+            environment: (650.0, 22.5, 45.0),
+            last_sample: (0.0, 0.0, 0.0),
+            fault: None,
+            commands: Vec::new(),
+        }
+    }
+
+    /// Delay provider sharing the clock of this simulator
+    ///
+    pub fn delay(&self) -> SimulatedDelay {
+        SimulatedDelay {
+            clock: Rc::clone(&self.clock),
+        }
+    }
+
+    /// Moves forward the simulated clock
+    ///
+    pub fn advance(&mut self, duration: Duration) {
+        self.clock
+            .set(self.clock.get().saturating_add(nanos(duration)));
+    }
+
+    /// Sets the real CO2 in ppm, temperature in Celsius and relative
+    /// humidity in % around the simulated device
+    ///
+    pub fn set_environment(&mut self, co2: f32, temperature: f32, humidity: f32) {
+        self.environment = (co2, temperature, humidity);
+    }
+
+    /// Injects a fault for the next `times` matching operations
+    ///
+    pub fn inject_fault(&mut self, fault: Fault, times: u32) {
+        self.fault = Some((fault, times));
+    }
+
+    /// Command words received by the simulator, in order
+    ///
+    pub fn commands(&self) -> &[u16] {
+        &self.commands
+    }
+
+    /// If the continuous measurements are running
+    pub fn is_measuring(&self) -> bool {
+        self.measuring
+    }
+
+    /// Ambient pressure in mbar sent with the last trigger, 0 if disabled
+    pub fn pressure(&self) -> u16 {
+        self.pressure
+    }
+
+    /// Measurement interval in seconds
+    pub fn interval(&self) -> u16 {
+        self.interval
+    }
+
+    /// Altitude in meters
+    pub fn altitude(&self) -> u16 {
+        self.altitude
+    }
+
+    /// Temperature offset in ticks of 0.01 Celsius
+    pub fn temperature_offset(&self) -> u16 {
+        self.temperature_offset
+    }
+
+    /// If automatic self calibration is active
+    pub fn self_calibration(&self) -> bool {
+        self.self_calibration
+    }
+
+    /// Last forced recalibration value in ppm
+    pub fn forced_value(&self) -> u16 {
+        self.forced_value
+    }
+
+    /// Takes the pending fault if it matches, decreasing its counter
+    fn take_fault(&mut self, fault: Fault) -> bool {
+        match self.fault {
+            Some((pending, times)) if pending == fault => {
+                self.fault = if times > 1 {
+                    Some((pending, times - 1))
+                } else {
+                    None
+                };
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Generates the samples due since the last transaction
+    fn update(&mut self) {
+        let now = self.clock.get();
+        let interval = u64::from(self.interval) * 1_000_000_000;
+        while self.measuring && now >= self.next_sample_at {
+            self.samples = self.samples.wrapping_add(1);
+            // Small deterministic noise, so consecutive samples are different
+            let noise = ((self.samples % 7) as f32 - 3.0) / 3.0;
+            let (co2, temperature, humidity) = self.environment;
+            self.last_sample = (
+                co2 + self.co2_correction + noise * 5.0,
+                temperature - f32::from(self.temperature_offset) / 100.0 + noise * 0.05,
+                humidity + noise * 0.2,
+            );
+            self.data_ready = true;
+            self.next_sample_at += interval;
+        }
+    }
+
+    /// Executes a command word, with its argument if any
+    fn execute(&mut self, command: u16, argument: Option<u16>) -> Result<(), SimulatorError> {
+        let nack = SimulatorError(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Data));
+        match (command, argument) {
+            (0x0010, Some(pressure)) => {
+                if pressure != 0 && !(700..=1400).contains(&pressure) {
+                    return Err(nack);
+                }
+                self.pressure = pressure;
+                if !self.measuring {
+                    self.measuring = true;
+                    self.next_sample_at =
+                        self.clock.get() + u64::from(self.interval) * 1_000_000_000;
+                }
+            }
+            (0x0104, None) => {
+                self.measuring = false;
+                self.data_ready = false;
+            }
+            (0x4600, Some(interval)) => {
+                if !(2..=1800).contains(&interval) {
+                    return Err(nack);
+                }
+                self.interval = interval;
+            }
+            (0x4600, None) => self.response = Some(words(&[self.interval])),
+            (0x0202, None) => self.response = Some(words(&[u16::from(self.data_ready)])),
+            (0x0300, None) => {
+                let (co2, temperature, humidity) = self.last_sample;
+                let co2 = co2.to_bits();
+                let temperature = temperature.to_bits();
+                let humidity = humidity.to_bits();
+                self.data_ready = false;
+                self.response = Some(words(&[
+                    (co2 >> 16) as u16,
+                    co2 as u16,
+                    (temperature >> 16) as u16,
+                    temperature as u16,
+                    (humidity >> 16) as u16,
+                    humidity as u16,
+                ]));
+            }
+            (0x5306, Some(active)) => self.self_calibration = active == 1,
+            (0x5306, None) => self.response = Some(words(&[u16::from(self.self_calibration)])),
+            (0x5204, Some(forced_value)) => {
+                if !(400..=2000).contains(&forced_value) {
+                    return Err(nack);
+                }
+                self.forced_value = forced_value;
+                self.co2_correction = f32::from(forced_value) - self.environment.0;
+            }
+            (0x5204, None) => self.response = Some(words(&[self.forced_value])),
+            (0x5403, Some(offset)) => self.temperature_offset = offset,
+            (0x5403, None) => self.response = Some(words(&[self.temperature_offset])),
+            (0x5102, Some(altitude)) => self.altitude = altitude,
+            (0x5102, None) => self.response = Some(words(&[self.altitude])),
+            (0xD100, None) => self.response = Some(words(&[SIMULATED_FIRMWARE])),
+            (0xD304, None) => {
+                // Configuration is kept in non volatile memory, only the
+                // pending data is lost
+                self.data_ready = false;
+                self.booting_until = self.clock.get() + nanos(BOOT_TIME);
+            }
+            _ => return Err(nack),
+        }
+        Ok(())
+    }
+
+    /// Decodes a write frame, a command word with an optional argument
+    fn write_frame(&mut self, bytes: &[u8]) -> Result<(), SimulatorError> {
+        let nack = SimulatorError(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Data));
+        self.response = None;
+        if bytes.len() != 2 && bytes.len() != 5 {
+            return Err(nack);
+        }
+        let command = u16::from_be_bytes([bytes[0], bytes[1]]);
+        self.commands.push(command);
+        self.ready_at = self.clock.get() + nanos(READ_DELAY);
+        let argument = if bytes.len() == 5 {
+            Some(sensirion_frame::decode_word(&bytes[2..]).map_err(|_| nack)?)
+        } else {
+            None
+        };
+        self.execute(command, argument)
+    }
+
+    /// Copies the pending response to the buffer
+    fn read_frame(&mut self, buffer: &mut [u8]) -> Result<(), SimulatorError> {
+        // Reading too soon after the command is not acknowledged
+        if self.clock.get() < self.ready_at {
+            return Err(SimulatorError(ErrorKind::NoAcknowledge(
+                NoAcknowledgeSource::Address,
+            )));
+        }
+        let response = match self.response.take() {
+            Some(response) => response,
+            None => {
+                return Err(SimulatorError(ErrorKind::NoAcknowledge(
+                    NoAcknowledgeSource::Address,
+                )))
+            }
+        };
+        for (i, byte) in buffer.iter_mut().enumerate() {
+            *byte = response.get(i).copied().unwrap_or(0xFF);
+        }
+        if self.take_fault(Fault::BadCrc) && buffer.len() >= 3 {
+            buffer[2] ^= 0xFF;
+        }
+        Ok(())
+    }
+}
+
+impl ErrorType for Scd30Simulator {
+    type Error = SimulatorError;
+}
+
+impl I2c for Scd30Simulator {
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        if address != self.address || self.clock.get() < self.booting_until {
+            return Err(SimulatorError(ErrorKind::NoAcknowledge(
+                NoAcknowledgeSource::Address,
+            )));
+        }
+        self.update();
+        for operation in operations {
+            match operation {
+                Operation::Write(bytes) => {
+                    if self.take_fault(Fault::NackWrite) {
+                        return Err(SimulatorError(ErrorKind::NoAcknowledge(
+                            NoAcknowledgeSource::Data,
+                        )));
+                    }
+                    self.write_frame(bytes)?;
+                }
+                Operation::Read(buffer) => {
+                    if self.take_fault(Fault::NackRead) {
+                        return Err(SimulatorError(ErrorKind::NoAcknowledge(
+                            NoAcknowledgeSource::Address,
+                        )));
+                    }
+                    self.read_frame(buffer)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "async")]
+impl embedded_hal_async::i2c::I2c for Scd30Simulator {
+    async fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        I2c::transaction(self, address, operations)
+    }
+}
+
+/// Duration in nanoseconds of the simulated clock
+fn nanos(duration: Duration) -> u64 {
+    u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX)
+}
+
+/// Encodes data words followed by their checksum, as the device sends them
+fn words(data: &[u16]) -> Vec<u8> {
+    let mut bytes = vec![0; sensirion_frame::frame_len(data.len())];
+    // The buffer has the length of the frame
+    let _ = sensirion_frame::encode_words(data, &mut bytes);
+    bytes
+}
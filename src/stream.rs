@@ -0,0 +1,311 @@
+// Copyright 2024, F. Stan
+//
+// Licensed under the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use crate::measurement::Measurement;
+use crate::scd30::{Scd30, Scd30Error};
+use core::time::Duration;
+use embedded_hal::delay::DelayNs;
+use embedded_hal::i2c::I2c;
+
+/// Default time between data ready checks
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// What the measurements iterator does when the device returns an error
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecoveryPolicy {
+    /// Yields the error and ends the iteration
+    Stop,
+    /// Yields the error, and waits before trying again on the next item
+    Continue { wait: Duration },
+    /// Retries silently up to `attempts` times waiting between them.
+    /// If all of them fail, yields the last error and keeps going
+    Retry { attempts: u32, wait: Duration },
+}
+
+/// Default policy, same as the usual manual loop: yield the error
+/// and wait 10 seconds before trying again
+impl Default for RecoveryPolicy {
+    fn default() -> Self {
+        RecoveryPolicy::Continue {
+            wait: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Sleeps of the measurement loops are given in milliseconds
+pub(crate) fn millis(duration: Duration) -> u32 {
+    u32::try_from(duration.as_millis()).unwrap_or(u32::MAX)
+}
+
+/// Device operations of a blocking measurements loop, implemented by the driver,
+/// the retrying driver and the MQTT publisher
+pub(crate) trait MeasurementSource {
+    /// Bus error of the device
+    type Error;
+
+    /// Reads the measurement interval in seconds
+    fn read_interval(&mut self) -> Result<u16, Scd30Error<Self::Error>>;
+
+    /// Checks the data ready flag
+    fn data_ready(&mut self) -> Result<bool, Scd30Error<Self::Error>>;
+
+    /// Reads the measurement
+    fn read_measurement(&mut self) -> Result<Measurement, Scd30Error<Self::Error>>;
+
+    /// Sleeps the given duration. Returns true if the measurement interval
+    /// may have changed meanwhile and must be read again
+    fn sleep(&mut self, duration: Duration) -> bool;
+}
+
+/// State of a measurements loop, shared by the blocking loops and the
+/// async stream: the recovery policy, the measurement interval of the device
+/// and the waits between the reads.
+///
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct StreamState {
+    policy: RecoveryPolicy,
+    poll_interval: Duration,
+    interval: Option<u16>,
+    pending_wait: Option<Duration>,
+    waited: Duration,
+    failures: u32,
+    finished: bool,
+}
+
+impl StreamState {
+    /// New loop state, the measurement interval is read on the first item
+    pub(crate) fn new(policy: RecoveryPolicy, poll_interval: Duration) -> StreamState {
+        StreamState {
+            policy,
+            poll_interval,
+            interval: None,
+            pending_wait: None,
+            waited: Duration::ZERO,
+            failures: 0,
+            finished: false,
+        }
+    }
+
+    /// Changes the policy used when the device returns an error
+    pub(crate) fn set_policy(&mut self, policy: RecoveryPolicy) {
+        self.policy = policy;
+    }
+
+    /// Changes the time between data ready checks
+    pub(crate) fn set_poll_interval(&mut self, poll_interval: Duration) {
+        self.poll_interval = poll_interval;
+    }
+
+    /// Measurement interval of the device, if already read
+    #[cfg(feature = "async")]
+    pub(crate) fn interval(&self) -> Option<u16> {
+        self.interval
+    }
+
+    /// Forgets the measurement interval, so it is read again on the next item
+    pub(crate) fn reset_interval(&mut self) {
+        self.interval = None;
+    }
+
+    /// Starts a new item, returning the wait before the first read,
+    /// or None if the iteration has ended
+    pub(crate) fn start(&mut self) -> Option<Duration> {
+        if self.finished {
+            return None;
+        }
+        self.failures = 0;
+        Some(self.pending_wait.take().unwrap_or_default())
+    }
+
+    /// Starts waiting for the data ready flag with the given measurement interval
+    pub(crate) fn begin_read(&mut self, interval: u16) {
+        self.interval = Some(interval);
+        self.waited = Duration::ZERO;
+    }
+
+    /// Data is not ready yet, returns the wait before checking again.
+    /// Fails with a timeout if no data is ready after two intervals.
+    pub(crate) fn not_ready<E>(&mut self) -> Result<Duration, Scd30Error<E>> {
+        let interval = Duration::from_secs(u64::from(self.interval.unwrap_or(0)));
+        if self.waited >= interval * 2 + Duration::from_secs(1) {
+            return Err(Scd30Error::Timeout);
+        }
+        self.waited += self.poll_interval;
+        Ok(self.poll_interval)
+    }
+
+    /// A measurement was read, the next sample will not be ready before the interval
+    pub(crate) fn succeeded(&mut self) {
+        let interval = Duration::from_secs(u64::from(self.interval.unwrap_or(0)));
+        self.pending_wait = Some(interval.saturating_sub(self.poll_interval));
+    }
+
+    /// A read failed. Returns the wait before retrying silently,
+    /// or None if the error must be yielded
+    pub(crate) fn failed(&mut self) -> Option<Duration> {
+        match self.policy {
+            RecoveryPolicy::Stop => {
+                self.finished = true;
+                None
+            }
+            RecoveryPolicy::Continue { wait } => {
+                self.pending_wait = Some(wait);
+                None
+            }
+            RecoveryPolicy::Retry { attempts, wait } => {
+                if self.failures >= attempts {
+                    self.pending_wait = Some(wait);
+                    return None;
+                }
+                self.failures += 1;
+                Some(wait)
+            }
+        }
+    }
+
+    /// Next item of a blocking loop: waits for the data ready flag and reads
+    /// the measurement, retrying or yielding the errors following the policy.
+    /// None if the iteration has ended
+    pub(crate) fn next_item<S: MeasurementSource>(
+        &mut self,
+        source: &mut S,
+    ) -> Option<Result<Measurement, Scd30Error<S::Error>>> {
+        let wait = self.start()?;
+        self.sleep(source, wait);
+        loop {
+            match self.read(source) {
+                Ok(measurement) => {
+                    self.succeeded();
+                    return Some(Ok(measurement));
+                }
+                Err(e) => match self.failed() {
+                    Some(wait) => self.sleep(source, wait),
+                    None => return Some(Err(e)),
+                },
+            }
+        }
+    }
+
+    /// Sleeps between reads, forgetting the interval if it may have changed
+    fn sleep<S: MeasurementSource>(&mut self, source: &mut S, duration: Duration) {
+        if source.sleep(duration) {
+            self.reset_interval();
+        }
+    }
+
+    /// Waits for the data ready flag and reads one measurement, reading the
+    /// measurement interval again if it changed while waiting
+    fn read<S: MeasurementSource>(
+        &mut self,
+        source: &mut S,
+    ) -> Result<Measurement, Scd30Error<S::Error>> {
+        'interval: loop {
+            let interval = match self.interval {
+                Some(interval) => interval,
+                None => source.read_interval()?,
+            };
+            self.begin_read(interval);
+            while !source.data_ready()? {
+                let wait = self.not_ready()?;
+                if source.sleep(wait) {
+                    self.reset_interval();
+                    continue 'interval;
+                }
+            }
+            return source.read_measurement();
+        }
+    }
+}
+
+impl<I: I2c, D: DelayNs> MeasurementSource for Scd30<I, D> {
+    type Error = I::Error;
+
+    fn read_interval(&mut self) -> Result<u16, Scd30Error<I::Error>> {
+        self.get_measurements_interval()
+    }
+
+    fn data_ready(&mut self) -> Result<bool, Scd30Error<I::Error>> {
+        self.get_data_ready()
+    }
+
+    fn read_measurement(&mut self) -> Result<Measurement, Scd30Error<I::Error>> {
+        self.get_measurements()
+    }
+
+    fn sleep(&mut self, duration: Duration) -> bool {
+        if !duration.is_zero() {
+            self.delay.delay_ms(millis(duration));
+        }
+        false
+    }
+}
+
+/// Blocking iterator over the SCD30 measurements.
+///
+/// Waits for the data ready flag before each read, so every measurement
+/// is read only once, and sleeps the configured measurement interval between
+/// reads. Created with [`Scd30::measurements`], the continous measurements must be
+/// already triggered.
+///
+pub struct Measurements<'a, I, D> {
+    scd: &'a mut Scd30<I, D>,
+    state: StreamState,
+}
+
+impl<'a, I: I2c, D: DelayNs> Measurements<'a, I, D> {
+    /// Create a new iterator over the given device
+    ///
+    pub fn new(scd: &'a mut Scd30<I, D>) -> Measurements<'a, I, D> {
+        Measurements {
+            scd,
+            state: StreamState::new(RecoveryPolicy::default(), DEFAULT_POLL_INTERVAL),
+        }
+    }
+
+    /// Sets the policy used when the device returns an error
+    ///
+    pub fn with_policy(mut self, policy: RecoveryPolicy) -> Self {
+        self.state.set_policy(policy);
+        self
+    }
+
+    /// Sets the time between data ready checks
+    ///
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.state.set_poll_interval(poll_interval);
+        self
+    }
+
+    /// Gives access to the device between items, for example to change
+    /// its configuration. The measurement interval is read again on the next item.
+    ///
+    pub fn device_mut(&mut self) -> &mut Scd30<I, D> {
+        self.state.reset_interval();
+        self.scd
+    }
+}
+
+impl<I: I2c, D: DelayNs> Iterator for Measurements<'_, I, D> {
+    type Item = Result<Measurement, Scd30Error<I::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.state.next_item(self.scd)
+    }
+}
+
+impl<I: I2c, D: DelayNs> Scd30<I, D> {
+    /// Blocking iterator over the measurements of the device.
+    ///
+    /// Each item waits for new data using the data ready flag, so there are no
+    /// duplicated reads. Errors are handled with the default [`RecoveryPolicy`],
+    /// it can be changed with [`Measurements::with_policy`].
+    ///
+    pub fn measurements(&mut self) -> Measurements<'_, I, D> {
+        Measurements::new(self)
+    }
+}
@@ -0,0 +1,394 @@
+// Copyright 2024, F. Stan
+//
+// Licensed under the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Typestate layer over the SCD30 driver
+//!
+//! [`Scd30<Idle, I, D>`](crate::typestate::Scd30) exposes the configuration of the device.
+//! [`start`](crate::typestate::Scd30::start) consumes it and returns a
+//! [`Scd30<Measuring, I, D>`](crate::typestate::Scd30) with the read operations, and
+//! [`stop`](crate::typestate::Scd30::stop) goes back. Reading before triggering the
+//! continuous measurements, or forcing a recalibration while stopped, does not compile.
+//!
+//!```no_run
+//!use scd30_i2c::typestate::Scd30;
+//!
+//!let mut scd = Scd30::new().unwrap();
+//!scd.set_measurements_interval(2).unwrap();
+//!let mut scd = scd.start().unwrap();
+//!for measurement in scd.measurements().take(10) {
+//!    println!("{:?}", measurement);
+//!}
+//!let scd = scd.stop().unwrap();
+//!```
+//!
+//!```compile_fail
+//!use embedded_hal::delay::DelayNs;
+//!use embedded_hal::i2c::I2c;
+//!use scd30_i2c::scd30;
+//!use scd30_i2c::typestate::Scd30;
+//!
+//!fn read<I: I2c, D: DelayNs>(i2c: I, delay: D) {
+//!    if let Ok(mut scd) = Scd30::stopped(scd30::Scd30::from_i2c(i2c, delay)) {
+//!        // Continuous measurements not started
+//!        let _ = scd.get_measurements();
+//!    }
+//!}
+//!```
+//!
+//!```compile_fail
+//!use embedded_hal::delay::DelayNs;
+//!use embedded_hal::i2c::I2c;
+//!use scd30_i2c::scd30;
+//!use scd30_i2c::typestate::Scd30;
+//!
+//!fn calibrate<I: I2c, D: DelayNs>(i2c: I, delay: D) {
+//!    if let Ok(mut scd) = Scd30::stopped(scd30::Scd30::from_i2c(i2c, delay)) {
+//!        // Forced recalibration while stopped
+//!        let _ = scd.set_force_recalibration_value(450);
+//!    }
+//!}
+//!```
+
+#[cfg(feature = "std")]
+use crate::calibration::{CalibrationError, OffsetReport};
+use crate::config::{ConfigError, Scd30Config, Settings};
+#[cfg(feature = "linux")]
+use crate::linux::{LinuxI2c, LinuxI2cError, StdDelay};
+use crate::measurement::Measurement;
+use crate::scd30::{self, Scd30Error};
+use crate::stream::Measurements;
+use core::error::Error;
+use core::fmt;
+use core::marker::PhantomData;
+use embedded_hal::delay::DelayNs;
+use embedded_hal::i2c::I2c;
+#[cfg(feature = "linux")]
+use std::io;
+#[cfg(feature = "linux")]
+use std::path::Path;
+
+/// Continuous measurements stopped, the device can be configured
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Idle;
+
+/// Continuous measurements running, the device can be read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Measuring;
+
+/// SCD30 device in the mode `M`, [`Idle`] or [`Measuring`], over the
+/// I2C bus `I` and the delay provider `D`
+///
+pub struct Scd30<M, I, D> {
+    device: scd30::Scd30<I, D>,
+    mode: PhantomData<M>,
+}
+
+///
+///Error of a mode transition, keeping the device in the mode
+///it was before the failed transition and the device error
+///
+pub struct TransitionError<T, E> {
+    /// Device, still in the previous mode
+    pub device: T,
+    /// Error from the device
+    pub error: Scd30Error<E>,
+}
+
+///Implementation of debug for TransitionError, without the device
+impl<T, E: fmt::Debug> fmt::Debug for TransitionError<T, E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TransitionError")
+            .field("error", &self.error)
+            .finish_non_exhaustive()
+    }
+}
+
+///Implementation of display for TransitionError
+impl<T, E> fmt::Display for TransitionError<T, E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.error, f)
+    }
+}
+
+///Implementation for Error to TransitionError, the source is the device error
+impl<T, E: Error + 'static> Error for TransitionError<T, E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Linux specific constructors, using i2cdev as I2C bus
+/// and thread sleep as delay provider
+///
+#[cfg(feature = "linux")]
+impl Scd30<Idle, LinuxI2c, StdDelay> {
+    /// Opens the device on bus /dev/i2c-1 and standard address 0x61,
+    /// stopping the continuous measurements if they were running.
+    ///
+    pub fn new() -> Result<Scd30<Idle, LinuxI2c, StdDelay>, Scd30Error<LinuxI2cError>> {
+        Scd30::open(scd30::DEFAULT_BUS, scd30::DEFAULT_ADDRESS)
+    }
+
+    /// Opens the device on the given bus path and address, stopping the
+    /// continuous measurements if they were running.
+    /// If the bus can not be opened, returns the Io error.
+    ///
+    pub fn open<P: AsRef<Path>>(
+        path: P,
+        address: u8,
+    ) -> Result<Scd30<Idle, LinuxI2c, StdDelay>, Scd30Error<LinuxI2cError>> {
+        let device = scd30::Scd30::open(path, address).map_err(io::Error::from)?;
+        Scd30::stopped(device).map_err(|e| e.error)
+    }
+}
+
+impl<I: I2c, D: DelayNs> Scd30<Idle, I, D> {
+    /// Wraps the device, stopping the continuous measurements if they were
+    /// running so it is in a known mode.
+    ///
+    pub fn stopped(
+        mut device: scd30::Scd30<I, D>,
+    ) -> Result<Self, TransitionError<scd30::Scd30<I, D>, I::Error>> {
+        match device.stop_cont_measurements() {
+            Ok(()) => Ok(Scd30::wrap(device)),
+            Err(error) => Err(TransitionError { device, error }),
+        }
+    }
+
+    /// Triggers the continuous measurements without pressure compensation
+    ///
+    pub fn start(self) -> Result<Scd30<Measuring, I, D>, TransitionError<Self, I::Error>> {
+        self.start_with_pressure(0)
+    }
+
+    /// Triggers the continuous measurements with the ambient pressure in mbar,
+    /// 0 disables the compensation.
+    ///
+    pub fn start_with_pressure(
+        mut self,
+        pressure: u16,
+    ) -> Result<Scd30<Measuring, I, D>, TransitionError<Self, I::Error>> {
+        match self
+            .device
+            .trigger_cont_measurements_with_pressure(pressure)
+        {
+            Ok(()) => Ok(Scd30::wrap(self.device)),
+            Err(error) => Err(TransitionError {
+                device: self,
+                error,
+            }),
+        }
+    }
+}
+
+impl<I: I2c, D: DelayNs> Scd30<Measuring, I, D> {
+    /// Wraps a device whose continuous measurements are already running,
+    /// triggering them again with the given ambient pressure in mbar.
+    ///
+    pub fn measuring(
+        mut device: scd30::Scd30<I, D>,
+        pressure: u16,
+    ) -> Result<Self, TransitionError<scd30::Scd30<I, D>, I::Error>> {
+        match device.trigger_cont_measurements_with_pressure(pressure) {
+            Ok(()) => Ok(Scd30::wrap(device)),
+            Err(error) => Err(TransitionError { device, error }),
+        }
+    }
+
+    /// Stops the continuous measurements
+    ///
+    pub fn stop(mut self) -> Result<Scd30<Idle, I, D>, TransitionError<Self, I::Error>> {
+        match self.device.stop_cont_measurements() {
+            Ok(()) => Ok(Scd30::wrap(self.device)),
+            Err(error) => Err(TransitionError {
+                device: self,
+                error,
+            }),
+        }
+    }
+
+    /// Checks if there is a new measurement ready
+    ///
+    pub fn get_data_ready(&mut self) -> Result<bool, Scd30Error<I::Error>> {
+        self.device.get_data_ready()
+    }
+
+    /// Reads the last measurement of the device
+    ///
+    pub fn get_measurements(&mut self) -> Result<Measurement, Scd30Error<I::Error>> {
+        self.device.get_measurements()
+    }
+
+    /// Blocking iterator over the measurements of the device, see
+    /// [`scd30::Scd30::measurements`]
+    ///
+    pub fn measurements(&mut self) -> Measurements<'_, I, D> {
+        self.device.measurements()
+    }
+
+    /// Updates the ambient pressure compensation in mbar, 0 disables it
+    ///
+    pub fn set_ambient_pressure(&mut self, pressure: u16) -> Result<(), Scd30Error<I::Error>> {
+        self.device.set_ambient_pressure(pressure)
+    }
+
+    /// Sets the forced recalibration value in ppm. The device must have been
+    /// measuring for at least 2 minutes in a stable environment, see
+    /// [`scd30::Scd30::force_recalibration`] for the guided procedure
+    ///
+    pub fn set_force_recalibration_value(
+        &mut self,
+        forced_value: u16,
+    ) -> Result<(), Scd30Error<I::Error>> {
+        self.device.set_force_recalibration_value(forced_value)
+    }
+
+    /// Applies the configuration with its forced recalibration value, see
+    /// [`scd30::Scd30::apply_config_with_recalibration`]
+    ///
+    pub fn apply_config_with_recalibration(
+        &mut self,
+        config: &Scd30Config,
+    ) -> Result<Settings, ConfigError<I::Error>> {
+        self.device.apply_config_with_recalibration(config)
+    }
+
+    /// Runs the temperature offset calibration, see
+    /// [`scd30::Scd30::calibrate_temperature_offset`]
+    ///
+    #[cfg(feature = "std")]
+    pub fn calibrate_temperature_offset(
+        &mut self,
+        reference_c: f32,
+        readings: usize,
+    ) -> Result<OffsetReport, CalibrationError<I::Error>> {
+        self.device
+            .calibrate_temperature_offset(reference_c, readings)
+    }
+}
+
+/// Operations available in both modes
+///
+impl<M, I: I2c, D: DelayNs> Scd30<M, I, D> {
+    fn wrap(device: scd30::Scd30<I, D>) -> Self {
+        Scd30 {
+            device,
+            mode: PhantomData,
+        }
+    }
+
+    /// Gives access to the I2C bus
+    ///
+    pub fn i2c_mut(&mut self) -> &mut I {
+        &mut self.device.i2c
+    }
+
+    /// Gives back the underlying driver, without any mode check
+    ///
+    pub fn into_inner(self) -> scd30::Scd30<I, D> {
+        self.device
+    }
+
+    /// Gets the firmware version of the device
+    ///
+    pub fn check_firmware(&mut self) -> Result<u16, Scd30Error<I::Error>> {
+        self.device.check_firmware()
+    }
+
+    /// Gets the measurement interval in seconds
+    ///
+    pub fn get_measurements_interval(&mut self) -> Result<u16, Scd30Error<I::Error>> {
+        self.device.get_measurements_interval()
+    }
+
+    /// Sets the measurement interval in seconds, from 2 to 1800
+    ///
+    pub fn set_measurements_interval(&mut self, seconds: u16) -> Result<(), Scd30Error<I::Error>> {
+        self.device.set_measurements_interval(seconds)
+    }
+
+    /// Gets if the automatic self calibration is active
+    ///
+    pub fn get_self_calibration_status(&mut self) -> Result<bool, Scd30Error<I::Error>> {
+        self.device.get_self_calibration_status()
+    }
+
+    /// Activates or deactivates the automatic self calibration
+    ///
+    pub fn set_self_calibration(&mut self, active: bool) -> Result<(), Scd30Error<I::Error>> {
+        self.device.set_self_calibration(active)
+    }
+
+    /// Gets the altitude in meters
+    ///
+    pub fn get_altitude(&mut self) -> Result<u16, Scd30Error<I::Error>> {
+        self.device.get_altitude()
+    }
+
+    /// Sets the altitude in meters
+    ///
+    pub fn set_altitude(&mut self, altitude: u16) -> Result<(), Scd30Error<I::Error>> {
+        self.device.set_altitude(altitude)
+    }
+
+    /// Gets the temperature offset in ticks of 0.01 Celsius
+    ///
+    pub fn get_temperature_offset(&mut self) -> Result<u16, Scd30Error<I::Error>> {
+        self.device.get_temperature_offset()
+    }
+
+    /// Sets the temperature offset in ticks of 0.01 Celsius
+    ///
+    pub fn set_temperature_offset(&mut self, offset: u16) -> Result<(), Scd30Error<I::Error>> {
+        self.device.set_temperature_offset(offset)
+    }
+
+    /// Gets the temperature offset in Celsius
+    ///
+    pub fn get_temperature_offset_celsius(&mut self) -> Result<f32, Scd30Error<I::Error>> {
+        self.device.get_temperature_offset_celsius()
+    }
+
+    /// Sets the temperature offset in Celsius, rounded to the 0.01 Celsius ticks
+    /// of the device
+    ///
+    pub fn set_temperature_offset_celsius(
+        &mut self,
+        offset_c: f32,
+    ) -> Result<(), Scd30Error<I::Error>> {
+        self.device.set_temperature_offset_celsius(offset_c)
+    }
+
+    /// Gets the last forced recalibration value in ppm
+    ///
+    pub fn get_forced_value(&mut self) -> Result<u16, Scd30Error<I::Error>> {
+        self.device.get_forced_value()
+    }
+
+    /// Reads the configuration of the device, see [`scd30::Scd30::read_config`]
+    ///
+    pub fn read_config(&mut self) -> Result<Scd30Config, Scd30Error<I::Error>> {
+        self.device.read_config()
+    }
+
+    /// Writes the settings of the configuration that differ from the device,
+    /// see [`scd30::Scd30::apply_config`]
+    ///
+    pub fn apply_config(
+        &mut self,
+        config: &Scd30Config,
+    ) -> Result<Settings, ConfigError<I::Error>> {
+        self.device.apply_config(config)
+    }
+
+    /// Restarts the device. The configuration, and the continuous measurements
+    /// if they were running, are kept
+    ///
+    pub fn soft_reset(&mut self) -> Result<(), Scd30Error<I::Error>> {
+        self.device.soft_reset()
+    }
+}
@@ -0,0 +1,340 @@
+#![cfg(feature = "alarms")]
+
+use scd30_i2c::alarms::{AlarmEngine, AlarmError, AlarmEvent, Quantity, Rule};
+use scd30_i2c::measurement::Measurement;
+use scd30_i2c::scd30::Scd30;
+use scd30_i2c::simulator::{Scd30Simulator, SimulatedDelay};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+fn simulated() -> Scd30<Scd30Simulator, SimulatedDelay> {
+    let simulator = Scd30Simulator::new();
+    let delay = simulator.delay();
+    Scd30::from_i2c(simulator, delay)
+}
+
+fn at(seconds: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(seconds)
+}
+
+fn measurement(seconds: u64, co2_ppm: f32, humidity_pct: f32) -> Measurement {
+    Measurement {
+        co2_ppm,
+        temperature_c: 22.0,
+        humidity_pct,
+        timestamp_ms: Some(seconds * 1000),
+    }
+}
+
+/// Feeds CO2 readings every minute, returning the minutes with an event and if it raised
+fn co2_events(engine: &mut AlarmEngine, readings: &[f32]) -> Vec<(u64, bool)> {
+    readings
+        .iter()
+        .enumerate()
+        .flat_map(|(minute, &co2)| {
+            let minute = minute as u64;
+            engine
+                .update(&measurement(minute * 60, co2, 45.0))
+                .into_iter()
+                .map(move |event| (minute, event.is_raised()))
+        })
+        .collect()
+}
+
+#[test]
+fn raises_only_after_the_minimum_duration() {
+    let mut engine = AlarmEngine::new(vec![Rule::above("co2_high", Quantity::Co2, 1400.0)
+        .with_raise_after(Duration::from_secs(5 * 60))])
+    .unwrap();
+    // Short spike, then above the limit for more than 5 minutes
+    let readings = [
+        800.0, 1500.0, 1500.0, 900.0, 1450.0, 1450.0, 1450.0, 1450.0, 1450.0, 1450.0, 1450.0,
+    ];
+    assert_eq!(co2_events(&mut engine, &readings), [(9, true)]);
+    assert_eq!(engine.active(), ["co2_high"]);
+}
+
+#[test]
+fn hysteresis_avoids_flapping() {
+    let mut engine = AlarmEngine::new(vec![
+        Rule::above("co2_high", Quantity::Co2, 1400.0).with_hysteresis(100.0)
+    ])
+    .unwrap();
+    let readings = [1401.0, 1350.0, 1410.0, 1301.0, 1300.0, 1390.0, 1401.0];
+    assert_eq!(
+        co2_events(&mut engine, &readings),
+        [(0, true), (4, false), (6, true)]
+    );
+}
+
+#[test]
+fn clears_only_after_the_minimum_duration() {
+    let mut engine = AlarmEngine::new(vec![Rule::above("co2_high", Quantity::Co2, 1400.0)
+        .with_clear_after(Duration::from_secs(2 * 60))])
+    .unwrap();
+    let readings = [1500.0, 1000.0, 1500.0, 1000.0, 1000.0, 1000.0];
+    assert_eq!(co2_events(&mut engine, &readings), [(0, true), (5, false)]);
+    assert!(engine.active().is_empty());
+}
+
+#[test]
+fn humidity_outside_range() {
+    let mut engine = AlarmEngine::new(vec![Rule::outside(
+        "humidity_out_of_range",
+        Quantity::Humidity,
+        30.0,
+        60.0,
+    )
+    .with_hysteresis(2.0)])
+    .unwrap();
+    let mut events = Vec::new();
+    for (minute, humidity) in [45.0, 61.0, 59.0, 57.0, 29.0, 31.0, 32.0]
+        .into_iter()
+        .enumerate()
+    {
+        events.extend(engine.update(&measurement(minute as u64 * 60, 600.0, humidity)));
+    }
+    assert_eq!(
+        events,
+        [
+            AlarmEvent::Raised {
+                rule: "humidity_out_of_range".to_string(),
+                value: 61.0,
+                time: at(60),
+            },
+            AlarmEvent::Cleared {
+                rule: "humidity_out_of_range".to_string(),
+                value: 57.0,
+                time: at(180),
+            },
+            AlarmEvent::Raised {
+                rule: "humidity_out_of_range".to_string(),
+                value: 29.0,
+                time: at(240),
+            },
+            AlarmEvent::Cleared {
+                rule: "humidity_out_of_range".to_string(),
+                value: 32.0,
+                time: at(360),
+            },
+        ]
+    );
+}
+
+#[test]
+fn rate_of_change_per_minute() {
+    let mut engine = AlarmEngine::new(vec![Rule::rate_of_change(
+        "co2_rising",
+        Quantity::Co2,
+        100.0,
+    )
+    .with_hysteresis(20.0)])
+    .unwrap();
+    // First reading has no rate, then 50, 150, 90 and 70 ppm per minute
+    assert!(engine.update(&measurement(0, 600.0, 45.0)).is_empty());
+    assert!(engine.update(&measurement(60, 650.0, 45.0)).is_empty());
+    // Readings every 30 seconds, 75 ppm in half a minute
+    let raised = engine.update(&measurement(90, 725.0, 45.0));
+    assert_eq!(
+        raised,
+        [AlarmEvent::Raised {
+            rule: "co2_rising".to_string(),
+            value: 150.0,
+            time: at(90),
+        }]
+    );
+    assert!(engine.update(&measurement(150, 815.0, 45.0)).is_empty());
+    let cleared = engine.update(&measurement(210, 745.0, 45.0));
+    assert!(!cleared[0].is_raised());
+}
+
+#[test]
+fn stale_data_is_checked_between_measurements() {
+    let mut engine = AlarmEngine::new(vec![Rule::stale("no_data", Duration::from_secs(60))])
+        .unwrap()
+        .with_start(at(0));
+    assert!(engine.update(&measurement(0, 600.0, 45.0)).is_empty());
+    assert!(engine.check(at(60)).is_empty());
+    let raised = engine.check(at(90));
+    assert_eq!(
+        raised,
+        [AlarmEvent::Raised {
+            rule: "no_data".to_string(),
+            value: 90.0,
+            time: at(90),
+        }]
+    );
+    assert!(engine.check(at(120)).is_empty());
+    let cleared = engine.update(&measurement(130, 600.0, 45.0));
+    assert_eq!(cleared[0].rule(), "no_data");
+    assert!(!cleared[0].is_raised());
+}
+
+#[test]
+fn stale_data_before_the_first_measurement() {
+    let mut engine = AlarmEngine::new(vec![Rule::stale("no_data", Duration::from_secs(60))])
+        .unwrap()
+        .with_start(at(1000));
+    assert!(engine.check(at(1030)).is_empty());
+    let raised = engine.check(at(1090));
+    assert_eq!(
+        raised,
+        [AlarmEvent::Raised {
+            rule: "no_data".to_string(),
+            value: 90.0,
+            time: at(1090),
+        }]
+    );
+    let cleared = engine.update(&measurement(1100, 600.0, 45.0));
+    assert!(!cleared[0].is_raised());
+
+    // By default counted from the creation of the engine
+    let mut engine =
+        AlarmEngine::new(vec![Rule::stale("no_data", Duration::from_secs(60))]).unwrap();
+    assert!(engine.check(SystemTime::now()).is_empty());
+    let raised = engine.check(SystemTime::now() + Duration::from_secs(120));
+    assert_eq!(raised.len(), 1);
+    assert!(raised[0].is_raised());
+}
+
+#[test]
+fn events_are_sent_to_callbacks_and_channels() {
+    let mut engine =
+        AlarmEngine::new(vec![Rule::above("co2_high", Quantity::Co2, 1400.0)]).unwrap();
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let callback_seen = seen.clone();
+    engine.on_event(move |event| callback_seen.lock().unwrap().push(event.to_string()));
+    let receiver = engine.subscribe();
+
+    engine.update(&measurement(0, 1500.0, 45.0));
+    engine.update(&measurement(60, 800.0, 45.0));
+    assert_eq!(
+        *seen.lock().unwrap(),
+        [
+            "Alarm co2_high raised, value 1500",
+            "Alarm co2_high cleared, value 800"
+        ]
+    );
+    let received: Vec<_> = receiver.try_iter().collect();
+    assert_eq!(received.len(), 2);
+    assert!(received[0].is_raised());
+
+    // A dropped receiver does not stop the engine
+    drop(receiver);
+    assert_eq!(engine.update(&measurement(120, 1500.0, 45.0)).len(), 1);
+}
+
+#[test]
+fn invalid_rules_are_rejected() {
+    let invalid = |rule: Rule| match AlarmEngine::new(vec![rule]) {
+        Err(AlarmError::InvalidRule { rule, .. }) => rule,
+        _ => panic!("rule accepted"),
+    };
+    assert_eq!(
+        invalid(Rule::outside("range", Quantity::Humidity, 60.0, 30.0)),
+        "range"
+    );
+    invalid(Rule::outside("range", Quantity::Humidity, 30.0, 60.0).with_hysteresis(20.0));
+    invalid(Rule::above("high", Quantity::Co2, f32::NAN));
+    invalid(Rule::above("high", Quantity::Co2, 1400.0).with_hysteresis(-1.0));
+    invalid(Rule::rate_of_change("rate", Quantity::Co2, 0.0));
+    invalid(Rule::rate_of_change("rate", Quantity::Co2, 100.0).with_hysteresis(100.0));
+    invalid(Rule::rate_of_change("rate", Quantity::Co2, 100.0).with_hysteresis(150.0));
+    assert_eq!(
+        AlarmEngine::new(vec![
+            Rule::above("co2", Quantity::Co2, 1400.0),
+            Rule::below("co2", Quantity::Co2, 400.0),
+        ])
+        .err(),
+        Some(AlarmError::DuplicatedRule("co2".to_string()))
+    );
+}
+
+#[test]
+fn watches_the_measurements_iterator() {
+    let mut scd = simulated();
+    scd.i2c.set_environment(600.0, 22.0, 70.0);
+    scd.trigger_cont_measurements().unwrap();
+    let mut engine = AlarmEngine::new(vec![
+        Rule::above("co2_high", Quantity::Co2, 1400.0),
+        Rule::outside("humidity_out_of_range", Quantity::Humidity, 30.0, 60.0),
+        Rule::below("cold", Quantity::Temperature, 16.0),
+    ])
+    .unwrap();
+    let events: Vec<_> = scd
+        .measurements()
+        .take(3)
+        .flat_map(|measurement| engine.update(&measurement.unwrap()))
+        .collect();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].rule(), "humidity_out_of_range");
+    assert_eq!(engine.active(), ["humidity_out_of_range"]);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn rules_from_toml() {
+    use scd30_i2c::alarms::{AlarmConfig, Condition};
+
+    let config: AlarmConfig = toml::from_str(
+        r#"
+        [[rules]]
+        name = "co2_high"
+        kind = "above"
+        quantity = "co2"
+        limit = 1400
+        hysteresis = 100.0
+        raise_after = 600
+
+        [[rules]]
+        name = "humidity_out_of_range"
+        kind = "outside"
+        quantity = "humidity"
+        low = 30.0
+        high = 60.0
+        clear_after = 1.5
+
+        [[rules]]
+        name = "co2_rising"
+        kind = "rate_of_change"
+        quantity = "co2"
+        per_minute = 100.0
+
+        [[rules]]
+        name = "no_data"
+        kind = "stale"
+        after = 60
+        "#,
+    )
+    .unwrap();
+    assert_eq!(
+        config.rules,
+        [
+            Rule::above("co2_high", Quantity::Co2, 1400.0)
+                .with_hysteresis(100.0)
+                .with_raise_after(Duration::from_secs(600)),
+            Rule::outside("humidity_out_of_range", Quantity::Humidity, 30.0, 60.0)
+                .with_clear_after(Duration::from_millis(1500)),
+            Rule::rate_of_change("co2_rising", Quantity::Co2, 100.0),
+            Rule::stale("no_data", Duration::from_secs(60)),
+        ]
+    );
+    assert!(matches!(
+        config.rules[3].condition,
+        Condition::Stale { after } if after == Duration::from_secs(60)
+    ));
+    let text = toml::to_string(&config).unwrap();
+    assert_eq!(toml::from_str::<AlarmConfig>(&text).unwrap(), config);
+    AlarmEngine::from_config(config).unwrap();
+
+    // Durations can not be negative
+    assert!(toml::from_str::<AlarmConfig>(
+        r#"
+        [[rules]]
+        name = "no_data"
+        kind = "stale"
+        after = -1
+        "#
+    )
+    .is_err());
+}
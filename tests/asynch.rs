@@ -0,0 +1,82 @@
+#![cfg(all(feature = "async", feature = "std"))]
+
+use futures_util::StreamExt;
+use scd30_i2c::asynch::Scd30Async;
+use scd30_i2c::scd30::Scd30Error;
+use scd30_i2c::simulator::{Fault, Scd30Simulator, SimulatedDelay, SIMULATED_FIRMWARE};
+use scd30_i2c::stream::RecoveryPolicy;
+use std::time::Duration;
+
+fn simulated() -> Scd30Async<Scd30Simulator, SimulatedDelay> {
+    let simulator = Scd30Simulator::new();
+    let delay = simulator.delay();
+    Scd30Async::from_i2c(simulator, delay)
+}
+
+#[tokio::test]
+async fn configuration_round_trip() {
+    let mut scd = simulated();
+    assert_eq!(scd.check_firmware().await.unwrap(), SIMULATED_FIRMWARE);
+    scd.set_measurements_interval(10).await.unwrap();
+    assert_eq!(scd.get_measurements_interval().await.unwrap(), 10);
+    scd.set_altitude(300).await.unwrap();
+    assert_eq!(scd.get_altitude().await.unwrap(), 300);
+    scd.set_temperature_offset(80).await.unwrap();
+    assert_eq!(scd.get_temperature_offset().await.unwrap(), 80);
+    scd.set_self_calibration(true).await.unwrap();
+    assert!(scd.get_self_calibration_status().await.unwrap());
+    assert!(matches!(
+        scd.set_measurements_interval(0).await,
+        Err(Scd30Error::OutOfRange { .. })
+    ));
+}
+
+#[tokio::test]
+async fn checksum_error() {
+    let mut scd = simulated();
+    scd.i2c.inject_fault(Fault::BadCrc, 1);
+    assert!(matches!(
+        scd.check_firmware().await,
+        Err(Scd30Error::ChecksumError)
+    ));
+}
+
+#[tokio::test]
+async fn stream_of_measurements() {
+    let mut scd = simulated();
+    scd.i2c.set_environment(900.0, 20.0, 55.0);
+    scd.trigger_cont_measurements().await.unwrap();
+    let measurements: Vec<_> = scd.measurements().take(3).collect().await;
+    assert_eq!(measurements.len(), 3);
+    for measurement in measurements {
+        let measurement = measurement.unwrap();
+        assert!((measurement.co2_ppm - 900.0).abs() < 10.0);
+    }
+    let reads = scd.i2c.commands().iter().filter(|&&c| c == 0x0300).count();
+    assert_eq!(reads, 3);
+}
+
+#[tokio::test]
+async fn stream_stops_on_timeout() {
+    let mut scd = simulated();
+    let measurements: Vec<_> = scd
+        .measurements_with_policy(RecoveryPolicy::Stop)
+        .collect()
+        .await;
+    assert_eq!(measurements.len(), 1);
+    assert!(matches!(measurements[0], Err(Scd30Error::Timeout)));
+    assert!(scd.delay.elapsed() >= Duration::from_secs(5));
+}
+
+#[tokio::test]
+async fn stream_with_poll_interval() {
+    let mut scd = simulated();
+    let measurements: Vec<_> = scd
+        .measurements_with_options(RecoveryPolicy::Stop, Duration::from_secs(1))
+        .collect()
+        .await;
+    assert!(matches!(measurements[0], Err(Scd30Error::Timeout)));
+    // Default interval of 2 seconds, checked every second until the 5 seconds timeout
+    let checks = scd.i2c.commands().iter().filter(|&&c| c == 0x0202).count();
+    assert_eq!(checks, 6);
+}
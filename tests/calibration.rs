@@ -0,0 +1,139 @@
+#![cfg(feature = "std")]
+
+use scd30_i2c::calibration::{CalibrationError, FrcOptions};
+use scd30_i2c::scd30::Scd30;
+use scd30_i2c::simulator::{Fault, Scd30Simulator, SimulatedDelay};
+use std::time::Duration;
+
+fn simulated() -> Scd30<Scd30Simulator, SimulatedDelay> {
+    let simulator = Scd30Simulator::new();
+    let delay = simulator.delay();
+    Scd30::from_i2c(simulator, delay)
+}
+
+#[test]
+fn forced_recalibration_after_settling() {
+    let mut scd = simulated();
+    scd.set_measurements_interval(10).unwrap();
+    let report = scd
+        .force_recalibration(420, &FrcOptions::default())
+        .unwrap();
+
+    assert_eq!(report.reference_ppm, 420);
+    assert_eq!(report.verified_ppm, 420);
+    assert_eq!(report.interval, 2);
+    assert_eq!(report.readings, 60);
+    assert_eq!(report.settled_after, Duration::from_secs(120));
+    assert!((report.co2_before - 650.0).abs() < 5.0);
+    assert!(report.co2_range.1 - report.co2_range.0 <= 20.0);
+    assert!(scd.delay.elapsed() >= Duration::from_secs(120));
+
+    assert!(scd.i2c.is_measuring());
+    assert_eq!(scd.i2c.interval(), 2);
+    assert_eq!(scd.i2c.forced_value(), 420);
+    let measurement = scd.measurements().next().unwrap().unwrap();
+    assert!((measurement.co2_ppm - 420.0).abs() < 10.0);
+}
+
+#[test]
+fn refuses_to_calibrate_if_not_settled() {
+    let mut scd = simulated();
+    let options = FrcOptions {
+        tolerance_ppm: 5.0,
+        timeout: Duration::from_secs(300),
+        ..FrcOptions::default()
+    };
+    match scd.force_recalibration(420, &options) {
+        Err(CalibrationError::NotSettled {
+            readings,
+            spread_ppm,
+        }) => {
+            assert_eq!(readings, 150);
+            assert!(spread_ppm > 5.0);
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+    assert!(!scd.i2c.commands().contains(&0x5204));
+    assert_eq!(scd.i2c.forced_value(), 400);
+}
+
+#[test]
+fn reference_out_of_range_is_not_sent() {
+    let mut scd = simulated();
+    assert!(matches!(
+        scd.force_recalibration(2500, &FrcOptions::default()),
+        Err(CalibrationError::OutOfRange {
+            value: 2500,
+            min: 400,
+            max: 2000
+        })
+    ));
+    assert!(scd.i2c.commands().is_empty());
+}
+
+#[test]
+fn settle_time_under_the_minimum_is_rejected() {
+    let mut scd = simulated();
+    let options = FrcOptions {
+        settle_time: Duration::from_secs(30),
+        ..FrcOptions::default()
+    };
+    assert!(matches!(
+        scd.force_recalibration(420, &options),
+        Err(CalibrationError::SettleTimeTooShort { settle_time })
+            if settle_time == Duration::from_secs(30)
+    ));
+    assert!(scd.i2c.commands().is_empty());
+}
+
+#[test]
+fn device_errors_are_returned() {
+    let mut scd = simulated();
+    scd.i2c.inject_fault(Fault::NackWrite, 1);
+    assert!(matches!(
+        scd.force_recalibration(420, &FrcOptions::default()),
+        Err(CalibrationError::Device(_))
+    ));
+    assert_eq!(scd.i2c.forced_value(), 400);
+}
+
+#[test]
+fn temperature_offset_from_reference() {
+    let mut scd = simulated();
+    // Self heating makes the device read 22.5 Celsius, the room is at 20.3
+    scd.set_temperature_offset(50).unwrap();
+    scd.trigger_cont_measurements().unwrap();
+    let report = scd.calibrate_temperature_offset(20.3, 7).unwrap();
+
+    assert_eq!(report.readings, 7);
+    assert_eq!(report.previous_offset_c, 0.5);
+    assert!((report.measured_c - 22.0).abs() < 0.01);
+    assert_eq!(report.offset_c, 2.2);
+    assert_eq!(scd.i2c.temperature_offset(), 220);
+    let measurement = scd.measurements().next().unwrap().unwrap();
+    assert!((measurement.temperature_c - 20.3).abs() < 0.1);
+}
+
+#[test]
+fn negative_temperature_offset_is_rejected() {
+    let mut scd = simulated();
+    scd.set_temperature_offset(50).unwrap();
+    scd.trigger_cont_measurements().unwrap();
+    match scd.calibrate_temperature_offset(25.0, 3) {
+        Err(CalibrationError::InvalidOffset { offset_c }) => assert!(offset_c < 0.0),
+        other => panic!("unexpected {:?}", other),
+    }
+    assert_eq!(scd.i2c.temperature_offset(), 50);
+}
+
+#[test]
+fn empty_temperature_window_is_rejected() {
+    let mut scd = simulated();
+    scd.trigger_cont_measurements().unwrap();
+    let before = scd.i2c.commands().len();
+    assert!(matches!(
+        scd.calibrate_temperature_offset(21.0, 0),
+        Err(CalibrationError::NoReadings)
+    ));
+    assert_eq!(scd.i2c.commands().len(), before);
+}
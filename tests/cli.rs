@@ -0,0 +1,125 @@
+#![cfg(feature = "cli")]
+
+use clap::{CommandFactory, Parser};
+use scd30_i2c::cli::{parse_address, Action, AscAction, Cli, Command, OffsetAction};
+use scd30_i2c::scd30::DEFAULT_BUS;
+use std::process;
+
+fn parse(args: &[&str]) -> Result<Cli, clap::Error> {
+    Cli::try_parse_from(["scd30"].iter().chain(args))
+}
+
+#[test]
+fn command_is_consistent() {
+    Cli::command().debug_assert();
+}
+
+#[test]
+fn parses_addresses() {
+    assert_eq!(parse_address("0x61"), Ok(0x61));
+    assert_eq!(parse_address("97"), Ok(0x61));
+    assert_eq!(parse_address("0x7F"), Ok(0x7F));
+    assert!(parse_address("0x80").is_err());
+    assert!(parse_address("128").is_err());
+    assert!(parse_address("0xFF").is_err());
+    assert!(parse_address("0x").is_err());
+    assert!(parse_address("0x100").is_err());
+    assert!(parse_address("256").is_err());
+    assert!(parse_address("sixty").is_err());
+}
+
+#[test]
+fn default_bus_and_address() {
+    let cli = parse(&["firmware"]).unwrap();
+    assert_eq!(cli.bus, DEFAULT_BUS);
+    assert_eq!(cli.address, 0x61);
+    assert!(!cli.json);
+    assert!(matches!(cli.command, Command::Firmware));
+}
+
+#[test]
+fn parses_subcommands() {
+    let cli = parse(&[
+        "--bus",
+        "/dev/i2c-3",
+        "--address",
+        "98",
+        "--json",
+        "watch",
+        "--count",
+        "10",
+    ])
+    .unwrap();
+    assert_eq!(cli.bus, "/dev/i2c-3");
+    assert_eq!(cli.address, 98);
+    assert!(cli.json);
+    assert!(matches!(cli.command, Command::Watch { count: Some(10) }));
+
+    let cli = parse(&["interval", "set", "30"]).unwrap();
+    assert!(matches!(
+        cli.command,
+        Command::Interval {
+            action: Action::Set { value: 30 }
+        }
+    ));
+    let cli = parse(&["frc", "get"]).unwrap();
+    assert!(matches!(
+        cli.command,
+        Command::Frc {
+            action: Action::Get
+        }
+    ));
+    let cli = parse(&["asc", "off"]).unwrap();
+    assert!(matches!(
+        cli.command,
+        Command::Asc {
+            action: AscAction::Off
+        }
+    ));
+    let cli = parse(&["temp-offset", "set", "1.5"]).unwrap();
+    assert!(matches!(
+        cli.command,
+        Command::TempOffset {
+            action: OffsetAction::Set { value }
+        } if value == 1.5
+    ));
+    let cli = parse(&["start", "--pressure", "950"]).unwrap();
+    assert!(matches!(cli.command, Command::Start { pressure: 950 }));
+}
+
+#[test]
+fn rejects_invalid_arguments() {
+    assert!(parse(&[]).is_err());
+    assert!(parse(&["--address", "0x1FF", "read"]).is_err());
+    assert!(parse(&["altitude", "set"]).is_err());
+    assert!(parse(&["altitude", "set", "-5"]).is_err());
+    assert!(parse(&["asc", "maybe"]).is_err());
+    assert!(parse(&["calibrate"]).is_err());
+}
+
+#[test]
+fn fails_without_the_bus() {
+    let output = process::Command::new(env!("CARGO_BIN_EXE_scd30"))
+        .args(["--bus", "/dev/i2c-missing", "firmware"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("error: cannot open /dev/i2c-missing"));
+}
+
+#[test]
+fn fails_as_json_with_json_output() {
+    let output = process::Command::new(env!("CARGO_BIN_EXE_scd30"))
+        .args(["--json", "--bus", "/dev/i2c-missing", "firmware"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(output.stderr.is_empty());
+    let error: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(error["ok"], false);
+    assert!(error["error"]
+        .as_str()
+        .unwrap()
+        .starts_with("cannot open /dev/i2c-missing"));
+}
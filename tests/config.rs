@@ -0,0 +1,171 @@
+#![cfg(feature = "std")]
+
+use scd30_i2c::config::{ConfigError, Scd30Config, Setting};
+use scd30_i2c::scd30::{Scd30, Scd30Error};
+use scd30_i2c::simulator::{Fault, Scd30Simulator, SimulatedDelay};
+
+fn simulated() -> Scd30<Scd30Simulator, SimulatedDelay> {
+    let simulator = Scd30Simulator::new();
+    let delay = simulator.delay();
+    Scd30::from_i2c(simulator, delay)
+}
+
+fn writes(scd: &Scd30<Scd30Simulator, SimulatedDelay>, command: u16) -> usize {
+    scd.i2c.commands().iter().filter(|&&c| c == command).count()
+}
+
+#[test]
+fn read_factory_config() {
+    let mut scd = simulated();
+    assert_eq!(
+        scd.read_config().unwrap(),
+        Scd30Config {
+            measurement_interval: Some(2),
+            altitude: Some(0),
+            temperature_offset: Some(0),
+            self_calibration: Some(false),
+            forced_recalibration: None,
+        }
+    );
+}
+
+#[test]
+fn apply_writes_only_changes() {
+    let mut scd = simulated();
+    let config = Scd30Config {
+        measurement_interval: Some(5),
+        altitude: Some(120),
+        temperature_offset: Some(0),
+        ..Scd30Config::default()
+    };
+    let changed = scd.apply_config(&config).unwrap();
+    assert_eq!(
+        changed.iter().collect::<Vec<_>>(),
+        [Setting::MeasurementInterval, Setting::Altitude]
+    );
+    assert!(changed.contains(Setting::Altitude));
+    assert!(!changed.contains(Setting::TemperatureOffset));
+    assert_eq!(changed.len(), 2);
+    assert_eq!(scd.i2c.interval(), 5);
+    assert_eq!(scd.i2c.altitude(), 120);
+    // Offset is only read and self calibration is not set
+    assert_eq!(writes(&scd, 0x5403), 1);
+    assert_eq!(writes(&scd, 0x5306), 0);
+
+    // Applying again does not write anything
+    let before = scd.i2c.commands().len();
+    assert!(scd.apply_config(&config).unwrap().is_empty());
+    assert_eq!(scd.i2c.commands().len() - before, 3);
+}
+
+#[test]
+fn forced_recalibration_needs_to_be_allowed() {
+    let mut scd = simulated();
+    let config = Scd30Config {
+        self_calibration: Some(true),
+        temperature_offset: Some(150),
+        forced_recalibration: Some(450),
+        ..Scd30Config::default()
+    };
+    assert!(matches!(
+        scd.apply_config(&config),
+        Err(ConfigError::RecalibrationNotAllowed)
+    ));
+    assert!(scd.i2c.commands().is_empty());
+
+    scd.trigger_cont_measurements().unwrap();
+    let changed = scd.apply_config_with_recalibration(&config).unwrap();
+    assert_eq!(
+        format!("{:?}", changed),
+        "{TemperatureOffset, SelfCalibration, ForcedRecalibration}"
+    );
+    assert_eq!(scd.i2c.forced_value(), 450);
+    assert_eq!(scd.i2c.temperature_offset(), 150);
+    assert!(scd.i2c.self_calibration());
+
+    // Applying again recalibrates again, the other settings are not written
+    let changed = scd.apply_config_with_recalibration(&config).unwrap();
+    assert_eq!(format!("{:?}", changed), "{ForcedRecalibration}");
+}
+
+#[test]
+fn invalid_config_does_not_change_the_device() {
+    let mut scd = simulated();
+    let config = Scd30Config {
+        altitude: Some(300),
+        forced_recalibration: Some(3000),
+        ..Scd30Config::default()
+    };
+    assert!(matches!(
+        scd.apply_config_with_recalibration(&config),
+        Err(ConfigError::Device(Scd30Error::OutOfRange {
+            value: 3000,
+            ..
+        }))
+    ));
+    assert!(scd.i2c.commands().is_empty());
+}
+
+#[test]
+fn device_errors_are_returned() {
+    let mut scd = simulated();
+    let config = Scd30Config {
+        altitude: Some(300),
+        ..Scd30Config::default()
+    };
+    scd.i2c.inject_fault(Fault::BadCrc, 1);
+    assert!(matches!(
+        scd.apply_config(&config),
+        Err(ConfigError::Device(Scd30Error::ChecksumError))
+    ));
+    assert_eq!(scd.i2c.altitude(), 0);
+    assert_eq!(
+        scd.apply_config(&config).unwrap(),
+        [Setting::Altitude].into_iter().collect()
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn toml_round_trip() {
+    let config = Scd30Config {
+        measurement_interval: Some(10),
+        altitude: Some(540),
+        temperature_offset: Some(200),
+        self_calibration: Some(true),
+        forced_recalibration: None,
+    };
+    let text = toml::to_string(&config).unwrap();
+    assert!(!text.contains("forced_recalibration"));
+    assert_eq!(toml::from_str::<Scd30Config>(&text).unwrap(), config);
+
+    let partial: Scd30Config =
+        toml::from_str("altitude = 120\nforced_recalibration = 420").unwrap();
+    assert_eq!(
+        partial,
+        Scd30Config {
+            altitude: Some(120),
+            forced_recalibration: Some(420),
+            ..Scd30Config::default()
+        }
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn partial_toml_keeps_the_other_settings() {
+    let mut scd = simulated();
+    scd.set_self_calibration(true).unwrap();
+    scd.set_temperature_offset(150).unwrap();
+    scd.set_measurements_interval(10).unwrap();
+
+    let config: Scd30Config = toml::from_str("altitude = 120").unwrap();
+    assert_eq!(
+        scd.apply_config(&config).unwrap(),
+        [Setting::Altitude].into_iter().collect()
+    );
+    assert_eq!(scd.i2c.altitude(), 120);
+    assert!(scd.i2c.self_calibration());
+    assert_eq!(scd.i2c.temperature_offset(), 150);
+    assert_eq!(scd.i2c.interval(), 10);
+}
@@ -0,0 +1,107 @@
+#![cfg(feature = "prometheus")]
+
+use scd30_i2c::exporter::{self, Metrics, MetricsServer};
+use scd30_i2c::scd30::{Scd30, Scd30Error};
+use scd30_i2c::sensirion_frame::FrameError;
+use scd30_i2c::simulator::{Fault, Scd30Simulator, SimulatorError};
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::sync::Arc;
+use std::thread;
+
+#[test]
+fn renders_measurements_metadata_and_errors() {
+    let simulator = Scd30Simulator::new();
+    let delay = simulator.delay();
+    let mut scd = Scd30::from_i2c(simulator, delay);
+    let metrics = Metrics::new("/dev/i2c-3", 0x61);
+
+    scd.set_altitude(120).unwrap();
+    scd.trigger_cont_measurements().unwrap();
+    exporter::read_metadata(&mut scd, &metrics);
+    scd.i2c.inject_fault(Fault::BadCrc, 1);
+    for result in scd.measurements().take(3) {
+        metrics.record(&result);
+    }
+
+    let text = metrics.render();
+    let labels = "bus=\"/dev/i2c-3\",address=\"0x61\"";
+    assert!(text.contains(&format!("scd30_co2_ppm{{{}}} 6", labels)));
+    assert!(text.contains("# TYPE scd30_temperature_celsius gauge"));
+    assert!(text.contains("scd30_relative_humidity_percent{"));
+    assert!(text.contains(&format!(
+        "scd30_measurement_interval_seconds{{{}}} 2",
+        labels
+    )));
+    assert!(text.contains(&format!("scd30_altitude_meters{{{}}} 120", labels)));
+    assert!(text.contains(&format!("scd30_asc_enabled{{{}}} 0", labels)));
+    assert!(text.contains(&format!("scd30_info{{{},firmware=\"3.66\"}} 1", labels)));
+    assert!(text.contains(&format!("scd30_checksum_errors_total{{{}}} 1", labels)));
+    assert!(text.contains(&format!("scd30_communication_errors_total{{{}}} 0", labels)));
+}
+
+#[test]
+fn serves_metrics_over_http() {
+    let metrics = Arc::new(Metrics::new("/dev/i2c-1", 0x61));
+    let server = MetricsServer::bind("127.0.0.1:0", Arc::clone(&metrics)).unwrap();
+    let address = server.local_addr().unwrap();
+    thread::spawn(move || server.run());
+
+    let get = |path: &str| {
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(stream, "GET {} HTTP/1.0\r\nHost: localhost\r\n\r\n", path).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    };
+    let response = get("/metrics");
+    assert!(response.starts_with("HTTP/1.0 200") || response.starts_with("HTTP/1.1 200"));
+    assert!(response.contains("scd30_checksum_errors_total"));
+
+    // Scrapers may add query parameters
+    let response = get("/metrics?name[]=scd30_co2_ppm");
+    assert!(response.starts_with("HTTP/1.0 200") || response.starts_with("HTTP/1.1 200"));
+
+    assert!(get("/").contains(" 404"));
+    assert!(get("/metrics/other").contains(" 404"));
+}
+
+#[test]
+fn failed_reads_drop_the_measurement() {
+    let simulator = Scd30Simulator::new();
+    let delay = simulator.delay();
+    let mut scd = Scd30::from_i2c(simulator, delay);
+    let metrics = Metrics::new("/dev/i2c-1", 0x61);
+    scd.trigger_cont_measurements().unwrap();
+    metrics.record(&scd.measurements().next().unwrap());
+    assert!(metrics.render().contains("scd30_co2_ppm{"));
+
+    scd.i2c.inject_fault(Fault::NackRead, 1);
+    metrics.record(&scd.get_measurements());
+    let text = metrics.render();
+    assert!(!text.contains("scd30_co2_ppm{"));
+    assert!(!text.contains("scd30_temperature_celsius{"));
+    assert!(text.contains("scd30_last_measurement_timestamp_seconds{"));
+    assert!(
+        text.contains("scd30_communication_errors_total{bus=\"/dev/i2c-1\",address=\"0x61\"} 1")
+    );
+}
+
+#[test]
+fn only_device_errors_are_counted() {
+    let metrics = Metrics::new("/dev/i2c-1", 0x61);
+    metrics.record_error::<SimulatorError>(&Scd30Error::OutOfRange {
+        value: 1,
+        min: 2,
+        max: 1800,
+    });
+    metrics.record_error::<SimulatorError>(&Scd30Error::Frame(FrameError::TooShort {
+        expected: 3,
+        actual: 2,
+    }));
+    let text = metrics.render();
+    let labels = "bus=\"/dev/i2c-1\",address=\"0x61\"";
+    assert!(text.contains(&format!("scd30_communication_errors_total{{{}}} 0", labels)));
+    assert!(text.contains(&format!("scd30_checksum_errors_total{{{}}} 0", labels)));
+    assert!(text.contains(&format!("scd30_frame_errors_total{{{}}} 1", labels)));
+}
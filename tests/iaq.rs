@@ -0,0 +1,212 @@
+#![cfg(feature = "iaq")]
+
+use scd30_i2c::iaq::{Band, Classifier, IaqError, IaqScheme, Transition, OUTDOOR_CO2};
+use scd30_i2c::scd30::Scd30;
+use scd30_i2c::simulator::{Scd30Simulator, SimulatedDelay};
+
+fn simulated() -> Scd30<Scd30Simulator, SimulatedDelay> {
+    let simulator = Scd30Simulator::new();
+    let delay = simulator.delay();
+    Scd30::from_i2c(simulator, delay)
+}
+
+fn bands(classifier: &mut Classifier, readings: &[f32]) -> Vec<Option<usize>> {
+    readings
+        .iter()
+        .map(|&co2| {
+            classifier.update(co2);
+            classifier.current_index()
+        })
+        .collect()
+}
+
+#[test]
+fn ready_to_use_schemes() {
+    let traffic_light = IaqScheme::traffic_light();
+    traffic_light.validate().unwrap();
+    assert_eq!(traffic_light, IaqScheme::default());
+    assert_eq!(traffic_light.band_of(420.0), 0);
+    assert_eq!(traffic_light.band_of(1000.0), 1);
+    assert_eq!(traffic_light.band_of(1499.9), 1);
+    assert_eq!(traffic_light.band_of(5000.0), 2);
+
+    let en16798 = IaqScheme::en16798(OUTDOOR_CO2);
+    en16798.validate().unwrap();
+    let names: Vec<_> = en16798.bands.iter().map(|b| b.name.as_str()).collect();
+    assert_eq!(names, ["I", "II", "III", "IV"]);
+    // Concentration above the outdoor one
+    assert_eq!(en16798.band_of(OUTDOOR_CO2 + 549.0), 0);
+    assert_eq!(en16798.band_of(OUTDOOR_CO2 + 550.0), 1);
+    assert_eq!(en16798.band_of(OUTDOOR_CO2 + 1000.0), 2);
+    assert_eq!(en16798.band_of(OUTDOOR_CO2 + 1350.0), 3);
+    assert_eq!(IaqScheme::en16798(500.0).band_of(1000.0), 0);
+}
+
+#[test]
+fn invalid_schemes_are_rejected() {
+    let scheme = |bands: Vec<Band>, hysteresis_ppm: f32| IaqScheme {
+        bands,
+        hysteresis_ppm,
+    };
+    assert_eq!(
+        Classifier::new(scheme(vec![], 50.0)).unwrap_err(),
+        IaqError::NoBands
+    );
+    assert_eq!(
+        scheme(vec![Band::above("a"), Band::above("b")], 50.0).validate(),
+        Err(IaqError::MissingThreshold { band: 0 })
+    );
+    assert_eq!(
+        scheme(
+            vec![
+                Band::below("a", 1000.0),
+                Band::below("b", 800.0),
+                Band::above("c")
+            ],
+            50.0
+        )
+        .validate(),
+        Err(IaqError::UnorderedThreshold { band: 1 })
+    );
+    assert_eq!(
+        scheme(vec![Band::below("a", 1000.0)], 50.0).validate(),
+        Err(IaqError::BoundedLastBand)
+    );
+    assert!(matches!(
+        scheme(vec![Band::above("a")], -1.0).validate(),
+        Err(IaqError::InvalidHysteresis(_))
+    ));
+    assert!(matches!(
+        scheme(vec![Band::above("a")], f32::NAN).validate(),
+        Err(IaqError::InvalidHysteresis(_))
+    ));
+    // A single open band is valid
+    scheme(vec![Band::above("a")], 0.0).validate().unwrap();
+}
+
+#[test]
+fn first_reading_is_a_transition() {
+    let mut classifier = Classifier::new(IaqScheme::traffic_light()).unwrap();
+    assert!(classifier.current().is_none());
+    let transition = classifier.update(1200.0).unwrap();
+    assert_eq!(
+        transition,
+        Transition {
+            from: None,
+            to: 1,
+            name: "amber".to_string(),
+            advice: Some("Ventilate soon".to_string()),
+            co2_ppm: 1200.0,
+        }
+    );
+    assert!(transition.is_worse());
+    assert_eq!(classifier.current().unwrap().name, "amber");
+    assert!(classifier.update(1300.0).is_none());
+}
+
+#[test]
+fn hysteresis_avoids_flapping() {
+    let mut classifier = Classifier::new(IaqScheme::traffic_light()).unwrap();
+    // Around the 1000 ppm threshold with 50 ppm of hysteresis
+    let readings = [990.0, 1001.0, 990.0, 960.0, 1010.0, 951.0, 949.0, 999.0];
+    assert_eq!(
+        bands(&mut classifier, &readings),
+        [
+            Some(0),
+            Some(1),
+            Some(1),
+            Some(1),
+            Some(1),
+            Some(1),
+            Some(0),
+            Some(0)
+        ]
+    );
+}
+
+#[test]
+fn jumps_several_bands() {
+    let mut classifier = Classifier::new(IaqScheme::traffic_light()).unwrap();
+    classifier.update(500.0);
+    let up = classifier.update(2000.0).unwrap();
+    assert_eq!((up.from, up.to), (Some(0), 2));
+    assert_eq!(up.advice.as_deref(), Some("Open the window"));
+    // Falls only to amber, still within the hysteresis of green
+    let down = classifier.update(980.0).unwrap();
+    assert_eq!((down.from, down.to), (Some(2), 1));
+    assert!(!down.is_worse());
+    let down = classifier.update(600.0).unwrap();
+    assert_eq!((down.from, down.to), (Some(1), 0));
+    assert_eq!(down.advice, None);
+}
+
+#[test]
+fn not_a_number_is_ignored_and_reset_starts_again() {
+    let mut classifier = Classifier::new(IaqScheme::traffic_light()).unwrap();
+    classifier.update(1600.0);
+    assert!(classifier.update(f32::NAN).is_none());
+    assert_eq!(classifier.current_index(), Some(2));
+    classifier.reset();
+    assert_eq!(classifier.update(1600.0).unwrap().from, None);
+}
+
+#[test]
+fn classifies_the_measurements_iterator() {
+    let mut scd = simulated();
+    scd.i2c.set_environment(600.0, 22.0, 40.0);
+    scd.trigger_cont_measurements().unwrap();
+    let classifier = Classifier::new(IaqScheme::traffic_light()).unwrap();
+    let mut readings = scd.measurements().classified(classifier);
+
+    let (_, transition) = readings.next().unwrap().unwrap();
+    assert_eq!(transition.unwrap().name, "green");
+    let (_, transition) = readings.next().unwrap().unwrap();
+    assert!(transition.is_none());
+
+    readings
+        .measurements_mut()
+        .device_mut()
+        .i2c
+        .set_environment(1800.0, 22.0, 40.0);
+    // Next sample may have been taken before the change
+    let transition = readings
+        .by_ref()
+        .take(2)
+        .find_map(|reading| reading.unwrap().1)
+        .unwrap();
+    assert_eq!(transition.name, "red");
+    assert!(transition.is_worse());
+    assert_eq!(readings.classifier().current().unwrap().name, "red");
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn scheme_from_toml() {
+    let scheme: IaqScheme = toml::from_str(
+        r#"
+        [[bands]]
+        name = "good"
+        upper_ppm = 800.0
+
+        [[bands]]
+        name = "poor"
+        advice = "Open the window"
+        "#,
+    )
+    .unwrap();
+    assert_eq!(
+        scheme,
+        IaqScheme {
+            bands: vec![
+                Band::below("good", 800.0),
+                Band::above("poor").with_advice("Open the window"),
+            ],
+            hysteresis_ppm: 50.0,
+        }
+    );
+    let text = toml::to_string(&IaqScheme::en16798(400.0)).unwrap();
+    assert_eq!(
+        toml::from_str::<IaqScheme>(&text).unwrap(),
+        IaqScheme::en16798(400.0)
+    );
+}
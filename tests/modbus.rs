@@ -0,0 +1,167 @@
+#![cfg(feature = "std")]
+
+use scd30_i2c::modbus::{crc16, ModbusError, Scd30Modbus};
+use scd30_i2c::scd30::{Phase, Scd30Error, Timing, BOOT_TIME};
+use scd30_i2c::simulator::{Scd30Simulator, SimulatedDelay};
+use serialport::{SerialPort, TTYPort};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::thread;
+use std::time::Duration;
+
+/// Minimal SCD30 Modbus responder running on the master side of a pseudo-terminal
+fn spawn_device(mut port: TTYPort, corrupt_crc: bool) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let mut registers: HashMap<u16, u16> = HashMap::new();
+        registers.insert(0x0020, 0x0342);
+        registers.insert(0x0025, 2);
+        registers.insert(0x0027, 1);
+        let measurement = [800.0f32, 21.5, 48.0];
+        for (i, value) in measurement.iter().enumerate() {
+            let bits = value.to_bits();
+            registers.insert(0x0028 + 2 * i as u16, (bits >> 16) as u16);
+            registers.insert(0x0029 + 2 * i as u16, bits as u16);
+        }
+        let mut request = [0u8; 8];
+        while port.read_exact(&mut request).is_ok() {
+            assert_eq!(crc16(&request[..6]).to_le_bytes(), [request[6], request[7]]);
+            let function = request[1];
+            let register = u16::from_be_bytes([request[2], request[3]]);
+            let value = u16::from_be_bytes([request[4], request[5]]);
+            let mut response = vec![request[0]];
+            match function {
+                0x03 => {
+                    if !registers.contains_key(&register) {
+                        response.extend_from_slice(&[function | 0x80, 0x02]);
+                    } else {
+                        response.extend_from_slice(&[function, (value * 2) as u8]);
+                        for r in register..register + value {
+                            let word = registers.get(&r).copied().unwrap_or(0);
+                            response.extend_from_slice(&word.to_be_bytes());
+                        }
+                    }
+                }
+                0x06 => {
+                    registers.insert(register, value);
+                    response.extend_from_slice(&request[1..6]);
+                }
+                _ => response.extend_from_slice(&[function | 0x80, 0x01]),
+            }
+            let mut crc = crc16(&response).to_le_bytes();
+            if corrupt_crc {
+                crc[0] ^= 0xFF;
+            }
+            response.extend_from_slice(&crc);
+            port.write_all(&response).unwrap();
+        }
+    })
+}
+
+fn connect(corrupt_crc: bool) -> Scd30Modbus<TTYPort, SimulatedDelay> {
+    let (mut master, mut slave) = TTYPort::pair().unwrap();
+    master.set_timeout(Duration::from_secs(1)).unwrap();
+    slave.set_timeout(Duration::from_secs(1)).unwrap();
+    spawn_device(master, corrupt_crc);
+    Scd30Modbus::from_port(slave, Scd30Simulator::new().delay())
+}
+
+#[test]
+fn crc16_reference() {
+    assert_eq!(crc16(b"123456789"), 0x4B37);
+    assert_eq!(
+        crc16(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x0A]).to_le_bytes(),
+        [0xC5, 0xCD]
+    );
+}
+
+#[test]
+fn read_firmware_and_measurements() {
+    let mut scd = connect(false);
+    assert_eq!(scd.check_firmware().unwrap(), 0x0342);
+    assert!(scd.get_data_ready().unwrap());
+    let measurement = scd.get_measurements().unwrap();
+    assert_eq!(measurement.co2_ppm, 800.0);
+    assert_eq!(measurement.temperature_c, 21.5);
+    assert_eq!(measurement.humidity_pct, 48.0);
+}
+
+#[test]
+fn write_and_read_back() {
+    let mut scd = connect(false);
+    scd.trigger_cont_measurements_with_pressure(980).unwrap();
+    scd.set_measurements_interval(5).unwrap();
+    assert_eq!(scd.get_measurements_interval().unwrap(), 5);
+    scd.set_altitude(250).unwrap();
+    assert_eq!(scd.get_altitude().unwrap(), 250);
+    scd.set_temperature_offset(120).unwrap();
+    assert_eq!(scd.get_temperature_offset().unwrap(), 120);
+    scd.set_self_calibration(true).unwrap();
+    assert!(scd.get_self_calibration_status().unwrap());
+    scd.set_force_recalibration_value(420).unwrap();
+    assert_eq!(scd.get_forced_value().unwrap(), 420);
+    scd.stop_cont_measurements().unwrap();
+}
+
+#[test]
+fn soft_reset_waits_for_boot() {
+    let mut scd = connect(false);
+    scd.soft_reset().unwrap();
+    assert_eq!(scd.delay.elapsed(), BOOT_TIME);
+
+    let mut scd = connect(false).with_timing(Timing {
+        boot_time: Duration::from_millis(200),
+        ..Timing::default()
+    });
+    scd.soft_reset().unwrap();
+    assert_eq!(scd.delay.elapsed(), Duration::from_millis(200));
+    assert_eq!(scd.check_firmware().unwrap(), 0x0342);
+}
+
+#[test]
+fn exception_response() {
+    let mut scd = connect(false);
+    match scd.get_altitude() {
+        Err(Scd30Error::ComunicationError {
+            command: 0x0038,
+            phase: Phase::Read,
+            source: ModbusError::Exception { function, code },
+        }) => assert_eq!((function, code), (0x03, 0x02)),
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn checksum_error() {
+    let mut scd = connect(true);
+    assert!(matches!(
+        scd.check_firmware(),
+        Err(Scd30Error::ChecksumError)
+    ));
+}
+
+#[test]
+fn out_of_range_is_not_sent() {
+    let mut scd = connect(false);
+    assert!(matches!(
+        scd.set_measurements_interval(1),
+        Err(Scd30Error::OutOfRange { .. })
+    ));
+    assert_eq!(scd.get_measurements_interval().unwrap(), 2);
+}
+
+#[test]
+fn temperature_offset_in_celsius() {
+    let mut scd = connect(false);
+    scd.set_temperature_offset_celsius(1.5).unwrap();
+    assert_eq!(scd.get_temperature_offset().unwrap(), 150);
+    assert_eq!(scd.get_temperature_offset_celsius().unwrap(), 1.5);
+    assert!(matches!(
+        scd.set_temperature_offset_celsius(-0.5),
+        Err(Scd30Error::OutOfRange {
+            value: -50,
+            min: 0,
+            max: 65535
+        })
+    ));
+    assert_eq!(scd.get_temperature_offset().unwrap(), 150);
+}
@@ -0,0 +1,308 @@
+#![cfg(feature = "mqtt")]
+
+use embedded_hal::delay::DelayNs;
+use rumqttc::{Client, Event, MqttOptions, Packet, QoS};
+use scd30_i2c::measurement::Measurement;
+use scd30_i2c::mqtt::{self, Command, MqttConfig};
+use scd30_i2c::scd30::{Scd30, Scd30Error};
+use scd30_i2c::simulator::{Fault, Scd30Simulator, SimulatedDelay};
+use serde_json::Value;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+#[test]
+fn topics() {
+    let config = MqttConfig::new("localhost", "office");
+    assert_eq!(config.state_topic(), "scd30/office/state");
+    assert_eq!(config.availability_topic(), "scd30/office/availability");
+    assert_eq!(config.command_topic("frc"), "scd30/office/set/frc");
+    assert_eq!(config.error_topic(), "scd30/office/error");
+}
+
+#[test]
+fn home_assistant_discovery() {
+    let config = MqttConfig::new("localhost", "office");
+    let discovery = config.discovery();
+    let topics: Vec<&str> = discovery.iter().map(|(topic, _)| topic.as_str()).collect();
+    assert_eq!(
+        topics,
+        [
+            "homeassistant/sensor/office/co2/config",
+            "homeassistant/sensor/office/temperature/config",
+            "homeassistant/sensor/office/humidity/config",
+        ]
+    );
+    let co2 = &discovery[0].1;
+    assert_eq!(co2["device_class"], "carbon_dioxide");
+    assert_eq!(co2["unit_of_measurement"], "ppm");
+    assert_eq!(co2["state_topic"], "scd30/office/state");
+    assert_eq!(co2["value_template"], "{{ value_json.co2_ppm }}");
+    assert_eq!(co2["availability_topic"], "scd30/office/availability");
+    assert_eq!(co2["unique_id"], "office_co2");
+    assert_eq!(co2["device"]["model"], "SCD30");
+    assert_eq!(discovery[1].1["device"], co2["device"]);
+}
+
+#[test]
+fn state_payload() {
+    let measurement = Measurement {
+        co2_ppm: 650.0,
+        temperature_c: 22.5,
+        humidity_pct: 40.0,
+        timestamp_ms: Some(1_700_000_000_000),
+    };
+    let payload = mqtt::state_payload(&measurement);
+    assert_eq!(payload["co2_ppm"], 650.0);
+    assert_eq!(payload["temperature_c"], 22.5);
+    assert_eq!(payload["humidity_pct"], 40.0);
+    assert_eq!(payload["timestamp"], 1_700_000_000.0);
+}
+
+#[test]
+fn error_payload() {
+    let error: Scd30Error = Scd30Error::Timeout;
+    let payload = mqtt::error_payload(&error, UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+    assert_eq!(payload["error"], "Timeout waiting for data ready");
+    assert_eq!(payload["timestamp"], 1_700_000_000.0);
+}
+
+#[test]
+fn parse_commands() {
+    let config = MqttConfig::new("localhost", "office");
+    assert_eq!(
+        config.parse_command("scd30/office/set/interval", b"10"),
+        Some(Ok(Command::SetInterval(10)))
+    );
+    assert_eq!(
+        config.parse_command("scd30/office/set/altitude", b" 250\n"),
+        Some(Ok(Command::SetAltitude(250)))
+    );
+    assert_eq!(
+        config.parse_command("scd30/office/set/frc", b"420"),
+        Some(Ok(Command::SetForcedRecalibration(420)))
+    );
+    assert!(matches!(
+        config.parse_command("scd30/office/set/frc", b"abc"),
+        Some(Err(_))
+    ));
+    assert!(matches!(
+        config.parse_command("scd30/office/set/reboot", b"1"),
+        Some(Err(_))
+    ));
+    assert_eq!(config.parse_command("scd30/kitchen/set/frc", b"420"), None);
+}
+
+#[test]
+fn apply_commands() {
+    let simulator = Scd30Simulator::new();
+    let delay = simulator.delay();
+    let mut scd = Scd30::from_i2c(simulator, delay);
+    Command::SetInterval(5).apply(&mut scd).unwrap();
+    Command::SetAltitude(300).apply(&mut scd).unwrap();
+    assert_eq!(scd.i2c.interval(), 5);
+    assert_eq!(scd.i2c.altitude(), 300);
+    assert!(Command::SetInterval(1).apply(&mut scd).is_err());
+}
+
+/// Simulated delay that also sleeps a twentieth of the time, so the
+/// publisher does not spin while the broker delivers the messages
+struct Paced(SimulatedDelay);
+
+impl DelayNs for Paced {
+    fn delay_ns(&mut self, ns: u32) {
+        self.0.delay_ns(ns);
+        thread::sleep(Duration::from_nanos(u64::from(ns) / 20));
+    }
+}
+
+#[test]
+#[ignore = "needs a MQTT broker, like mosquitto, on localhost:1883"]
+fn publishes_on_a_local_broker() {
+    let node_id = format!("test_{}", process::id());
+    let config = MqttConfig::new("localhost", &node_id);
+    let options = MqttOptions::new(format!("{}-observer", node_id), "localhost", 1883);
+    let (observer, mut connection) = Client::new(options, 16);
+    observer
+        .subscribe(format!("{}/#", config.base_topic), QoS::AtLeastOnce)
+        .unwrap();
+    let deadline = Instant::now() + Duration::from_secs(30);
+    let mut next_message = || loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        match connection.recv_timeout(remaining) {
+            Ok(Ok(Event::Incoming(Packet::Publish(message)))) => {
+                let payload = String::from_utf8(message.payload.to_vec()).unwrap();
+                return Some((message.topic, payload));
+            }
+            Ok(Ok(Event::Incoming(Packet::SubAck(_)))) => return None,
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => panic!("broker connection failed: {}", e),
+            Err(_) => panic!("timeout waiting for the publisher"),
+        }
+    };
+    // Subscribed before the publisher starts
+    while next_message().is_some() {}
+
+    let publisher_config = config.clone();
+    thread::spawn(move || {
+        let mut simulator = Scd30Simulator::new();
+        let delay = Paced(simulator.delay());
+        simulator.set_environment(800.0, 21.0, 45.0);
+        // The first read of the publisher fails
+        simulator.inject_fault(Fault::BadCrc, 1);
+        let mut scd = Scd30::from_i2c(simulator, delay);
+        scd.trigger_cont_measurements().unwrap();
+        mqtt::publish(&mut scd, &publisher_config).unwrap();
+    });
+
+    let (mut online, mut error, mut state, mut result) = (false, None, None, None);
+    while !online || error.is_none() || state.is_none() || result.is_none() {
+        let Some((topic, payload)) = next_message() else {
+            continue;
+        };
+        if topic == config.availability_topic() {
+            online |= payload == mqtt::ONLINE;
+        } else if topic == config.error_topic() {
+            error = Some(serde_json::from_str::<Value>(&payload).unwrap());
+        } else if topic == config.state_topic() {
+            if state.is_none() {
+                observer
+                    .publish(
+                        config.command_topic("interval"),
+                        QoS::AtLeastOnce,
+                        false,
+                        "4",
+                    )
+                    .unwrap();
+            }
+            state = Some(serde_json::from_str::<Value>(&payload).unwrap());
+        } else if topic == config.result_topic() {
+            result = Some(serde_json::from_str::<Value>(&payload).unwrap());
+        }
+    }
+    assert!(error.unwrap()["error"]
+        .as_str()
+        .unwrap()
+        .contains("Checksum"));
+    assert!((state.unwrap()["co2_ppm"].as_f64().unwrap() - 800.0).abs() < 10.0);
+    assert_eq!(result.unwrap()["ok"], true);
+}
+
+/// Reads one MQTT packet, returning its first byte and its body
+fn read_packet(stream: &mut TcpStream) -> io::Result<(u8, Vec<u8>)> {
+    let mut header = [0; 1];
+    stream.read_exact(&mut header)?;
+    let (mut length, mut shift) = (0usize, 0);
+    loop {
+        let mut byte = [0; 1];
+        stream.read_exact(&mut byte)?;
+        length |= usize::from(byte[0] & 0x7F) << shift;
+        shift += 7;
+        if byte[0] & 0x80 == 0 {
+            break;
+        }
+    }
+    let mut body = vec![0; length];
+    stream.read_exact(&mut body)?;
+    Ok((header[0], body))
+}
+
+/// Minimal MQTT broker session: acknowledges the connection, the subscriptions and
+/// the publishes of the client, returning the topic and payload of each publish
+/// until `done` returns true or the deadline passes
+fn serve(
+    listener: &TcpListener,
+    deadline: Instant,
+    mut done: impl FnMut(&str, &str) -> bool,
+) -> Vec<(String, String)> {
+    listener.set_nonblocking(true).unwrap();
+    let mut stream = loop {
+        match listener.accept() {
+            Ok((stream, _)) => break stream,
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                assert!(Instant::now() < deadline, "the publisher did not connect");
+                thread::sleep(Duration::from_millis(10));
+            }
+            Err(e) => panic!("accept failed: {}", e),
+        }
+    };
+    stream.set_nonblocking(false).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_millis(100)))
+        .unwrap();
+    let mut published = Vec::new();
+    while Instant::now() < deadline {
+        let (header, body) = match read_packet(&mut stream) {
+            Ok(packet) => packet,
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => continue,
+            Err(e) if e.kind() == io::ErrorKind::TimedOut => continue,
+            Err(e) => panic!("publisher connection failed: {}", e),
+        };
+        match header >> 4 {
+            // CONNECT
+            1 => stream.write_all(&[0x20, 2, 0, 0]).unwrap(),
+            // PUBLISH, with QoS 1
+            3 => {
+                let topic_len = usize::from(u16::from_be_bytes([body[0], body[1]]));
+                let topic = String::from_utf8(body[2..2 + topic_len].to_vec()).unwrap();
+                let id = &body[2 + topic_len..4 + topic_len];
+                let payload = String::from_utf8(body[4 + topic_len..].to_vec()).unwrap();
+                stream.write_all(&[0x40, 2, id[0], id[1]]).unwrap();
+                let finished = done(&topic, &payload);
+                published.push((topic, payload));
+                if finished {
+                    return published;
+                }
+            }
+            // SUBSCRIBE
+            8 => stream.write_all(&[0x90, 3, body[0], body[1], 1]).unwrap(),
+            // PINGREQ
+            12 => stream.write_all(&[0xD0, 0]).unwrap(),
+            _ => {}
+        }
+    }
+    panic!(
+        "timeout waiting for the publisher, received {:?}",
+        published
+    );
+}
+
+#[test]
+fn announces_again_after_the_queue_filled_up_during_an_outage() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let mut config = MqttConfig::new("127.0.0.1", "outage");
+    config.port = port;
+
+    let publisher_config = config.clone();
+    thread::spawn(move || {
+        let simulator = Scd30Simulator::new();
+        let delay = Paced(simulator.delay());
+        let mut scd = Scd30::from_i2c(simulator, delay);
+        scd.trigger_cont_measurements().unwrap();
+        let _ = mqtt::publish(&mut scd, &publisher_config);
+    });
+
+    let deadline = Instant::now() + Duration::from_secs(30);
+    let state_topic = config.state_topic();
+    let availability_topic = config.availability_topic();
+    let first = serve(&listener, deadline, |topic, _| topic == state_topic);
+    assert!(first
+        .iter()
+        .any(|(topic, payload)| *topic == availability_topic && payload == mqtt::ONLINE));
+
+    // Broker down long enough for the publisher to fill the request channel,
+    // a measurement every 100 ms and room for 16 requests
+    drop(listener);
+    thread::sleep(Duration::from_secs(3));
+    let listener = TcpListener::bind(("127.0.0.1", port)).unwrap();
+
+    let mut online = false;
+    let second = serve(&listener, deadline, |topic, payload| {
+        online |= topic == availability_topic && payload == mqtt::ONLINE;
+        online && topic == state_topic
+    });
+    assert!(second.len() > 16);
+}
@@ -0,0 +1,199 @@
+#![cfg(feature = "std")]
+
+use scd30_i2c::measurement::Measurement;
+use scd30_i2c::psychrometrics::{
+    absolute_humidity_g_m3, dew_point_c, heat_index_c, humidity_ratio_g_kg,
+    saturation_vapour_pressure_hpa, vapour_pressure_deficit_kpa, vapour_pressure_hpa,
+};
+
+fn assert_close(actual: f32, expected: f32, tolerance: f32) {
+    assert!(
+        (actual - expected).abs() <= tolerance,
+        "{} is not within {} of {}",
+        actual,
+        tolerance,
+        expected
+    );
+}
+
+fn to_celsius(fahrenheit: f32) -> f32 {
+    (fahrenheit - 32.0) * 5.0 / 9.0
+}
+
+#[test]
+fn saturation_vapour_pressure_matches_reference_table() {
+    // Temperature in Celsius, saturation vapour pressure over water in hPa (WMO)
+    let table = [
+        (-20.0, 1.2540),
+        (0.0, 6.1121),
+        (10.0, 12.281),
+        (20.0, 23.388),
+        (30.0, 42.455),
+        (40.0, 73.814),
+        (50.0, 123.49),
+    ];
+    for (temperature, expected) in table {
+        // Within 0.4 %
+        assert_close(
+            saturation_vapour_pressure_hpa(temperature),
+            expected,
+            expected * 0.004,
+        );
+    }
+    assert_close(vapour_pressure_hpa(20.0, 50.0), 23.388 / 2.0, 0.05);
+}
+
+#[test]
+fn dew_point_matches_reference_table() {
+    // Temperature in Celsius, relative humidity in %, dew point in Celsius
+    let table = [
+        (20.0, 50.0, 9.3),
+        (25.0, 60.0, 16.7),
+        (30.0, 80.0, 26.2),
+        (10.0, 90.0, 8.4),
+        (0.0, 70.0, -4.8),
+        (35.0, 20.0, 8.8),
+    ];
+    for (temperature, humidity, expected) in table {
+        assert_close(dew_point_c(temperature, humidity), expected, 0.1);
+    }
+    // Saturated air condenses at its own temperature
+    assert_close(dew_point_c(22.5, 100.0), 22.5, 0.001);
+    assert!(dew_point_c(22.5, 0.0).is_nan());
+}
+
+#[test]
+fn absolute_humidity_matches_reference_table() {
+    // Temperature in Celsius, relative humidity in %, water vapour density in g/m³
+    let table = [
+        (0.0, 100.0, 4.85),
+        (10.0, 100.0, 9.40),
+        (20.0, 50.0, 8.65),
+        (30.0, 80.0, 24.27),
+        (40.0, 100.0, 51.1),
+    ];
+    for (temperature, humidity, expected) in table {
+        assert_close(
+            absolute_humidity_g_m3(temperature, humidity),
+            expected,
+            expected * 0.005,
+        );
+    }
+}
+
+#[test]
+fn humidity_ratio_matches_ashrae_table() {
+    // Temperature in Celsius, humidity ratio of saturated air at 101.325 kPa in g/kg
+    let table = [
+        (0.0, 3.789),
+        (10.0, 7.661),
+        (20.0, 14.758),
+        (30.0, 27.329),
+        (40.0, 49.141),
+    ];
+    for (temperature, expected) in table {
+        assert_close(
+            humidity_ratio_g_kg(temperature, 100.0, 1013.25),
+            expected,
+            expected * 0.01,
+        );
+    }
+}
+
+#[test]
+fn humidity_ratio_grows_with_altitude() {
+    // Same air at sea level and around 1500 m
+    let sea_level = humidity_ratio_g_kg(20.0, 50.0, 1013.25);
+    let mountain = humidity_ratio_g_kg(20.0, 50.0, 850.0);
+    assert_close(sea_level, 7.26, 0.07);
+    assert_close(mountain / sea_level, 1013.25 / 850.0, 0.01);
+    assert_eq!(humidity_ratio_g_kg(20.0, 0.0, 1013.25), 0.0);
+}
+
+#[test]
+fn vapour_pressure_deficit_matches_reference_table() {
+    // Temperature in Celsius, relative humidity in %, deficit in kPa
+    let table = [
+        (20.0, 70.0, 0.70),
+        (25.0, 60.0, 1.27),
+        (30.0, 50.0, 2.12),
+        (24.0, 80.0, 0.60),
+    ];
+    for (temperature, humidity, expected) in table {
+        assert_close(
+            vapour_pressure_deficit_kpa(temperature, humidity),
+            expected,
+            0.01,
+        );
+    }
+    assert_eq!(vapour_pressure_deficit_kpa(25.0, 100.0), 0.0);
+}
+
+#[test]
+fn heat_index_matches_nws_table() {
+    // Temperature in Fahrenheit, relative humidity in %, heat index in Fahrenheit
+    let table = [
+        (80.0, 40.0, 80.0),
+        (90.0, 50.0, 95.0),
+        (100.0, 40.0, 109.0),
+        (86.0, 90.0, 105.0),
+        (96.0, 65.0, 121.0),
+        (84.0, 70.0, 90.0),
+        (104.0, 55.0, 137.0),
+    ];
+    for (temperature, humidity, expected) in table {
+        // Within 1 Fahrenheit
+        assert_close(
+            heat_index_c(to_celsius(temperature), humidity),
+            to_celsius(expected),
+            5.0 / 9.0,
+        );
+    }
+}
+
+#[test]
+fn heat_index_adjustments() {
+    // Dry air below 13 % and humid air above 85 %, NWS algorithm in double precision
+    assert_close(
+        heat_index_c(to_celsius(100.0), 10.0),
+        to_celsius(94.12),
+        0.05,
+    );
+    assert_close(
+        heat_index_c(to_celsius(85.0), 90.0),
+        to_celsius(101.78),
+        0.05,
+    );
+    // Mild conditions stay close to the temperature
+    assert_close(heat_index_c(21.0, 50.0), 21.0, 1.0);
+}
+
+#[test]
+fn derives_all_quantities_from_a_measurement() {
+    let measurement = Measurement {
+        co2_ppm: 600.0,
+        temperature_c: 25.0,
+        humidity_pct: 60.0,
+        timestamp_ms: None,
+    };
+    let derived = measurement.psychrometrics();
+    assert_eq!(derived.dew_point_c, dew_point_c(25.0, 60.0));
+    assert_eq!(derived.vapour_pressure_hpa, vapour_pressure_hpa(25.0, 60.0));
+    assert_eq!(
+        derived.absolute_humidity_g_m3,
+        absolute_humidity_g_m3(25.0, 60.0)
+    );
+    assert_eq!(
+        derived.humidity_ratio_g_kg,
+        humidity_ratio_g_kg(25.0, 60.0, 1013.25)
+    );
+    assert_eq!(
+        derived.vapour_pressure_deficit_kpa,
+        vapour_pressure_deficit_kpa(25.0, 60.0)
+    );
+    assert_eq!(derived.heat_index_c, heat_index_c(25.0, 60.0));
+
+    let at_altitude = measurement.psychrometrics_at(900.0);
+    assert!(at_altitude.humidity_ratio_g_kg > derived.humidity_ratio_g_kg);
+    assert_eq!(at_altitude.dew_point_c, derived.dew_point_c);
+}
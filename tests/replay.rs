@@ -0,0 +1,106 @@
+#![cfg(feature = "std")]
+
+use embedded_hal::i2c::{Error, ErrorKind, NoAcknowledgeSource};
+use scd30_i2c::replay::{Direction, Frame, NoDelay, Recorder, Replay, ReplayError};
+use scd30_i2c::scd30::{Phase, Scd30, Scd30Error};
+use scd30_i2c::simulator::{Fault, Scd30Simulator};
+
+/// Records a short session with the simulator, with a bad checksum and a NACK
+fn record() -> String {
+    let simulator = Scd30Simulator::new();
+    let delay = simulator.delay();
+    let mut scd = Scd30::from_i2c(Recorder::new(simulator, Vec::new()), delay);
+    scd.check_firmware().unwrap();
+    scd.trigger_cont_measurements().unwrap();
+    while !scd.get_data_ready().unwrap() {}
+    scd.i2c.inner_mut().inject_fault(Fault::BadCrc, 1);
+    assert!(matches!(
+        scd.get_measurements(),
+        Err(Scd30Error::ChecksumError)
+    ));
+    scd.i2c.inner_mut().inject_fault(Fault::NackWrite, 1);
+    assert!(scd.set_altitude(100).is_err());
+    let (recorder, _) = scd.release();
+    let (_, log) = recorder.into_inner();
+    String::from_utf8(log).unwrap()
+}
+
+#[test]
+fn records_every_frame() {
+    let log = record();
+    let frames: Vec<Frame> = log.lines().map(|l| Frame::parse(l).unwrap()).collect();
+    assert_eq!(frames[0].direction, Direction::Write);
+    assert_eq!(frames[0].bytes, [0xD1, 0x00]);
+    assert_eq!(frames[1].direction, Direction::Read);
+    assert_eq!(frames[1].bytes, [0x03, 0x42, 0xF3]);
+    assert!(frames.iter().all(|f| f.address == 0x61));
+    assert!(frames.windows(2).all(|w| w[0].time <= w[1].time));
+    let last = frames.last().unwrap();
+    assert_eq!(last.bytes[..2], [0x51, 0x02]);
+    assert_eq!(
+        last.error,
+        Some(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Data))
+    );
+    assert_eq!(frames.iter().filter(|f| f.error.is_some()).count(), 1);
+    // Lines are written back the same
+    for (line, frame) in log.lines().zip(&frames) {
+        assert_eq!(line, frame.to_string());
+    }
+}
+
+#[test]
+fn replays_the_same_results() {
+    let mut scd = Scd30::from_i2c(Replay::from_log(&record()).unwrap(), NoDelay);
+    assert_eq!(scd.check_firmware().unwrap(), 0x0342);
+    scd.trigger_cont_measurements().unwrap();
+    while !scd.get_data_ready().unwrap() {}
+    assert!(matches!(
+        scd.get_measurements(),
+        Err(Scd30Error::ChecksumError)
+    ));
+    match scd.set_altitude(100) {
+        Err(Scd30Error::ComunicationError {
+            command: 0x5102,
+            phase: Phase::Write,
+            source,
+        }) => assert_eq!(
+            source.kind(),
+            ErrorKind::NoAcknowledge(NoAcknowledgeSource::Data)
+        ),
+        other => panic!("unexpected {:?}", other),
+    }
+    assert_eq!(scd.i2c.remaining(), 0);
+    assert!(matches!(
+        scd.check_firmware(),
+        Err(Scd30Error::ComunicationError {
+            source: ReplayError::Exhausted,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn different_frames_are_reported() {
+    let mut scd = Scd30::from_i2c(Replay::from_log(&record()).unwrap(), NoDelay);
+    match scd.get_altitude() {
+        Err(Scd30Error::ComunicationError {
+            source: ReplayError::Mismatch { line, .. },
+            ..
+        }) => assert_eq!(line, 1),
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn invalid_log() {
+    let log = "# comment\n\n0.1 61 w d100\n0.2 61 x 00\n";
+    let error = Replay::from_log(log).unwrap_err();
+    assert!(error.to_string().contains("line 4"));
+    assert!(Replay::from_log("0.1 61 r 0342f3 lost").is_err());
+    assert_eq!(
+        Replay::from_log("0.1 61 r 0342f3 nack")
+            .unwrap()
+            .remaining(),
+        1
+    );
+}
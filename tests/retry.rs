@@ -0,0 +1,150 @@
+#![cfg(feature = "std")]
+
+use scd30_i2c::config::Scd30Config;
+use scd30_i2c::retry::{RetryPolicy, Retrying};
+use scd30_i2c::scd30::{Scd30, Scd30Error};
+use scd30_i2c::simulator::{Fault, Scd30Simulator, SimulatedDelay};
+use scd30_i2c::stream::RecoveryPolicy;
+use std::time::Duration;
+
+fn simulated(policy: RetryPolicy) -> Retrying<Scd30Simulator, SimulatedDelay> {
+    let simulator = Scd30Simulator::new();
+    let delay = simulator.delay();
+    Scd30::from_i2c(simulator, delay).with_retry(policy)
+}
+
+#[test]
+fn transient_errors_are_retried_with_backoff() {
+    let mut scd = simulated(RetryPolicy::default());
+    scd.device_mut().i2c.inject_fault(Fault::BadCrc, 2);
+    assert_eq!(scd.check_firmware().unwrap(), 0x0342);
+    assert_eq!(scd.retries(), 2);
+    assert_eq!(scd.recoveries(), 0);
+    // Three attempts of 3 ms plus 100 and 200 ms of backoff
+    assert_eq!(
+        scd.device_mut().delay.elapsed(),
+        Duration::from_millis(3 * 3 + 300)
+    );
+}
+
+#[test]
+fn error_kinds_can_be_excluded() {
+    let policy = RetryPolicy {
+        retry_checksum: false,
+        ..RetryPolicy::default()
+    };
+    let mut scd = simulated(policy);
+    scd.device_mut().i2c.inject_fault(Fault::BadCrc, 1);
+    assert!(matches!(
+        scd.check_firmware(),
+        Err(Scd30Error::ChecksumError)
+    ));
+    scd.device_mut().i2c.inject_fault(Fault::NackWrite, 1);
+    assert_eq!(scd.check_firmware().unwrap(), 0x0342);
+    assert_eq!(scd.retries(), 1);
+}
+
+#[test]
+fn invalid_values_are_not_retried() {
+    let mut scd = simulated(RetryPolicy::default());
+    assert!(matches!(
+        scd.set_measurements_interval(1),
+        Err(Scd30Error::OutOfRange { .. })
+    ));
+    assert_eq!(scd.retries(), 0);
+}
+
+#[test]
+fn repeated_failures_recover_the_device() {
+    let policy = RetryPolicy {
+        attempts: 2,
+        reset_after: Some(2),
+        ..RetryPolicy::default()
+    };
+    let mut scd = simulated(policy);
+    let config = Scd30Config {
+        measurement_interval: Some(5),
+        altitude: Some(250),
+        ..Scd30Config::default()
+    };
+    scd.apply_config(&config).unwrap();
+    scd.trigger_cont_measurements_with_pressure(980).unwrap();
+
+    scd.device_mut().i2c.inject_fault(Fault::NackRead, 4);
+    assert!(scd.run(|scd| scd.get_altitude()).is_err());
+    assert_eq!(scd.recoveries(), 0);
+    // Second failed operation resets the device and then succeeds
+    assert_eq!(scd.check_firmware().unwrap(), 0x0342);
+    assert_eq!(scd.recoveries(), 1);
+
+    let simulator = &scd.device_mut().i2c;
+    let commands = simulator.commands();
+    let reset = commands.iter().position(|&c| c == 0xD304).unwrap();
+    assert_eq!(commands[reset + 1..], [0x4600, 0x5102, 0x0010, 0xD100]);
+    assert!(simulator.is_measuring());
+    assert_eq!(simulator.pressure(), 980);
+    assert_eq!(simulator.altitude(), 250);
+}
+
+#[test]
+fn settings_written_through_the_wrapper_are_restored() {
+    let mut scd = simulated(RetryPolicy::default());
+    scd.set_altitude(300).unwrap();
+    scd.set_self_calibration(true).unwrap();
+    scd.recover().unwrap();
+
+    let commands = scd.device_mut().i2c.commands();
+    let reset = commands.iter().position(|&c| c == 0xD304).unwrap();
+    assert_eq!(commands[reset + 1..], [0x5102, 0x5306]);
+}
+
+#[test]
+fn no_policy() {
+    let mut scd = simulated(RetryPolicy::none());
+    scd.device_mut().i2c.inject_fault(Fault::NackWrite, 1);
+    assert!(scd.check_firmware().is_err());
+    assert_eq!(scd.check_firmware().unwrap(), 0x0342);
+    assert_eq!((scd.retries(), scd.recoveries()), (0, 0));
+}
+
+#[test]
+fn getters_are_retried() {
+    let mut scd = simulated(RetryPolicy::default());
+    scd.set_altitude(540).unwrap();
+    scd.set_temperature_offset_celsius(1.5).unwrap();
+    scd.set_force_recalibration_value(450).unwrap();
+    scd.set_self_calibration(true).unwrap();
+
+    scd.device_mut().i2c.inject_fault(Fault::BadCrc, 1);
+    assert_eq!(scd.get_measurements_interval().unwrap(), 2);
+    scd.device_mut().i2c.inject_fault(Fault::BadCrc, 1);
+    assert_eq!(scd.get_altitude().unwrap(), 540);
+    scd.device_mut().i2c.inject_fault(Fault::BadCrc, 1);
+    assert_eq!(scd.get_temperature_offset().unwrap(), 150);
+    scd.device_mut().i2c.inject_fault(Fault::BadCrc, 1);
+    assert_eq!(scd.get_temperature_offset_celsius().unwrap(), 1.5);
+    scd.device_mut().i2c.inject_fault(Fault::BadCrc, 1);
+    assert_eq!(scd.get_forced_value().unwrap(), 450);
+    scd.device_mut().i2c.inject_fault(Fault::BadCrc, 1);
+    assert!(scd.get_self_calibration_status().unwrap());
+    assert_eq!(scd.retries(), 6);
+}
+
+#[test]
+fn measurements_are_retried() {
+    let mut scd = simulated(RetryPolicy::default());
+    scd.device_mut().i2c.set_environment(800.0, 21.0, 50.0);
+    scd.trigger_cont_measurements().unwrap();
+    scd.device_mut().i2c.inject_fault(Fault::NackRead, 2);
+    let measurements: Vec<_> = scd
+        .measurements()
+        .with_policy(RecoveryPolicy::Stop)
+        .take(3)
+        .collect();
+    assert_eq!(measurements.len(), 3);
+    for measurement in measurements {
+        assert!((measurement.unwrap().co2_ppm - 800.0).abs() < 10.0);
+    }
+    assert_eq!(scd.retries(), 2);
+    assert_eq!(scd.recoveries(), 0);
+}
@@ -0,0 +1,284 @@
+#![cfg(feature = "std")]
+
+use embedded_hal::i2c::{ErrorKind, NoAcknowledgeSource};
+use scd30_i2c::scd30::{
+    self, BusErrorKind, Phase, Scd30, Scd30Error, Timing, BOOT_TIME, READ_DELAY,
+};
+use scd30_i2c::simulator::{Fault, Scd30Simulator, SimulatedDelay, SIMULATED_FIRMWARE};
+use std::error::Error;
+use std::time::{Duration, SystemTime};
+
+fn simulated() -> Scd30<Scd30Simulator, SimulatedDelay> {
+    let simulator = Scd30Simulator::new();
+    let delay = simulator.delay();
+    Scd30::from_i2c(simulator, delay)
+}
+
+#[test]
+fn crc8_matches_datasheet_example() {
+    assert_eq!(scd30::crc8(&[0xBE, 0xEF]), 0x92);
+    assert_eq!(scd30::crc8(&[0x00, 0x00]), 0x81);
+}
+
+#[test]
+fn reads_firmware() {
+    let mut scd = simulated();
+    assert_eq!(scd.check_firmware().unwrap(), SIMULATED_FIRMWARE);
+}
+
+#[test]
+fn start_and_stop_measurements() {
+    let mut scd = simulated();
+    scd.trigger_cont_measurements().unwrap();
+    assert!(scd.i2c.is_measuring());
+    assert_eq!(scd.i2c.pressure(), 0);
+    scd.stop_cont_measurements().unwrap();
+    assert!(!scd.i2c.is_measuring());
+    assert_eq!(scd.i2c.commands(), &[0x0010, 0x0104]);
+}
+
+#[test]
+fn pressure_compensation() {
+    let mut scd = simulated();
+    scd.trigger_cont_measurements_with_pressure(950).unwrap();
+    assert_eq!(scd.i2c.pressure(), 950);
+    scd.set_ambient_pressure(1013).unwrap();
+    assert_eq!(scd.i2c.pressure(), 1013);
+    match scd.set_ambient_pressure(600) {
+        Err(Scd30Error::OutOfRange { value, min, max }) => {
+            assert_eq!((value, min, max), (600, 700, 1400))
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+    assert_eq!(scd.i2c.pressure(), 1013);
+}
+
+#[test]
+fn measurements_interval() {
+    let mut scd = simulated();
+    assert_eq!(scd.get_measurements_interval().unwrap(), 2);
+    scd.set_measurements_interval(30).unwrap();
+    assert_eq!(scd.get_measurements_interval().unwrap(), 30);
+    assert!(matches!(
+        scd.set_measurements_interval(1),
+        Err(Scd30Error::OutOfRange { .. })
+    ));
+    assert!(matches!(
+        scd.set_measurements_interval(1801),
+        Err(Scd30Error::OutOfRange { .. })
+    ));
+    assert_eq!(scd.i2c.interval(), 30);
+}
+
+#[test]
+fn data_ready_and_measurements() {
+    let mut scd = simulated();
+    scd.i2c.set_environment(800.0, 21.0, 50.0);
+    scd.trigger_cont_measurements().unwrap();
+    assert!(!scd.get_data_ready().unwrap());
+    scd.i2c.advance(Duration::from_secs(2));
+    assert!(scd.get_data_ready().unwrap());
+    let measurement = scd.get_measurements().unwrap();
+    assert!((measurement.co2_ppm - 800.0).abs() < 10.0);
+    assert!((measurement.temperature_c - 21.0).abs() < 0.1);
+    assert!((measurement.humidity_pct - 50.0).abs() < 0.5);
+    let timestamp = measurement.timestamp().unwrap();
+    assert!(SystemTime::now().duration_since(timestamp).unwrap() < Duration::from_secs(60));
+    assert!(!scd.get_data_ready().unwrap());
+}
+
+#[test]
+fn self_calibration() {
+    let mut scd = simulated();
+    assert!(!scd.get_self_calibration_status().unwrap());
+    scd.set_self_calibration(true).unwrap();
+    assert!(scd.get_self_calibration_status().unwrap());
+    scd.set_self_calibration(false).unwrap();
+    assert!(!scd.get_self_calibration_status().unwrap());
+}
+
+#[test]
+fn altitude_and_temperature_offset() {
+    let mut scd = simulated();
+    scd.set_altitude(540).unwrap();
+    assert_eq!(scd.get_altitude().unwrap(), 540);
+    scd.set_temperature_offset(150).unwrap();
+    assert_eq!(scd.get_temperature_offset().unwrap(), 150);
+}
+
+#[test]
+fn temperature_offset_in_celsius() {
+    let mut scd = simulated();
+    scd.set_temperature_offset_celsius(1.234).unwrap();
+    assert_eq!(scd.i2c.temperature_offset(), 123);
+    assert_eq!(scd.get_temperature_offset_celsius().unwrap(), 1.23);
+    scd.set_temperature_offset_celsius(655.35).unwrap();
+    assert_eq!(scd.i2c.temperature_offset(), u16::MAX);
+    scd.set_temperature_offset_celsius(-0.004).unwrap();
+    assert_eq!(scd.i2c.temperature_offset(), 0);
+    match scd.set_temperature_offset_celsius(-0.5) {
+        Err(Scd30Error::OutOfRange { value, min, max }) => {
+            assert_eq!((value, min, max), (-50, 0, 65535))
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+    for offset_c in [700.0, f32::NAN, f32::INFINITY] {
+        assert!(matches!(
+            scd.set_temperature_offset_celsius(offset_c),
+            Err(Scd30Error::OutOfRange { .. })
+        ));
+    }
+    assert_eq!(scd.i2c.temperature_offset(), 0);
+}
+
+#[test]
+fn forced_recalibration() {
+    let mut scd = simulated();
+    scd.i2c.set_environment(700.0, 22.0, 40.0);
+    scd.trigger_cont_measurements().unwrap();
+    scd.set_force_recalibration_value(450).unwrap();
+    assert_eq!(scd.get_forced_value().unwrap(), 450);
+    scd.i2c.advance(Duration::from_secs(2));
+    let measurement = scd.get_measurements().unwrap();
+    assert!((measurement.co2_ppm - 450.0).abs() < 10.0);
+}
+
+#[test]
+fn forced_recalibration_value_out_of_range_is_not_sent() {
+    let mut scd = simulated();
+    for value in [0, 399, 2001, u16::MAX] {
+        assert!(matches!(
+            scd.set_force_recalibration_value(value),
+            Err(Scd30Error::OutOfRange {
+                min: 400,
+                max: 2000,
+                ..
+            })
+        ));
+    }
+    assert!(scd.i2c.commands().is_empty());
+    scd.set_force_recalibration_value(2000).unwrap();
+}
+
+#[test]
+fn soft_reset_keeps_configuration() {
+    let mut scd = simulated();
+    scd.set_altitude(100).unwrap();
+    scd.soft_reset().unwrap();
+    assert_eq!(scd.get_altitude().unwrap(), 100);
+}
+
+#[test]
+fn waits_only_before_reading() {
+    let mut scd = simulated();
+    scd.set_altitude(100).unwrap();
+    assert_eq!(scd.delay.elapsed(), Duration::ZERO);
+    assert_eq!(scd.get_altitude().unwrap(), 100);
+    assert_eq!(scd.delay.elapsed(), READ_DELAY);
+}
+
+#[test]
+fn soft_reset_waits_for_boot() {
+    let mut scd = simulated();
+    scd.soft_reset().unwrap();
+    assert_eq!(scd.delay.elapsed(), BOOT_TIME);
+    assert_eq!(scd.check_firmware().unwrap(), SIMULATED_FIRMWARE);
+}
+
+#[test]
+fn reading_too_early_is_not_acknowledged() {
+    let mut scd = simulated().with_timing(Timing {
+        read_delay: Duration::from_millis(1),
+        ..Timing::default()
+    });
+    assert!(matches!(
+        scd.check_firmware(),
+        Err(Scd30Error::ComunicationError {
+            phase: Phase::Read,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn relaxed_timing() {
+    let mut scd = simulated().with_timing(Timing::relaxed());
+    scd.set_altitude(100).unwrap();
+    assert_eq!(scd.get_altitude().unwrap(), 100);
+    assert_eq!(scd.delay.elapsed(), Duration::from_millis(60));
+}
+
+#[test]
+fn checksum_error() {
+    let mut scd = simulated();
+    scd.i2c.inject_fault(Fault::BadCrc, 1);
+    assert!(matches!(
+        scd.check_firmware(),
+        Err(Scd30Error::ChecksumError)
+    ));
+    assert!(scd.check_firmware().is_ok());
+}
+
+#[test]
+fn communication_error_keeps_source() {
+    let mut scd = simulated();
+    scd.i2c.inject_fault(Fault::NackWrite, 1);
+    let error = scd.get_altitude().unwrap_err();
+    match error {
+        Scd30Error::ComunicationError {
+            command,
+            phase,
+            ref source,
+        } => {
+            assert_eq!(command, 0x5102);
+            assert_eq!(phase, Phase::Write);
+            assert_eq!(
+                source.0,
+                ErrorKind::NoAcknowledge(NoAcknowledgeSource::Data)
+            );
+        }
+        ref other => panic!("unexpected {:?}", other),
+    }
+    assert!(error.source().is_some());
+
+    scd.i2c.inject_fault(Fault::NackRead, 1);
+    assert!(matches!(
+        scd.get_measurements_interval(),
+        Err(Scd30Error::ComunicationError {
+            command: 0x4600,
+            phase: Phase::Read,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn default_error_is_boxed_with_its_source() {
+    fn read() -> Result<u16, Box<dyn Error>> {
+        let error: Scd30Error = Scd30Error::ComunicationError {
+            command: 0x0300,
+            phase: Phase::Read,
+            source: BusErrorKind(ErrorKind::Bus),
+        };
+        Err(error)?
+    }
+    let error = read().unwrap_err();
+    assert_eq!(
+        error.source().unwrap().to_string(),
+        ErrorKind::Bus.to_string()
+    );
+}
+
+#[test]
+fn wrong_address_is_not_acknowledged() {
+    let simulator = Scd30Simulator::new();
+    let delay = simulator.delay();
+    let mut scd = Scd30::from_i2c_with_address(simulator, delay, 0x62);
+    assert!(matches!(
+        scd.check_firmware(),
+        Err(Scd30Error::ComunicationError {
+            phase: Phase::Write,
+            ..
+        })
+    ));
+}
@@ -0,0 +1,170 @@
+use scd30_i2c::sensirion_frame::{
+    self, crc8, decode_f32, decode_word, decode_words, encode_command,
+    encode_command_with_argument, encode_command_with_arguments, encode_words, f32_from_words,
+    FrameError, COMMAND_LEN, WORD_LEN,
+};
+
+#[test]
+fn crc8_matches_datasheet_examples() {
+    assert_eq!(crc8(&[0xBE, 0xEF]), 0x92);
+    assert_eq!(crc8(&[0x00, 0x00]), 0x81);
+    assert_eq!(crc8(&[0x00, 0x02]), 0xE3);
+    assert_eq!(crc8(&[]), sensirion_frame::CRC8_INIT);
+}
+
+#[test]
+fn frame_lengths() {
+    assert_eq!(COMMAND_LEN, 2);
+    assert_eq!(WORD_LEN, 3);
+    assert_eq!(sensirion_frame::frame_len(0), 0);
+    assert_eq!(sensirion_frame::frame_len(6), 18);
+}
+
+#[test]
+fn encodes_commands() {
+    assert_eq!(encode_command(0x0104), [0x01, 0x04]);
+    // Set measurement interval to 2 seconds, from the interface description
+    assert_eq!(
+        encode_command_with_argument(0x4600, 2),
+        [0x46, 0x00, 0x00, 0x02, 0xE3]
+    );
+}
+
+#[test]
+fn encodes_commands_with_several_arguments() {
+    let mut buffer = [0; 10];
+    let len = encode_command_with_arguments(0x0010, &[0xBEEF, 0x0000], &mut buffer).unwrap();
+    assert_eq!(len, 8);
+    assert_eq!(
+        buffer[..len],
+        [0x00, 0x10, 0xBE, 0xEF, 0x92, 0x00, 0x00, 0x81]
+    );
+    // Untouched after the frame
+    assert_eq!(buffer[len..], [0, 0]);
+
+    let len = encode_command_with_arguments(0xD100, &[], &mut buffer).unwrap();
+    assert_eq!(buffer[..len], [0xD1, 0x00]);
+}
+
+#[test]
+fn encoding_in_a_short_buffer_fails() {
+    let mut buffer = [0xAA; 6];
+    assert_eq!(
+        encode_command_with_arguments(0x0010, &[1, 2], &mut buffer),
+        Err(FrameError::TooShort {
+            expected: 8,
+            actual: 6
+        })
+    );
+    assert_eq!(
+        encode_words(&[1, 2, 3], &mut buffer),
+        Err(FrameError::TooShort {
+            expected: 9,
+            actual: 6
+        })
+    );
+    // Nothing written on failure
+    assert_eq!(buffer, [0xAA; 6]);
+}
+
+#[test]
+fn encodes_and_decodes_words() {
+    let words = [0x0000, 0xBEEF, 0x0342, 0xFFFF];
+    let mut buffer = [0; 12];
+    assert_eq!(encode_words(&words, &mut buffer).unwrap(), 12);
+    assert_eq!(buffer[3..6], [0xBE, 0xEF, 0x92]);
+
+    let mut decoded = [0; 4];
+    decode_words(&buffer, &mut decoded).unwrap();
+    assert_eq!(decoded, words);
+    assert_eq!(decode_word(&buffer[3..6]).unwrap(), 0xBEEF);
+}
+
+#[test]
+fn decoding_ignores_trailing_bytes() {
+    let bytes = [0xBE, 0xEF, 0x92, 0xFF, 0xFF, 0xFF];
+    assert_eq!(decode_word(&bytes).unwrap(), 0xBEEF);
+    decode_words(&bytes, &mut []).unwrap();
+}
+
+#[test]
+fn checksum_error_reports_the_word() {
+    let mut buffer = [0; 9];
+    encode_words(&[1, 2, 3], &mut buffer).unwrap();
+    let expected = buffer[8];
+    buffer[8] ^= 0xFF;
+    let mut words = [0; 3];
+    assert_eq!(
+        decode_words(&buffer, &mut words),
+        Err(FrameError::Checksum {
+            word: 2,
+            expected,
+            actual: expected ^ 0xFF
+        })
+    );
+
+    // Corrupted data is detected too, on the first word that fails
+    buffer[8] = expected;
+    buffer[3] ^= 0x01;
+    assert!(matches!(
+        decode_words(&buffer, &mut words),
+        Err(FrameError::Checksum { word: 1, .. })
+    ));
+}
+
+#[test]
+fn short_response_reports_missing_bytes() {
+    let error = decode_words(&[0xBE, 0xEF, 0x92, 0x00], &mut [0; 2]).unwrap_err();
+    assert_eq!(
+        error,
+        FrameError::TooShort {
+            expected: 6,
+            actual: 4
+        }
+    );
+    assert_eq!(error.missing(), 2);
+    assert_eq!(decode_word(&[]).unwrap_err().missing(), 3);
+    assert_eq!(
+        FrameError::Checksum {
+            word: 0,
+            expected: 0,
+            actual: 1
+        }
+        .missing(),
+        0
+    );
+}
+
+#[test]
+fn decodes_floats() {
+    // CO2 concentration of 439 ppm, from the interface description
+    let bytes = [0x43, 0xDB, 0xCB, 0x8C, 0x2E, 0x8F];
+    let co2 = decode_f32(&bytes).unwrap();
+    assert!((co2 - 439.09).abs() < 0.01);
+    assert_eq!(co2, f32_from_words(0x43DB, 0x8C2E));
+    assert!(matches!(
+        decode_f32(&bytes[..3]),
+        Err(FrameError::TooShort { .. })
+    ));
+}
+
+#[test]
+fn error_messages() {
+    assert_eq!(
+        FrameError::Checksum {
+            word: 1,
+            expected: 0x92,
+            actual: 0x00
+        }
+        .to_string(),
+        "Checksum error in word 1, expected 0x92 but found 0x00"
+    );
+    assert_eq!(
+        FrameError::TooShort {
+            expected: 6,
+            actual: 4
+        }
+        .to_string(),
+        "Frame is 2 bytes short, expected 6 bytes but found 4"
+    );
+}
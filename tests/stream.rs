@@ -0,0 +1,77 @@
+#![cfg(feature = "std")]
+
+use scd30_i2c::scd30::{Scd30, Scd30Error};
+use scd30_i2c::simulator::{Fault, Scd30Simulator, SimulatedDelay};
+use scd30_i2c::stream::RecoveryPolicy;
+use std::time::Duration;
+
+fn simulated() -> Scd30<Scd30Simulator, SimulatedDelay> {
+    let simulator = Scd30Simulator::new();
+    let delay = simulator.delay();
+    Scd30::from_i2c(simulator, delay)
+}
+
+fn reads(scd: &Scd30<Scd30Simulator, SimulatedDelay>) -> usize {
+    scd.i2c.commands().iter().filter(|&&c| c == 0x0300).count()
+}
+
+#[test]
+fn yields_one_measurement_per_interval() {
+    let mut scd = simulated();
+    scd.set_measurements_interval(5).unwrap();
+    scd.trigger_cont_measurements().unwrap();
+    let measurements: Vec<_> = scd.measurements().take(3).collect();
+    assert!(measurements.iter().all(|m| m.is_ok()));
+    assert_eq!(reads(&scd), 3);
+    let elapsed = scd.delay.elapsed();
+    assert!(elapsed >= Duration::from_secs(15));
+    assert!(elapsed < Duration::from_secs(17));
+}
+
+#[test]
+fn consecutive_measurements_are_different_samples() {
+    let mut scd = simulated();
+    scd.trigger_cont_measurements().unwrap();
+    let measurements: Vec<_> = scd.measurements().take(2).map(|m| m.unwrap()).collect();
+    assert_ne!(measurements[0].co2_ppm, measurements[1].co2_ppm);
+}
+
+#[test]
+fn timeout_when_not_measuring() {
+    let mut scd = simulated();
+    let mut measurements = scd.measurements().with_policy(RecoveryPolicy::Stop);
+    assert!(matches!(
+        measurements.next(),
+        Some(Err(Scd30Error::Timeout))
+    ));
+    assert!(measurements.next().is_none());
+}
+
+#[test]
+fn retry_hides_transient_errors() {
+    let mut scd = simulated();
+    scd.trigger_cont_measurements().unwrap();
+    scd.i2c.inject_fault(Fault::BadCrc, 2);
+    let policy = RecoveryPolicy::Retry {
+        attempts: 3,
+        wait: Duration::from_millis(100),
+    };
+    let measurement = scd.measurements().with_policy(policy).next().unwrap();
+    assert!(measurement.is_ok());
+}
+
+#[test]
+fn continue_yields_errors_and_recovers() {
+    let mut scd = simulated();
+    scd.trigger_cont_measurements().unwrap();
+    scd.i2c.inject_fault(Fault::NackWrite, 1);
+    let policy = RecoveryPolicy::Continue {
+        wait: Duration::from_secs(1),
+    };
+    let mut measurements = scd.measurements().with_policy(policy);
+    assert!(matches!(
+        measurements.next(),
+        Some(Err(Scd30Error::ComunicationError { .. }))
+    ));
+    assert!(matches!(measurements.next(), Some(Ok(_))));
+}
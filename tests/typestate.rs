@@ -0,0 +1,107 @@
+#![cfg(feature = "std")]
+
+use scd30_i2c::config::{ConfigError, Scd30Config};
+use scd30_i2c::scd30::{self, Scd30Error};
+use scd30_i2c::simulator::{Fault, Scd30Simulator, SimulatedDelay};
+use scd30_i2c::typestate::{Idle, Scd30};
+
+fn simulated() -> Scd30<Idle, Scd30Simulator, SimulatedDelay> {
+    let simulator = Scd30Simulator::new();
+    let delay = simulator.delay();
+    Scd30::stopped(scd30::Scd30::from_i2c(simulator, delay)).unwrap()
+}
+
+#[test]
+fn configure_start_read_and_stop() {
+    let mut scd = simulated();
+    scd.set_measurements_interval(5).unwrap();
+    scd.set_altitude(200).unwrap();
+    let mut scd = scd.start_with_pressure(990).unwrap();
+    let measurements: Vec<_> = scd.measurements().take(2).collect();
+    assert!(measurements.iter().all(|m| m.is_ok()));
+    assert!(scd.get_data_ready().is_ok());
+
+    let scd = scd.stop().unwrap();
+    let device = scd.into_inner();
+    assert!(!device.i2c.is_measuring());
+    assert_eq!(device.i2c.pressure(), 990);
+    assert_eq!(device.i2c.interval(), 5);
+    assert_eq!(device.i2c.altitude(), 200);
+}
+
+#[test]
+fn temperature_offset_in_both_modes() {
+    let mut scd = simulated();
+    scd.set_temperature_offset_celsius(1.5).unwrap();
+    let mut scd = scd.start().unwrap();
+    assert_eq!(scd.get_temperature_offset_celsius().unwrap(), 1.5);
+    assert_eq!(scd.get_temperature_offset().unwrap(), 150);
+}
+
+#[test]
+fn configuration_in_both_modes() {
+    let mut scd = simulated();
+    let config = Scd30Config {
+        altitude: Some(300),
+        forced_recalibration: Some(450),
+        ..Scd30Config::default()
+    };
+    assert!(matches!(
+        scd.apply_config(&config),
+        Err(ConfigError::RecalibrationNotAllowed)
+    ));
+
+    let mut scd = scd.start().unwrap();
+    scd.apply_config_with_recalibration(&config).unwrap();
+    assert_eq!(scd.read_config().unwrap().altitude, Some(300));
+    assert_eq!(scd.get_forced_value().unwrap(), 450);
+}
+
+#[test]
+fn stopped_stops_a_running_device() {
+    let simulator = Scd30Simulator::new();
+    let delay = simulator.delay();
+    let mut device = scd30::Scd30::from_i2c(simulator, delay);
+    device.trigger_cont_measurements().unwrap();
+    let scd = Scd30::stopped(device).unwrap();
+    assert!(!scd.into_inner().i2c.is_measuring());
+}
+
+#[test]
+fn failed_transition_gives_the_device_back() {
+    let mut scd = simulated();
+    scd.i2c_mut().inject_fault(Fault::NackWrite, 1);
+    let error = match scd.start() {
+        Ok(_) => panic!("start should fail"),
+        Err(error) => error,
+    };
+    assert!(matches!(
+        error.error,
+        Scd30Error::ComunicationError {
+            command: 0x0010,
+            ..
+        }
+    ));
+
+    // Still idle, can be started again
+    let mut scd = error.device.start().unwrap();
+    scd.i2c_mut().inject_fault(Fault::NackWrite, 1);
+    let mut scd = match scd.stop() {
+        Ok(_) => panic!("stop should fail"),
+        Err(error) => error.device,
+    };
+    assert!(scd.get_measurements().is_ok());
+    assert!(scd.stop().is_ok());
+}
+
+#[cfg(feature = "linux")]
+#[test]
+fn missing_bus_is_an_io_error() {
+    match Scd30::open("/dev/i2c-scd30-missing", scd30::DEFAULT_ADDRESS) {
+        Err(Scd30Error::Io(e)) => {
+            assert_eq!(e.get_ref().kind(), std::io::ErrorKind::NotFound)
+        }
+        Err(e) => panic!("unexpected {:?}", e),
+        Ok(_) => panic!("bus opened"),
+    }
+}